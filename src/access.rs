@@ -0,0 +1,305 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+use mlua::prelude::*;
+
+use crate::value::ContextValue;
+
+// ============================================================================
+// CONTEXT ACCESS TRACKING
+// ============================================================================
+// EventContext lives in the event_chains crate, so its get/set cannot be
+// instrumented; tracking covers the Lua path only.
+//
+// When enabled, Lua handlers and middleware receive an empty proxy table
+// whose metatable forwards every read and write to the real context and
+// records the key. `pairs(ctx)` is forwarded but not recorded, and native
+// events (which copy the whole context) are not recorded either.
+//
+// The same proxy, marked frozen, backs the runner's `immutable_context` mode:
+// its `__newindex` raises instead of forwarding, so assigning to the context
+// (even to a key that exists) fails, and `getmetatable` returns false so the
+// real table cannot be reached. `rawset` on the proxy bypasses the check but
+// only writes to the empty proxy, never to the real context.
+//
+// A proxy can also carry an event's KeyPolicy (its `allowed_keys` and
+// `denied_keys`). Reading or assigning a key the policy refuses raises, and
+// `pairs(ctx)` skips such keys, so the handler cannot observe them at all.
+// Non-string keys are not subject to the policy. The runner closes the other
+// ways to the real table while such a handler runs (the `__context` global
+// and the `chain` helpers that take the context); see runner.rs.
+//
+// Validators registered with `LuaChainRunner::with_validator` are checked by
+// the same `__newindex`: an assignment whose value they reject raises at the
+// assignment, and the rejection is kept so the runner can report it as a
+// validation failure rather than a plain event error. Values with no owned
+// form (tables, functions) and nil are not validated.
+
+const TARGET_FIELD: &str = "__target";
+const FROZEN_FIELD: &str = "__frozen";
+const POLICY_FIELD: &str = "__policy";
+
+/// Which context keys an event's Lua code may read and write.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPolicy {
+    /// Only these keys are accessible, if set
+    pub allowed: Option<HashSet<String>>,
+    /// Never accessible, even if also allowed
+    pub denied: HashSet<String>,
+}
+
+impl KeyPolicy {
+    pub fn permits(&self, key: &str) -> bool {
+        !self.denied.contains(key) && self.allowed.as_ref().is_none_or(|allowed| allowed.contains(key))
+    }
+}
+
+impl LuaUserData for KeyPolicy {}
+
+/// Keys read and written by Lua handlers and middleware during one run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessReport {
+    pub read: BTreeSet<String>,
+    pub written: BTreeSet<String>,
+}
+
+impl AccessReport {
+    /// Keys that were written but never read: candidates for removal.
+    pub fn unused(&self) -> impl Iterator<Item = &String> {
+        self.written.difference(&self.read)
+    }
+
+    /// Keys that were read but never written during the run, i.e. values
+    /// the chain expects the initial context to provide.
+    pub fn implicit_inputs(&self) -> impl Iterator<Item = &String> {
+        self.read.difference(&self.written)
+    }
+}
+
+/// What a trial run predicts about the context, from
+/// `LuaChainRunner::preview_context`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreviewResult {
+    /// Keys each event read and wrote, in declaration order.
+    pub events: Vec<(String, AccessReport)>,
+    /// Keys present once the last event has run.
+    pub final_keys: BTreeSet<String>,
+    /// One line per key an event read while it was absent from the context.
+    pub warnings: Vec<String>,
+}
+
+/// Checks a value about to be stored under a context key.
+pub type ValidatorFn = Box<dyn Fn(&ContextValue) -> Result<(), String>>;
+
+type Validators = Rc<RefCell<HashMap<String, Vec<ValidatorFn>>>>;
+
+/// The metamethods shared by every proxy of one runner.
+pub(crate) struct AccessTracker {
+    report: Rc<RefCell<AccessReport>>,
+    validators: Validators,
+    // Key and message of the last assignment a validator rejected
+    violation: Rc<RefCell<Option<(String, String)>>>,
+    index: LuaRegistryKey,
+    newindex: LuaRegistryKey,
+    pairs: LuaRegistryKey,
+}
+
+impl AccessTracker {
+    pub(crate) fn new(lua: &Lua) -> LuaResult<Self> {
+        let report = Rc::new(RefCell::new(AccessReport::default()));
+
+        let log = report.clone();
+        let index = lua.create_function(move |_, (proxy, key): (LuaTable, LuaValue)| {
+            if let LuaValue::String(name) = &key {
+                let name = name.to_string_lossy();
+                check_policy(&proxy, &name, "read")?;
+                log.borrow_mut().read.insert(name.to_string());
+            }
+            target_of(&proxy)?.get::<_, LuaValue>(key)
+        })?;
+
+        let validators: Validators = Rc::default();
+        let violation = Rc::new(RefCell::new(None));
+
+        let log = report.clone();
+        let (checks, rejected) = (validators.clone(), violation.clone());
+        let newindex =
+            lua.create_function(move |_, (proxy, key, value): (LuaTable, LuaValue, LuaValue)| {
+                if is_frozen(&proxy)? {
+                    return Err(LuaError::RuntimeError(
+                        "context is immutable; return a table of changes instead".to_string(),
+                    ));
+                }
+                if let LuaValue::String(name) = &key {
+                    let name = name.to_string_lossy();
+                    check_policy(&proxy, &name, "write")?;
+                    if let Some(owned) = ContextValue::from_lua(&value)
+                        && let Err(message) = validate(&checks, &name, &owned)
+                    {
+                        let error = format!("context key '{}' failed validation: {}", name, message);
+                        *rejected.borrow_mut() = Some((name.to_string(), message));
+                        return Err(LuaError::RuntimeError(error));
+                    }
+                    log.borrow_mut().written.insert(name.to_string());
+                }
+                target_of(&proxy)?.set(key, value)
+            })?;
+
+        let pairs = lua.create_function(|lua, proxy: LuaTable| {
+            let target = target_of(&proxy)?;
+            let Some(policy) = policy_of(&proxy)? else {
+                let next: LuaFunction = lua.globals().get("next")?;
+                return Ok((next, LuaValue::Table(target)));
+            };
+            // Like `next` over the real context, minus refused keys
+            let raw_next: LuaFunction = lua.globals().get("next")?;
+            let raw_next = lua.create_registry_value(raw_next)?;
+            let next = lua.create_function(move |lua, (target, mut key): (LuaTable, LuaValue)| loop {
+                let raw_next: LuaFunction = lua.registry_value(&raw_next)?;
+                let (found, value): (LuaValue, LuaValue) = raw_next.call((target.clone(), key))?;
+                let refused = match &found {
+                    LuaValue::String(name) => !policy.permits(&name.to_string_lossy()),
+                    _ => false,
+                };
+                if !refused {
+                    return Ok((found, value));
+                }
+                key = found;
+            })?;
+            Ok((next, LuaValue::Table(target)))
+        })?;
+
+        Ok(Self {
+            report,
+            validators,
+            violation,
+            index: lua.create_registry_value(index)?,
+            newindex: lua.create_registry_value(newindex)?,
+            pairs: lua.create_registry_value(pairs)?,
+        })
+    }
+
+    /// Forget the keys recorded by the previous run.
+    pub(crate) fn reset(&self) {
+        *self.report.borrow_mut() = AccessReport::default();
+    }
+
+    pub(crate) fn report(&self) -> AccessReport {
+        self.report.borrow().clone()
+    }
+
+    pub(crate) fn add_validator(&self, key: &str, validator: ValidatorFn) {
+        self.validators.borrow_mut().entry(key.to_string()).or_default().push(validator);
+    }
+
+    pub(crate) fn has_validators(&self) -> bool {
+        !self.validators.borrow().is_empty()
+    }
+
+    pub(crate) fn validated_keys(&self) -> Vec<String> {
+        self.validators.borrow().keys().cloned().collect()
+    }
+
+    pub(crate) fn validate(&self, key: &str, value: &ContextValue) -> Result<(), String> {
+        validate(&self.validators, key, value)
+    }
+
+    /// The rejection recorded since the last call, if any.
+    pub(crate) fn take_violation(&self) -> Option<(String, String)> {
+        self.violation.borrow_mut().take()
+    }
+
+    /// Wrap `target` in a recording proxy, read-only if `frozen` and
+    /// limited to the keys `policy` permits, if any.
+    pub(crate) fn proxy<'lua>(
+        &self,
+        lua: &'lua Lua,
+        target: LuaTable<'lua>,
+        frozen: bool,
+        policy: Option<&KeyPolicy>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        let metatable = lua.create_table_with_capacity(0, 6)?;
+        metatable.raw_set("__index", lua.registry_value::<LuaFunction>(&self.index)?)?;
+        metatable.raw_set("__newindex", lua.registry_value::<LuaFunction>(&self.newindex)?)?;
+        metatable.raw_set("__pairs", lua.registry_value::<LuaFunction>(&self.pairs)?)?;
+        metatable.raw_set(TARGET_FIELD, target)?;
+        if let Some(policy) = policy {
+            metatable.raw_set(POLICY_FIELD, policy.clone())?;
+        }
+        if frozen || policy.is_some() {
+            // Hide the metatable (and the real table in it) from getmetatable
+            metatable.raw_set("__metatable", false)?;
+        }
+        if frozen {
+            metatable.raw_set(FROZEN_FIELD, true)?;
+        }
+
+        let proxy = lua.create_table()?;
+        proxy.set_metatable(Some(metatable));
+        Ok(proxy)
+    }
+}
+
+/// The real context behind `table` if it is a proxy, otherwise `table`.
+pub(crate) fn unwrap(table: LuaTable) -> LuaResult<LuaTable> {
+    match table.get_metatable() {
+        Some(metatable) => match metatable.raw_get::<_, Option<LuaTable>>(TARGET_FIELD)? {
+            Some(target) => Ok(target),
+            None => Ok(table),
+        },
+        None => Ok(table),
+    }
+}
+
+pub(crate) fn is_frozen(proxy: &LuaTable) -> LuaResult<bool> {
+    match proxy.get_metatable() {
+        Some(metatable) => Ok(metatable.raw_get::<_, Option<bool>>(FROZEN_FIELD)?.unwrap_or(false)),
+        None => Ok(false),
+    }
+}
+
+fn policy_of(proxy: &LuaTable) -> LuaResult<Option<KeyPolicy>> {
+    match proxy.get_metatable() {
+        Some(metatable) => match metatable.raw_get::<_, Option<LuaAnyUserData>>(POLICY_FIELD)? {
+            Some(policy) => Ok(Some(policy.borrow::<KeyPolicy>()?.clone())),
+            None => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Whether `table` is a proxy carrying a KeyPolicy.
+pub(crate) fn has_policy(table: &LuaTable) -> LuaResult<bool> {
+    match table.get_metatable() {
+        Some(metatable) => Ok(metatable.raw_get::<_, Option<LuaAnyUserData>>(POLICY_FIELD)?.is_some()),
+        None => Ok(false),
+    }
+}
+
+/// Fail unless the policy of `proxy`, if any, permits `access` ("read" or
+/// "write") to `key`.
+pub(crate) fn check_policy(proxy: &LuaTable, key: &str, access: &str) -> LuaResult<()> {
+    match proxy.get_metatable() {
+        Some(metatable) => match metatable.raw_get::<_, Option<LuaAnyUserData>>(POLICY_FIELD)? {
+            Some(policy) if !policy.borrow::<KeyPolicy>()?.permits(key) => Err(LuaError::RuntimeError(
+                format!("this event may not {} context key '{}'", access, key),
+            )),
+            _ => Ok(()),
+        },
+        None => Ok(()),
+    }
+}
+
+fn validate(validators: &Validators, key: &str, value: &ContextValue) -> Result<(), String> {
+    match validators.borrow().get(key) {
+        Some(checks) => checks.iter().try_for_each(|check| check(value)),
+        None => Ok(()),
+    }
+}
+
+fn target_of<'lua>(proxy: &LuaTable<'lua>) -> LuaResult<LuaTable<'lua>> {
+    proxy
+        .get_metatable()
+        .ok_or_else(|| LuaError::RuntimeError("context proxy has no metatable".to_string()))?
+        .raw_get(TARGET_FIELD)
+}
@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
+
+use crate::runner::{new_run_id, LuaChainRunner};
+use crate::value::{ContextMap, ContextValue};
+
+// ============================================================================
+// LUA EVENTS ON THE NATIVE EXECUTOR
+// ============================================================================
+// `into_event_chain` turns a runner into an EventChain of LuaEvents, one per
+// Lua event, so native middleware, fault tolerance and ChainResult apply.
+//
+// ChainableEvent must be Send + Sync, but a runner owns an `Rc<Lua>`, so it
+// is parked in a thread-local table and each LuaEvent refers to it by ID.
+// The chain can only run on the thread that built it; elsewhere (e.g. under
+// `execute_parallel`) every LuaEvent fails. The runner is dropped when the
+// last LuaEvent is dropped on that thread, or when the thread exits.
+//
+// What carries over, per event: the handler, its args, on_error, return
+// mode, the Lua middleware stack (which still wraps each event), and the
+// chain helpers. What does not: anything that spans a run — finalize,
+// cleanup tags, once, TTLs, checkpoints, stats, timeouts, weighted_random —
+// and `chain.abort_chain()` only fails its own event.
+//
+// The Lua context travels between events as a ContextMap stored in the
+// EventContext under "__lua_context", seeded from the definition's context
+// and the runner's defaults. Before each Lua event, keys of that map that
+// the EventContext also holds are refreshed from it, so native events (and
+// the caller) can change them; after it, every scalar key is also written
+// to the EventContext under its natural type. Keys only set natively never
+// reach Lua, and non-scalar values (tables, userdata) do not survive
+// between events.
+//
+// The Lua events of one EventContext share a run ID, generated by the first
+// of them and kept under "__lua_run_id", which `chain.run_id()` returns.
+
+const LUA_CONTEXT_KEY: &str = "__lua_context";
+const LUA_RUN_ID_KEY: &str = "__lua_run_id";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static PARKED: RefCell<HashMap<u64, Rc<LuaChainRunner>>> = RefCell::new(HashMap::new());
+}
+
+// Removes the parked runner once the last event referring to it is dropped
+struct Parked {
+    id: u64,
+    thread: ThreadId,
+}
+
+impl Drop for Parked {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // Taken out first: the runner's own drop may run Lua code
+            let runner = PARKED.try_with(|parked| parked.borrow_mut().remove(&self.id));
+            drop(runner);
+        }
+    }
+}
+
+/// One event of a parked [`LuaChainRunner`], for the native executor.
+pub struct LuaEvent {
+    parked: Arc<Parked>,
+    index: usize,
+    name: String,
+}
+
+impl ChainableEvent for LuaEvent {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let runner = PARKED.with(|parked| parked.borrow().get(&self.parked.id).cloned());
+        let Some(runner) = runner else {
+            return EventResult::Failure("Lua runner is not available on this thread".to_string());
+        };
+
+        let mut map = match context.get::<ContextMap>(LUA_CONTEXT_KEY) {
+            Some(map) => map,
+            None => match runner.initial_map() {
+                Ok(map) => map,
+                Err(e) => return EventResult::Failure(e.to_string()),
+            },
+        };
+        for (key, value) in map.iter_mut() {
+            if let Some(current) = ContextValue::read(context, key) {
+                *value = current;
+            }
+        }
+
+        let run_id = context.get::<String>(LUA_RUN_ID_KEY).unwrap_or_else(|| {
+            let run_id = new_run_id();
+            context.set(LUA_RUN_ID_KEY, run_id.clone());
+            run_id
+        });
+
+        match runner.execute_event_map(self.index, &map, &run_id) {
+            Ok(updated) => {
+                for (key, value) in &updated {
+                    value.clone().write(context, key);
+                }
+                context.set(LUA_CONTEXT_KEY, updated);
+                EventResult::Success(())
+            }
+            Err(e) => EventResult::Failure(e.to_string()),
+        }
+    }
+
+    fn name(&self) -> &str { &self.name }
+}
+
+impl LuaChainRunner {
+    /// Hand the runner's events to the native executor, one [`LuaEvent`]
+    /// per Lua event, in order. See the module comment for what runs.
+    pub fn into_event_chain(self) -> EventChain {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let parked = Arc::new(Parked { id, thread: thread::current().id() });
+        let mut chain = EventChain::new();
+        for (index, name) in self.event_names().iter().enumerate() {
+            chain = chain.event(LuaEvent { parked: parked.clone(), index, name: name.clone() });
+        }
+        // With no events nothing would ever unpark it
+        if Arc::strong_count(&parked) > 1 {
+            PARKED.with(|runners| runners.borrow_mut().insert(id, Rc::new(self)));
+        }
+        chain
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// CIRCUIT BREAKERS
+// ============================================================================
+// An event declared with
+//
+//   circuit_breaker = { threshold = 3, cooldown_ms = 30000 }
+//
+// gets a breaker that lives as long as the runner, so its state carries over
+// from one execute to the next. Each time the handler raises, the breaker
+// counts a failure; a success resets the count. Once `threshold` failures in
+// a row are reached the breaker opens: for `cooldown_ms` the handler is not
+// called at all and the event fails at once with "circuit breaker open",
+// which goes through the event's `on_error` like any failure, so `on_error`
+// can supply the fast-fail context. After the cooldown one attempt is let
+// through (half-open): success closes the breaker, failure reopens it for
+// another cooldown.
+//
+// The breaker sits directly around the handler, inside the middleware
+// stack, so middleware still runs for short-circuited attempts. Aborts are
+// not failures, and `on_error` recovering from a failure does not hide it
+// from the breaker.
+
+/// Where a breaker stands; see [`LuaChainRunner::breaker_state`](crate::LuaChainRunner::breaker_state).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Calls go through
+    Closed,
+    /// Calls fail at once until the cooldown ends
+    Open,
+    /// The cooldown has ended; the next call is a trial
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: Cell<u32>,
+    opened_at: Cell<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold, cooldown, failures: Cell::new(0), opened_at: Cell::new(None) }
+    }
+
+    pub(crate) fn state(&self) -> BreakerState {
+        match self.opened_at.get() {
+            None => BreakerState::Closed,
+            Some(opened) if opened.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    pub(crate) fn threshold(&self) -> u32 { self.threshold }
+
+    pub(crate) fn cooldown(&self) -> Duration { self.cooldown }
+
+    /// Whether the handler may be called now.
+    pub(crate) fn allows(&self) -> bool { self.state() != BreakerState::Open }
+
+    pub(crate) fn record(&self, succeeded: bool) {
+        if succeeded {
+            self.reset();
+            return;
+        }
+        let failures = self.failures.get().saturating_add(1);
+        self.failures.set(failures);
+        if failures >= self.threshold {
+            self.opened_at.set(Some(Instant::now()));
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.failures.set(0);
+        self.opened_at.set(None);
+    }
+}
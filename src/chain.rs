@@ -0,0 +1,444 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use event_chains::core::chain_result::ChainResult;
+use event_chains::{
+    ChainableEvent, EventChain, EventContext, EventMiddleware, EventResult, FaultToleranceMode,
+};
+
+use crate::error::ChainError;
+use crate::value::ContextValue;
+
+// ============================================================================
+// NATIVE CHAIN EXTENSIONS
+// ============================================================================
+// EventChain and ChainableEvent live in the event_chains crate and always
+// return EventResult<()>. Events that produce a value implement PayloadEvent
+// and are added wrapped in `Collect`; `execute_collecting` gathers what they
+// return. Under a plain `execute` the payloads are simply dropped.
+//
+// EventChain keeps its event list private, so a built chain cannot be taken
+// apart or edited. Chains assembled conditionally are put together in a
+// `ChainBuilder`, which can insert and remove events before building the
+// EventChain, and reusable fragments are kept as builders too:
+// `a.then(b)` appends b's events after a's, each under its own name and in
+// order, and b's middleware after a's, so every layer wraps every event. The
+// combined chain keeps a's fault tolerance. To run a built chain as one step
+// of another instead, wrap it in a `SubChain`; `SwitchEvent` holds several
+// sub-chains and runs the one selected by a context value.
+//
+// Nesting is counted per thread. A sub-chain that would run deeper than the
+// thread's limit (DEFAULT_MAX_CHAIN_DEPTH unless changed with
+// `set_max_chain_depth`) fails instead, so a cycle of chains that reach each
+// other through shared handles ends with an error rather than a stack
+// overflow. Custom events that execute other chains should go through
+// `with_nested_chain` to take part in the count; it reports the limit being
+// hit as `ChainError::MaxDepthExceeded`, which `SubChain` turns into the
+// failure message of its event.
+
+const PAYLOAD_SINK_KEY: &str = "__payload_sink";
+
+/// Deepest sub-chain nesting allowed on a thread that has not called
+/// [`set_max_chain_depth`].
+pub const DEFAULT_MAX_CHAIN_DEPTH: usize = 32;
+
+thread_local! {
+    static CHAIN_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static MAX_CHAIN_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_CHAIN_DEPTH) };
+}
+
+/// How many sub-chains are currently executing on this thread.
+pub fn chain_depth() -> usize {
+    CHAIN_DEPTH.with(Cell::get)
+}
+
+/// Deepest sub-chain nesting allowed on this thread.
+pub fn max_chain_depth() -> usize {
+    MAX_CHAIN_DEPTH.with(Cell::get)
+}
+
+/// Allow sub-chains on this thread to nest `limit` levels deep.
+pub fn set_max_chain_depth(limit: usize) {
+    MAX_CHAIN_DEPTH.with(|max| max.set(limit));
+}
+
+/// Run `f` one sub-chain level deeper, failing without calling it if that
+/// would exceed [`max_chain_depth`].
+pub fn with_nested_chain<R>(f: impl FnOnce() -> R) -> Result<R, ChainError> {
+    struct Level;
+
+    impl Drop for Level {
+        fn drop(&mut self) {
+            CHAIN_DEPTH.with(|depth| depth.set(depth.get() - 1));
+        }
+    }
+
+    let (depth, limit) = (chain_depth() + 1, max_chain_depth());
+    if depth > limit {
+        return Err(ChainError::MaxDepthExceeded { limit, depth });
+    }
+    CHAIN_DEPTH.with(|current| current.set(depth));
+    let _level = Level;
+    Ok(f())
+}
+
+/// An event whose success carries a value back to the caller.
+pub trait PayloadEvent: Send + Sync {
+    type Output: Any + Send + Sync;
+
+    fn execute(&self, context: &mut EventContext) -> EventResult<Self::Output>;
+    fn name(&self) -> &str;
+}
+
+/// Adapts a [`PayloadEvent`] so it can be added to an `EventChain`.
+pub struct Collect<E>(pub E);
+
+impl<E: PayloadEvent> ChainableEvent for Collect<E> {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        match self.0.execute(context) {
+            EventResult::Success(value) => {
+                if let Some(sink) = context.get::<PayloadSink>(PAYLOAD_SINK_KEY) {
+                    sink.push(Payload {
+                        event: self.0.name().to_string(),
+                        value: Box::new(value),
+                    });
+                }
+                EventResult::Success(())
+            }
+            EventResult::Failure(msg) => EventResult::Failure(msg),
+            EventResult::MiddlewareFailure(msg) => EventResult::MiddlewareFailure(msg),
+        }
+    }
+
+    fn name(&self) -> &str { self.0.name() }
+}
+
+/// Runs a whole chain as one event. Fails if the inner chain fails, with
+/// every inner failure in the message; warnings from a chain that still
+/// succeeded are dropped.
+pub struct SubChain {
+    chain: EventChain,
+    name: String,
+}
+
+impl SubChain {
+    pub fn new(name: &str, chain: EventChain) -> Self {
+        Self { chain, name: name.to_string() }
+    }
+}
+
+impl ChainableEvent for SubChain {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let result = match with_nested_chain(|| self.chain.execute(context)) {
+            Ok(result) => result,
+            Err(e) => return EventResult::Failure(e.to_string()),
+        };
+        if result.success {
+            return EventResult::Success(());
+        }
+        let failures: Vec<String> = result
+            .failures
+            .iter()
+            .map(|f| format!("{}: {}", f.event_name, f.error_message))
+            .collect();
+        EventResult::Failure(failures.join("; "))
+    }
+
+    fn name(&self) -> &str { &self.name }
+}
+
+/// Runs one of several sub-chains, chosen by the value under `key`.
+///
+/// ```ignore
+/// let route = SwitchEvent::new("mode")
+///     .branch("fast", fast_chain)
+///     .branch("safe", safe_chain)
+///     .default_branch(fallback_chain);
+/// ```
+///
+/// When no branch matches (or `key` is missing) the event fails, unless a
+/// default branch was given (it runs) or `skip_unmatched` was called (the
+/// event succeeds without doing anything).
+pub struct SwitchEvent {
+    key: String,
+    branches: Vec<(ContextValue, SubChain)>,
+    unmatched: Unmatched,
+    name: String,
+}
+
+enum Unmatched {
+    Fail,
+    Skip,
+    Run(SubChain),
+}
+
+impl SwitchEvent {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            branches: Vec::new(),
+            unmatched: Unmatched::Fail,
+            name: format!("switch({})", key),
+        }
+    }
+
+    /// Run `chain` when the key holds `value`. The first matching branch wins.
+    pub fn branch(mut self, value: impl Into<ContextValue>, chain: EventChain) -> Self {
+        let value = value.into();
+        let name = format!("{}={}", self.key, value);
+        self.branches.push((value, SubChain::new(&name, chain)));
+        self
+    }
+
+    pub fn default_branch(mut self, chain: EventChain) -> Self {
+        let name = format!("{}=default", self.key);
+        self.unmatched = Unmatched::Run(SubChain::new(&name, chain));
+        self
+    }
+
+    pub fn skip_unmatched(mut self) -> Self {
+        self.unmatched = Unmatched::Skip;
+        self
+    }
+}
+
+impl ChainableEvent for SwitchEvent {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let value = ContextValue::read(context, &self.key);
+        let selected = self
+            .branches
+            .iter()
+            .find(|(candidate, _)| Some(candidate) == value.as_ref())
+            .map(|(_, chain)| chain);
+
+        match (selected, &self.unmatched) {
+            (Some(chain), _) | (None, Unmatched::Run(chain)) => chain.execute(context),
+            (None, Unmatched::Skip) => EventResult::Success(()),
+            (None, Unmatched::Fail) => EventResult::Failure(match value {
+                Some(value) => format!("no branch for {} = {}", self.key, value),
+                None => format!("context key '{}' is missing", self.key),
+            }),
+        }
+    }
+
+    fn name(&self) -> &str { &self.name }
+}
+
+/// A value returned by one event during `execute_collecting`.
+pub struct Payload {
+    pub event: String,
+    pub value: Box<dyn Any + Send + Sync>,
+}
+
+impl Payload {
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+}
+
+pub struct ChainRunResult {
+    pub result: ChainResult,
+    /// Payloads in the order their events completed
+    pub payloads: Vec<Payload>,
+}
+
+impl ChainRunResult {
+    /// All payloads of type `T`, in order.
+    pub fn payloads_of<T: Any>(&self) -> impl Iterator<Item = &T> {
+        self.payloads.iter().filter_map(|p| p.downcast_ref::<T>())
+    }
+}
+
+// `None` is a closed sink: payloads pushed into it are dropped.
+#[derive(Clone)]
+struct PayloadSink(Option<Arc<Mutex<Vec<Payload>>>>);
+
+impl PayloadSink {
+    fn open() -> Self {
+        PayloadSink(Some(Arc::new(Mutex::new(Vec::new()))))
+    }
+
+    fn push(&self, payload: Payload) {
+        if let Some(Ok(mut payloads)) = self.0.as_ref().map(|p| p.lock()) {
+            payloads.push(payload);
+        }
+    }
+
+    fn take(&self) -> Vec<Payload> {
+        match self.0.as_ref().map(|p| p.lock()) {
+            Some(Ok(mut payloads)) => std::mem::take(&mut *payloads),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// An event list that can still be edited, built into an [`EventChain`]
+/// once complete.
+///
+/// ```ignore
+/// let mut builder = ChainBuilder::new().event(Load).event(Save);
+/// if validate {
+///     builder.insert(1, Validate)?;
+/// }
+/// let chain = builder.build();
+/// ```
+pub struct ChainBuilder {
+    events: Vec<Box<dyn ChainableEvent>>,
+    middlewares: Vec<Box<dyn EventMiddleware>>,
+    fault_tolerance: FaultToleranceMode,
+}
+
+impl Default for ChainBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl ChainBuilder {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            middlewares: Vec::new(),
+            fault_tolerance: FaultToleranceMode::Strict,
+        }
+    }
+
+    pub fn event<E: ChainableEvent + 'static>(mut self, event: E) -> Self {
+        self.events.push(Box::new(event));
+        self
+    }
+
+    pub fn middleware<M: EventMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Box::new(middleware));
+        self
+    }
+
+    pub fn with_fault_tolerance(mut self, mode: FaultToleranceMode) -> Self {
+        self.fault_tolerance = mode;
+        self
+    }
+
+    /// Add `event` at position `index` (`0..=len`).
+    pub fn insert<E: ChainableEvent + 'static>(&mut self, index: usize, event: E) -> Result<(), ChainError> {
+        if index > self.events.len() {
+            return Err(ChainError::EventIndexOutOfRange { index, len: self.events.len() });
+        }
+        self.events.insert(index, Box::new(event));
+        Ok(())
+    }
+
+    /// Take out the event at `index`.
+    pub fn remove(&mut self, index: usize) -> Result<Box<dyn ChainableEvent>, ChainError> {
+        if index >= self.events.len() {
+            return Err(ChainError::EventIndexOutOfRange { index, len: self.events.len() });
+        }
+        Ok(self.events.remove(index))
+    }
+
+    pub fn len(&self) -> usize { self.events.len() }
+
+    pub fn is_empty(&self) -> bool { self.events.is_empty() }
+
+    /// Event names in execution order.
+    pub fn event_names(&self) -> Vec<&str> {
+        self.events.iter().map(|event| event.name()).collect()
+    }
+
+    /// Append `other`'s events after this builder's, and its middleware
+    /// after this builder's middleware. This builder's fault tolerance is kept.
+    pub fn then(mut self, other: ChainBuilder) -> Self {
+        self.events.extend(other.events);
+        self.middlewares.extend(other.middlewares);
+        self
+    }
+
+    pub fn build(self) -> EventChain {
+        let mut chain = EventChain::new().with_fault_tolerance(self.fault_tolerance);
+        for event in self.events {
+            chain.add_event(event);
+        }
+        for middleware in self.middlewares {
+            chain.use_middleware(middleware);
+        }
+        chain
+    }
+}
+
+pub trait EventChainExt {
+    /// Execute the chain, collecting the payloads of `Collect`-wrapped events.
+    fn execute_collecting(&self, context: &mut EventContext) -> ChainRunResult;
+
+    /// Run each context through the chain on up to `threads` scoped threads
+    /// (at least one), returning every context with its result in input
+    /// order. Contexts are split into contiguous slices, one per thread.
+    /// The native counterpart of `LuaChainRunner::execute_batch`.
+    ///
+    /// This relies on `ChainableEvent` and `EventMiddleware` being
+    /// `Send + Sync` (so the chain can be shared) and on context values being
+    /// `Send + Sync` (so contexts can move between threads).
+    fn execute_parallel(
+        &self,
+        contexts: Vec<EventContext>,
+        threads: usize,
+    ) -> Vec<(EventContext, ChainResult)>;
+
+    /// Append `other`'s events after this chain's, under their own names,
+    /// and its middleware after this chain's; see [`ChainBuilder::then`].
+    fn then(self, other: ChainBuilder) -> EventChain;
+}
+
+impl EventChainExt for EventChain {
+    fn execute_collecting(&self, context: &mut EventContext) -> ChainRunResult {
+        let sink = PayloadSink::open();
+        context.set(PAYLOAD_SINK_KEY, sink.clone());
+        let result = self.execute(context);
+        // EventContext has no remove; close the sink so later runs drop payloads
+        context.set(PAYLOAD_SINK_KEY, PayloadSink(None));
+        ChainRunResult { result, payloads: sink.take() }
+    }
+
+    fn execute_parallel(
+        &self,
+        contexts: Vec<EventContext>,
+        threads: usize,
+    ) -> Vec<(EventContext, ChainResult)> {
+        let chunk_size = contexts.len().div_ceil(threads.max(1)).max(1);
+        let mut chunks = Vec::new();
+        let mut contexts = contexts.into_iter().peekable();
+        while contexts.peek().is_some() {
+            chunks.push(contexts.by_ref().take(chunk_size).collect::<Vec<_>>());
+        }
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .into_iter()
+                            .map(|mut context| {
+                                let result = self.execute(&mut context);
+                                (context, result)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| match handle.join() {
+                    Ok(results) => results,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect()
+        })
+    }
+
+    fn then(mut self, other: ChainBuilder) -> EventChain {
+        for event in other.events {
+            self.add_event(event);
+        }
+        for middleware in other.middlewares {
+            self.use_middleware(middleware);
+        }
+        self
+    }
+}
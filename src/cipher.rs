@@ -0,0 +1,133 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::ChainError;
+use crate::value::ContextValue;
+
+// ============================================================================
+// ENCRYPTED STORE VALUES
+// ============================================================================
+// Keys marked with `LuaChainRunner::with_sensitive_key` are encrypted with
+// the runner's `with_cipher` before they are written to a ContextStore (by
+// `execute_with_store` or a persistent context) and decrypted when a run
+// loads them. Other keys are stored as they are.
+//
+// A sensitive value is encoded to bytes (a tag byte, then the value), passed
+// to `ContextCipher::encrypt` together with its key (so a cipher can bind
+// the ciphertext to the key, e.g. as associated data) and stored as
+// `ContextValue::Bytes`. Reading the store directly therefore only yields
+// ciphertext. A stored value that is not `Bytes`, or that the cipher or the
+// decoding rejects, fails the load with `ChainError::Cipher`; so does
+// declaring sensitive keys without a cipher. Streams hold no data of their
+// own and are stored unencrypted.
+//
+// The crate does no cryptography itself. Choosing the algorithm, storing and
+// rotating keys, and authenticating ciphertexts are the cipher's (and so the
+// caller's) responsibility.
+
+/// Encrypts and decrypts the values of sensitive context keys.
+pub trait ContextCipher {
+    fn encrypt(&self, key: &str, plaintext: &[u8]) -> Result<Vec<u8>, String>;
+
+    fn decrypt(&self, key: &str, ciphertext: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// The form of `value` to store under sensitive `key`.
+pub(crate) fn seal(cipher: &dyn ContextCipher, key: &str, value: &ContextValue) -> Result<ContextValue, ChainError> {
+    let Some(plaintext) = encode(value) else {
+        return Ok(value.clone());
+    };
+    let ciphertext = cipher.encrypt(key, &plaintext).map_err(|message| failure(key, message))?;
+    Ok(ContextValue::Bytes(ciphertext))
+}
+
+/// The value stored under sensitive `key`, decrypted.
+pub(crate) fn open(cipher: &dyn ContextCipher, key: &str, stored: ContextValue) -> Result<ContextValue, ChainError> {
+    match stored {
+        ContextValue::Bytes(ciphertext) => {
+            let plaintext = cipher.decrypt(key, &ciphertext).map_err(|message| failure(key, message))?;
+            decode(&plaintext).ok_or_else(|| failure(key, "decrypted value is malformed".to_string()))
+        }
+        ContextValue::Stream(stream) => Ok(ContextValue::Stream(stream)),
+        other => Err(failure(key, format!("stored value is not encrypted (found {})", other.type_name()))),
+    }
+}
+
+pub(crate) fn failure(key: &str, message: String) -> ChainError {
+    ChainError::Cipher { key: key.to_string(), message }
+}
+
+fn encode(value: &ContextValue) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match value {
+        ContextValue::Int(i) => {
+            bytes.push(0);
+            bytes.extend(i.to_le_bytes());
+        }
+        ContextValue::Float(n) => {
+            bytes.push(1);
+            bytes.extend(n.to_le_bytes());
+        }
+        ContextValue::Str(s) => {
+            bytes.push(2);
+            bytes.extend(s.as_bytes());
+        }
+        ContextValue::Bool(b) => bytes.extend([3, *b as u8]),
+        ContextValue::Bytes(raw) => {
+            bytes.push(4);
+            bytes.extend(raw);
+        }
+        ContextValue::Timestamp(time) => {
+            let (sign, offset) = match time.duration_since(UNIX_EPOCH) {
+                Ok(offset) => (0, offset),
+                Err(before) => (1, before.duration()),
+            };
+            bytes.extend([5, sign]);
+            bytes.extend(offset.as_secs().to_le_bytes());
+            bytes.extend(offset.subsec_nanos().to_le_bytes());
+        }
+        ContextValue::Ref(target) => {
+            bytes.push(6);
+            bytes.extend(target.as_bytes());
+        }
+        #[cfg(feature = "decimal")]
+        ContextValue::Decimal(decimal) => {
+            bytes.push(7);
+            bytes.extend(decimal.serialize());
+        }
+        ContextValue::Stream(_) => return None,
+    }
+    Some(bytes)
+}
+
+fn decode(bytes: &[u8]) -> Option<ContextValue> {
+    let (&tag, body) = bytes.split_first()?;
+    let value = match tag {
+        0 => ContextValue::Int(i64::from_le_bytes(body.try_into().ok()?)),
+        1 => ContextValue::Float(f64::from_le_bytes(body.try_into().ok()?)),
+        2 => ContextValue::Str(String::from_utf8(body.to_vec()).ok()?),
+        3 => match body {
+            [b] => ContextValue::Bool(*b != 0),
+            _ => return None,
+        },
+        4 => ContextValue::Bytes(body.to_vec()),
+        5 => {
+            let (&sign, rest) = body.split_first()?;
+            if rest.len() != 12 {
+                return None;
+            }
+            let secs = u64::from_le_bytes(rest[..8].try_into().ok()?);
+            let nanos = u32::from_le_bytes(rest[8..].try_into().ok()?);
+            let offset = Duration::new(secs, nanos);
+            let time = match sign {
+                0 => UNIX_EPOCH.checked_add(offset)?,
+                _ => UNIX_EPOCH.checked_sub(offset)?,
+            };
+            ContextValue::Timestamp(time)
+        }
+        6 => ContextValue::Ref(String::from_utf8(body.to_vec()).ok()?),
+        #[cfg(feature = "decimal")]
+        7 => ContextValue::Decimal(rust_decimal::Decimal::deserialize(body.try_into().ok()?)),
+        _ => return None,
+    };
+    Some(value)
+}
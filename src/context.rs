@@ -0,0 +1,237 @@
+use std::any::{type_name, Any};
+use std::fmt;
+use event_chains::EventContext;
+
+use crate::value::{resolve_ref, ContextDisplay, ContextValue};
+
+// ============================================================================
+// CONTEXT EXTENSIONS
+// ============================================================================
+// EventContext lives in the event_chains crate, so these helpers are provided
+// as an extension trait over its public get/set/has surface.
+
+pub trait EventContextExt {
+    /// Get a typed entry for `key`, similar to `HashMap::entry`.
+    ///
+    /// ```ignore
+    /// ctx.entry("counter").and_modify(|c: &mut i64| *c += 1).or_insert(1i64);
+    /// ```
+    fn entry(&mut self, key: &str) -> Entry<'_>;
+
+    /// Extract several typed values at once.
+    ///
+    /// ```ignore
+    /// let (counter, message) = ctx.view::<(i64, String)>(("counter", "message"))?;
+    /// ```
+    fn view<V: ContextView>(&self, keys: V::Keys<'_>) -> Result<V, ContextViewError>;
+
+    /// Read `key` as a [`ContextValue`] without knowing its Rust type,
+    /// following refs. Values stored under a type `ContextValue` cannot
+    /// represent, and refs that end in a cycle, are `None`.
+    fn get_value(&self, key: &str) -> Option<ContextValue>;
+
+    /// Store `value` under its natural Rust type (`i64`, `f64`, `String`,
+    /// `bool` or `Vec<u8>`). Narrower types widen on the way in: `i32`,
+    /// `u32` and `usize` are stored as `i64`, `f32` as `f64` and `&str` as
+    /// `String`, so `ctx.set_value("counter", 0)` reads back with
+    /// `get::<i64>`.
+    fn set_value(&mut self, key: &str, value: impl Into<ContextValue>);
+
+    /// Snapshot the values under `keys`, to put back later with
+    /// [`restore`](Self::restore).
+    ///
+    /// ```ignore
+    /// let saved = ctx.checkpoint(&["balance", "ledger"]);
+    /// if let Err(e) = apply(ctx) {
+    ///     ctx.restore(saved);
+    /// }
+    /// ```
+    fn checkpoint(&self, keys: &[&str]) -> Checkpoint;
+
+    /// Put back every value captured by `checkpoint`.
+    fn restore(&mut self, checkpoint: Checkpoint);
+
+    /// Render the values under `keys` for humans, sorted and type-annotated.
+    /// EventContext cannot list its keys, so they have to be named.
+    ///
+    /// ```ignore
+    /// println!("{}", ctx.display(&["counter", "message"]));
+    /// // counter: i64 = 1
+    /// // message: String = "start -> processed"
+    /// ```
+    fn display(&self, keys: &[&str]) -> ContextDisplay;
+}
+
+impl EventContextExt for EventContext {
+    fn entry(&mut self, key: &str) -> Entry<'_> {
+        Entry { context: self, key: key.to_string() }
+    }
+
+    fn view<V: ContextView>(&self, keys: V::Keys<'_>) -> Result<V, ContextViewError> {
+        V::extract(self, keys)
+    }
+
+    fn get_value(&self, key: &str) -> Option<ContextValue> {
+        resolve_ref(|key| ContextValue::read(self, key), key).ok()?
+    }
+
+    fn set_value(&mut self, key: &str, value: impl Into<ContextValue>) {
+        value.into().write(self, key);
+    }
+
+    fn checkpoint(&self, keys: &[&str]) -> Checkpoint {
+        Checkpoint {
+            values: keys
+                .iter()
+                .filter_map(|key| Some((key.to_string(), ContextValue::read(self, key)?)))
+                .collect(),
+        }
+    }
+
+    fn display(&self, keys: &[&str]) -> ContextDisplay {
+        ContextDisplay::new(keys.iter().map(|key| (key.to_string(), ContextValue::read(self, key))).collect())
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        for (key, value) in checkpoint.values {
+            value.write(self, &key);
+        }
+    }
+}
+
+// ============================================================================
+// CHECKPOINTS (snapshot and restore for transactional native events)
+// ============================================================================
+// EventContext neither lists nor removes its keys, so a checkpoint covers the
+// keys it is given, and only values a ContextValue can hold (integers,
+// floats, strings, booleans, bytes). Restoring writes those values back; a
+// key that was absent at checkpoint time cannot be removed again and keeps
+// whatever was set since. Each checkpoint clones the captured values, so it
+// costs one allocation per string or byte value.
+
+/// Values captured by [`EventContextExt::checkpoint`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    values: Vec<(String, ContextValue)>,
+}
+
+impl Checkpoint {
+    /// The keys whose values were captured.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|(key, _)| key.as_str())
+    }
+}
+
+// ============================================================================
+// ENTRY API (in-place mutation without the get/compute/set triple)
+// ============================================================================
+
+pub struct Entry<'a> {
+    context: &'a mut EventContext,
+    key: String,
+}
+
+impl<'a> Entry<'a> {
+    /// Modify the stored value in place if it exists and is a `T`.
+    /// Missing keys and values of another type are left untouched.
+    pub fn and_modify<T, F>(self, f: F) -> Self
+    where
+        T: Any + Send + Sync + Clone,
+        F: FnOnce(&mut T),
+    {
+        if let Some(mut value) = self.context.get::<T>(&self.key) {
+            f(&mut value);
+            self.context.set(&self.key, value);
+        }
+        self
+    }
+
+    /// Return the stored value, inserting `default` if the key is missing.
+    ///
+    /// If the key holds a value of a different type, it is replaced by
+    /// `default` and the default is returned.
+    pub fn or_insert<T>(self, default: T) -> T
+    where
+        T: Any + Send + Sync + Clone,
+    {
+        match self.context.get::<T>(&self.key) {
+            Some(value) => value,
+            None => {
+                self.context.set(&self.key, default.clone());
+                default
+            }
+        }
+    }
+
+    pub fn key(&self) -> &str { &self.key }
+}
+
+// ============================================================================
+// TYPED VIEWS (declare the keys and types a handler needs up front)
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextViewError {
+    Missing { key: String },
+    WrongType { key: String, expected: &'static str },
+}
+
+impl fmt::Display for ContextViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextViewError::Missing { key } => write!(f, "context key '{}' is missing", key),
+            ContextViewError::WrongType { key, expected } => {
+                write!(f, "context key '{}' does not hold a {}", key, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContextViewError {}
+
+/// A set of typed values extracted from an `EventContext` by key.
+/// Implemented for tuples of up to four `Clone` values.
+pub trait ContextView: Sized {
+    type Keys<'k>;
+
+    fn extract(context: &EventContext, keys: Self::Keys<'_>) -> Result<Self, ContextViewError>;
+}
+
+fn required<T: Any + Send + Sync + Clone>(
+    context: &EventContext,
+    key: &str,
+) -> Result<T, ContextViewError> {
+    match context.get::<T>(key) {
+        Some(value) => Ok(value),
+        // Bytes that are valid UTF-8 come back from Lua as a String
+        None if let Some(text) = context.get::<String>(key)
+            && let Ok(bytes) = (Box::new(text.into_bytes()) as Box<dyn Any>).downcast::<T>() =>
+        {
+            Ok(*bytes)
+        }
+        None if context.has(key) => Err(ContextViewError::WrongType {
+            key: key.to_string(),
+            expected: type_name::<T>(),
+        }),
+        None => Err(ContextViewError::Missing { key: key.to_string() }),
+    }
+}
+
+macro_rules! impl_context_view {
+    ($($t:ident => $k:ident),+) => {
+        impl<$($t: Any + Send + Sync + Clone),+> ContextView for ($($t,)+) {
+            type Keys<'k> = ($(impl_context_view!(@key 'k $t),)+);
+
+            fn extract(context: &EventContext, keys: Self::Keys<'_>) -> Result<Self, ContextViewError> {
+                let ($($k,)+) = keys;
+                Ok(($(required::<$t>(context, $k)?,)+))
+            }
+        }
+    };
+    (@key $l:lifetime $t:ident) => { &$l str };
+}
+
+impl_context_view!(A => a);
+impl_context_view!(A => a, B => b);
+impl_context_view!(A => a, B => b, C => c);
+impl_context_view!(A => a, B => b, C => c, D => d);
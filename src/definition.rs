@@ -0,0 +1,54 @@
+use serde::Deserialize;
+
+use crate::error::ChainError;
+use crate::value::ContextMap;
+
+// ============================================================================
+// DECLARATIVE CHAIN DEFINITIONS (JSON / TOML)
+// ============================================================================
+// A context plus a list of registered native event names, for chains that
+// need no Lua handler bodies:
+//
+//   { "meta": { "name": "demo", "version": "1.0" },
+//     "context": { "counter": 0, "message": "start" },
+//     "events": ["increment", "append"] }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainDefinition {
+    #[serde(default)]
+    pub meta: ChainMeta,
+    #[serde(default)]
+    pub context: ContextMap,
+    pub events: Vec<String>,
+}
+
+/// Descriptive chain-level metadata, for registries and dashboards.
+/// Every field is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ChainMeta {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// What was loaded from a definition, for hosts to display. Warnings point
+/// at entries that load but are probably mistakes, such as duplicate names
+/// or fields that are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    pub events: Vec<String>,
+    pub middleware: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ChainDefinition {
+    pub fn from_json(json: &str) -> Result<Self, ChainError> {
+        serde_json::from_str(json).map_err(|e| ChainError::InvalidDefinition(e.to_string()))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ChainError> {
+        toml::from_str(toml).map_err(|e| ChainError::InvalidDefinition(e.to_string()))
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::time::Duration;
+use mlua::prelude::*;
+
+use crate::value::{ContextMap, MAX_REF_DEPTH};
+use crate::verify::ContextMismatch;
+
+// ============================================================================
+// CHAIN ERRORS
+// ============================================================================
+
+#[derive(Debug)]
+pub enum ChainError {
+    /// Error raised by the Lua VM outside of any event (loading, globals, ...)
+    Lua(LuaError),
+    /// A chain script could not be read
+    Io(std::io::Error),
+    /// The chain definition table is malformed
+    InvalidDefinition(String),
+    /// An event handler (or middleware wrapping it) failed
+    EventFailed {
+        chain: Option<String>,
+        run_id: String,
+        index: usize,
+        name: String,
+        message: String,
+    },
+    /// An event stored a value that a validator rejected; `message` starts
+    /// with the key
+    ValidationFailed {
+        chain: Option<String>,
+        run_id: String,
+        index: usize,
+        name: String,
+        message: String,
+    },
+    /// Every event failure of a run made with `with_collect_errors`, and
+    /// the context the run ended with
+    Multiple { errors: Vec<ChainError>, context: ContextMap },
+    /// `verify` found the final context differing from the definition's
+    /// `expect` table, one mismatch per key
+    ExpectationFailed { chain: Option<String>, run_id: String, mismatches: Vec<ContextMismatch> },
+    /// The refs starting at this key do not reach a value within
+    /// `MAX_REF_DEPTH` steps, usually because they form a cycle
+    RefCycle(String),
+    /// A context could not be converted to or from a serde type
+    ContextConversion(String),
+    /// A sensitive key's value could not be encrypted for, or decrypted
+    /// from, a context store; see cipher.rs
+    Cipher { key: String, message: String },
+    /// `execute` was called on a runner that is already executing
+    AlreadyRunning,
+    /// An event index outside the chain was requested
+    EventIndexOutOfRange { index: usize, len: usize },
+    /// No event with this name exists in the chain
+    UnknownEvent(String),
+    /// No middleware with this name exists in the chain
+    UnknownMiddleware(String),
+    /// A sub-chain would have run `depth` levels deep, past the thread's
+    /// limit; see `with_nested_chain`
+    MaxDepthExceeded { limit: usize, depth: usize },
+    /// The run exceeded `max_total_duration` before event `next_event` started
+    TotalTimeout {
+        chain: Option<String>,
+        run_id: String,
+        limit: Duration,
+        elapsed: Duration,
+        next_event: usize,
+    },
+}
+
+impl ChainError {
+    /// The run this error belongs to, for errors raised while executing.
+    pub fn run_id(&self) -> Option<&str> {
+        match self {
+            ChainError::EventFailed { run_id, .. }
+            | ChainError::ValidationFailed { run_id, .. }
+            | ChainError::ExpectationFailed { run_id, .. }
+            | ChainError::TotalTimeout { run_id, .. } => Some(run_id),
+            ChainError::Multiple { errors, .. } => errors.first().and_then(ChainError::run_id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Lua(e) => write!(f, "Lua error: {}", e),
+            ChainError::Io(e) => write!(f, "I/O error: {}", e),
+            ChainError::InvalidDefinition(msg) => write!(f, "Invalid chain definition: {}", msg),
+            ChainError::EventFailed { chain, run_id, index, name, message } => {
+                write_run_prefix(f, chain.as_deref(), run_id)?;
+                write!(f, "Event {} ({}) failed: {}", index, name, message)
+            }
+            ChainError::ValidationFailed { chain, run_id, index, name, message } => {
+                write_run_prefix(f, chain.as_deref(), run_id)?;
+                write!(f, "Event {} ({}) failed validation: {}", index, name, message)
+            }
+            ChainError::Multiple { errors, .. } => {
+                write!(f, "{} events failed", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            ChainError::ExpectationFailed { chain, run_id, mismatches } => {
+                write_run_prefix(f, chain.as_deref(), run_id)?;
+                write!(f, "Final context does not match `expect` ({} keys differ)", mismatches.len())?;
+                for mismatch in mismatches {
+                    write!(f, "\n  {}", mismatch)?;
+                }
+                Ok(())
+            }
+            ChainError::RefCycle(key) => {
+                write!(f, "Context key '{}' is part of a reference cycle (or a chain deeper than {})", key, MAX_REF_DEPTH)
+            }
+            ChainError::ContextConversion(msg) => write!(f, "Context conversion failed: {}", msg),
+            ChainError::Cipher { key, message } => write!(f, "Sensitive context key '{}': {}", key, message),
+            ChainError::AlreadyRunning => write!(f, "Chain is already running"),
+            ChainError::UnknownEvent(name) => write!(f, "No event named '{}' in the chain", name),
+            ChainError::UnknownMiddleware(name) => write!(f, "No middleware named '{}' in the chain", name),
+            ChainError::EventIndexOutOfRange { index, len } => {
+                write!(f, "Event index {} out of range (chain has {} events)", index, len)
+            }
+            ChainError::MaxDepthExceeded { limit, depth } => {
+                write!(f, "Sub-chain nesting depth {} exceeds the limit of {}", depth, limit)
+            }
+            ChainError::TotalTimeout { chain, run_id, limit, elapsed, next_event } => {
+                write_run_prefix(f, chain.as_deref(), run_id)?;
+                write!(
+                    f,
+                    "Chain exceeded its {:?} budget ({:?} elapsed) before event {}",
+                    limit, elapsed, next_event
+                )
+            }
+        }
+    }
+}
+
+fn write_run_prefix(f: &mut fmt::Formatter<'_>, chain: Option<&str>, run_id: &str) -> fmt::Result {
+    match chain {
+        Some(chain) => write!(f, "[chain {}, run {}] ", chain, run_id),
+        None => write!(f, "[run {}] ", run_id),
+    }
+}
+
+impl std::error::Error for ChainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChainError::Lua(e) => Some(e),
+            ChainError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LuaError> for ChainError {
+    fn from(e: LuaError) -> Self {
+        ChainError::Lua(e)
+    }
+}
+
+impl From<std::io::Error> for ChainError {
+    fn from(e: std::io::Error) -> Self {
+        ChainError::Io(e)
+    }
+}
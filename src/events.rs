@@ -0,0 +1,103 @@
+use std::time::Duration;
+use event_chains::{ChainableEvent, EventContext, EventResult};
+
+use crate::context::EventContextExt;
+
+// ============================================================================
+// NATIVE DEMO EVENTS (Rust implementations)
+// ============================================================================
+// `Default` keeps the original benchmark behaviour: `counter += 1` and
+// `message .. " -> processed"`.
+
+pub struct IncrementEvent {
+    key: String,
+    by: i64,
+    name: String,
+}
+
+impl IncrementEvent {
+    pub fn new(key: &str, by: i64) -> Self {
+        Self {
+            key: key.to_string(),
+            by,
+            name: format!("increment({}, {})", key, by),
+        }
+    }
+
+    pub fn key(&self) -> &str { &self.key }
+
+    pub fn by(&self) -> i64 { self.by }
+}
+
+impl Default for IncrementEvent {
+    fn default() -> Self {
+        Self::new("counter", 1)
+    }
+}
+
+impl ChainableEvent for IncrementEvent {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let by = self.by;
+        context.entry(&self.key).and_modify(|c: &mut i64| *c += by).or_insert(by);
+        EventResult::Success(())
+    }
+    fn name(&self) -> &str { &self.name }
+}
+
+pub struct AppendEvent {
+    key: String,
+    suffix: String,
+    name: String,
+}
+
+impl AppendEvent {
+    pub fn new(key: &str, suffix: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            suffix: suffix.to_string(),
+            name: format!("append({}, {:?})", key, suffix),
+        }
+    }
+
+    pub fn key(&self) -> &str { &self.key }
+
+    pub fn suffix(&self) -> &str { &self.suffix }
+}
+
+impl Default for AppendEvent {
+    fn default() -> Self {
+        Self::new("message", " -> processed")
+    }
+}
+
+impl ChainableEvent for AppendEvent {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let message: String = context.get(&self.key).unwrap_or_default();
+        context.set(&self.key, message + &self.suffix);
+        EventResult::Success(())
+    }
+    fn name(&self) -> &str { &self.name }
+}
+
+/// Sleeps for a fixed duration; useful for simulating latency, pacing
+/// external calls and exercising timeouts.
+pub struct DelayEvent {
+    duration: Duration,
+    name: String,
+}
+
+impl DelayEvent {
+    pub fn new(duration: Duration) -> Self {
+        Self { duration, name: format!("delay({:?})", duration) }
+    }
+
+    pub fn duration(&self) -> Duration { self.duration }
+}
+
+impl ChainableEvent for DelayEvent {
+    fn execute(&self, _context: &mut EventContext) -> EventResult<()> {
+        std::thread::sleep(self.duration);
+        EventResult::Success(())
+    }
+    fn name(&self) -> &str { &self.name }
+}
@@ -0,0 +1,86 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::runner::next_unit;
+
+// ============================================================================
+// FAULT INJECTION
+// ============================================================================
+// A testing aid for chaos runs: `LuaChainRunner::with_fault_injector` makes
+// named events fail, or stall, a chosen fraction of the time without
+// touching their handlers, to exercise `on_error`, circuit breakers, cleanup
+// events and error collection. It is not meant for production runners.
+//
+//   let faults = FaultInjector::new()
+//       .fail("charge", 0.25)
+//       .delay("fetch", Duration::from_millis(200), 0.5);
+//   let runner = runner.with_seed(42).with_fault_injector(faults);
+//
+// Each time a configured event's handler is about to be called, the runner
+// draws from its RNG: first whether to sleep for the delay, then whether to
+// raise "injected fault in event[i]:name" instead of calling the handler.
+// The fault sits where the handler would run, inside the middleware stack
+// and the circuit breaker, so middleware sees it, the breaker counts it and
+// `on_error` can recover from it. A memoized result of a `pure` event is
+// served without calling the handler and so is never faulted. Draws share
+// the seeded RNG, so with `with_seed` a sequence of runs faults the same
+// events every time. Rates are clamped to 0 to 1: 0 never fires, 1 always.
+
+/// What to do to one event; see [`FaultInjector`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Fault {
+    /// Chance (0 to 1) that the handler is replaced by an error
+    pub fail_rate: f64,
+    pub delay: Duration,
+    /// Chance (0 to 1) of sleeping for `delay` before the handler
+    pub delay_rate: f64,
+}
+
+/// Synthetic failures and delays per event name, for resilience tests.
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: HashMap<String, Fault>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self { Self::default() }
+
+    /// Make `event` fail with probability `rate`.
+    pub fn fail(mut self, event: &str, rate: f64) -> Self {
+        self.faults.entry(event.to_string()).or_default().fail_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Delay `event` by `delay` with probability `rate`.
+    pub fn delay(mut self, event: &str, delay: Duration, rate: f64) -> Self {
+        let fault = self.faults.entry(event.to_string()).or_default();
+        fault.delay = delay;
+        fault.delay_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The fault configured for `event`, if any.
+    pub fn fault(&self, event: &str) -> Option<&Fault> { self.faults.get(event) }
+
+    /// Apply `event`'s fault: sleep if its delay is drawn, and return true
+    /// if its failure is drawn.
+    pub(crate) fn inject(&self, event: &str, rng: &Cell<u64>) -> bool {
+        let Some(fault) = self.faults.get(event) else {
+            return false;
+        };
+        if drawn(fault.delay_rate, rng) {
+            std::thread::sleep(fault.delay);
+        }
+        drawn(fault.fail_rate, rng)
+    }
+}
+
+// Rates of 0 and 1 decide without drawing
+fn drawn(rate: f64, rng: &Cell<u64>) -> bool {
+    match rate {
+        rate if rate <= 0.0 => false,
+        rate if rate >= 1.0 => true,
+        rate => next_unit(rng) < rate,
+    }
+}
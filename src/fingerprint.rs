@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
+
+use crate::error::ChainError;
+use crate::runner::LuaChainRunner;
+
+// ============================================================================
+// CONFIGURATION FINGERPRINTS
+// ============================================================================
+// `LuaChainRunner::config_snapshot` records the structural shape of a runner:
+// the chain's name and version, the keys of its initial context, its events
+// and middleware in order, and its options as strings. Handler bodies are
+// opaque and not part of it, so editing a handler without touching its
+// declaration leaves the shape unchanged.
+//
+// Options cover what changes how a run behaves: per-event fields (only where
+// they differ from the default, so adding a plain event adds no options),
+// middleware `observational`, `sample_rate` and `applies_to`, the
+// chain-level fields, and builder options such as `with_collect_errors`.
+// Observability toggles (traces, stats, access logs) and the seed are left
+// out. Options of events that share a name are merged under that name.
+//
+// `config_fingerprint` hashes the snapshot's JSON with 64-bit FNV-1a, which
+// is fixed by its definition, so the same shape gives the same fingerprint
+// across builds, platforms and compiler versions. Two runners whose
+// fingerprints differ are told apart with `config_diff`.
+
+/// The structural shape of a runner's configuration.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigSnapshot {
+    pub chain: Option<String>,
+    pub version: Option<String>,
+    /// Keys of the initial context, sorted
+    pub context_keys: Vec<String>,
+    /// Event names in run order
+    pub events: Vec<String>,
+    /// Middleware names in declaration order
+    pub middleware: Vec<String>,
+    /// Option name (e.g. "execution_mode", "event.fetch.weight") to value
+    pub options: BTreeMap<String, String>,
+}
+
+/// What changed between two snapshots, from the first to the second.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub added_events: Vec<String>,
+    pub removed_events: Vec<String>,
+    /// The events both have run in a different relative order
+    pub events_reordered: bool,
+    pub added_middleware: Vec<String>,
+    pub removed_middleware: Vec<String>,
+    /// The middleware both have is declared in a different order
+    pub middleware_reordered: bool,
+    pub added_context_keys: Vec<String>,
+    pub removed_context_keys: Vec<String>,
+    /// Option, value before and value after; `None` where it is unset
+    pub changed_options: Vec<(String, Option<String>, Option<String>)>,
+    /// The chain's name or version differs
+    pub meta_changed: bool,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool { *self == Self::default() }
+}
+
+impl ConfigSnapshot {
+    /// 16 hex digits identifying this shape; see the module comment.
+    pub fn fingerprint(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        format!("{:016x}", fnv1a(json.as_bytes()))
+    }
+
+    /// Changes that turn `self` into `other`.
+    pub fn diff(&self, other: &ConfigSnapshot) -> ConfigDiff {
+        let (added_events, removed_events) = (missing(&other.events, &self.events), missing(&self.events, &other.events));
+        let (added_middleware, removed_middleware) =
+            (missing(&other.middleware, &self.middleware), missing(&self.middleware, &other.middleware));
+
+        let mut changed_options = Vec::new();
+        for (option, before) in &self.options {
+            let after = other.options.get(option);
+            if after != Some(before) {
+                changed_options.push((option.clone(), Some(before.clone()), after.cloned()));
+            }
+        }
+        for (option, after) in &other.options {
+            if !self.options.contains_key(option) {
+                changed_options.push((option.clone(), None, Some(after.clone())));
+            }
+        }
+        changed_options.sort();
+
+        ConfigDiff {
+            events_reordered: reordered(&self.events, &other.events, &removed_events, &added_events),
+            middleware_reordered: reordered(&self.middleware, &other.middleware, &removed_middleware, &added_middleware),
+            added_events,
+            removed_events,
+            added_middleware,
+            removed_middleware,
+            added_context_keys: missing(&other.context_keys, &self.context_keys),
+            removed_context_keys: missing(&self.context_keys, &other.context_keys),
+            changed_options,
+            meta_changed: self.chain != other.chain || self.version != other.version,
+        }
+    }
+}
+
+impl LuaChainRunner {
+    /// Fingerprint of [`config_snapshot`](Self::config_snapshot), for telling
+    /// whether a chain's shape changed between two builds.
+    pub fn config_fingerprint(&self) -> Result<String, ChainError> {
+        Ok(self.config_snapshot()?.fingerprint())
+    }
+
+    /// Changes that turn this runner's configuration into `other`'s.
+    pub fn config_diff(&self, other: &LuaChainRunner) -> Result<ConfigDiff, ChainError> {
+        Ok(self.config_snapshot()?.diff(&other.config_snapshot()?))
+    }
+}
+
+// Entries of `names` beyond those in `other`, counting duplicates
+fn missing(names: &[String], other: &[String]) -> Vec<String> {
+    let mut available: HashMap<&str, usize> = HashMap::new();
+    for name in other {
+        *available.entry(name).or_default() += 1;
+    }
+    names
+        .iter()
+        .filter(|name| match available.get_mut(name.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+// Whether the entries common to both lists appear in a different order
+fn reordered(before: &[String], after: &[String], removed: &[String], added: &[String]) -> bool {
+    let kept = |names: &[String], dropped: &[String]| {
+        let mut dropped: Vec<&String> = dropped.iter().collect();
+        names
+            .iter()
+            .filter(|name| match dropped.iter().position(|d| d == name) {
+                Some(i) => {
+                    dropped.remove(i);
+                    false
+                }
+                None => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    kept(before, removed) != kept(after, added)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+use mlua::prelude::*;
+
+use crate::access::PreviewResult;
+use crate::breaker::BreakerState;
+use crate::definition::{ChainMeta, ParseReport};
+use crate::error::ChainError;
+use crate::fingerprint::ConfigSnapshot;
+use crate::runner::{ChainOutcome, ExecutionMode, LuaChainRunner, TrialOutcome};
+use crate::stats::{BoundaryStats, ChainStats};
+use crate::store::ContextStore;
+use crate::topology::Topology;
+use crate::value::ContextMap;
+
+// ============================================================================
+// FROZEN RUNNERS
+// ============================================================================
+// `LuaChainRunner::freeze` consumes a configured runner and returns a
+// FrozenRunner, which only exposes the execute family and read-only
+// accessors. There is no way back to the runner and no `&mut` access to it,
+// so once frozen the events, middleware, aliases and builder options are
+// fixed for the runner's lifetime; code that tries to edit them does not
+// compile (see the example on FrozenRunner).
+//
+// What freezing does not cover:
+// - The Lua state. Whoever holds the `Rc<Lua>` the runner was built with can
+//   still change globals or modules that handlers read; FrozenRunner does
+//   not expose `lua()` so it cannot be reached through the runner itself.
+// - Run state. Stats, `once` flags and the last outcome still change with
+//   every run, as on any runner, but `reset_once_flags` is not exposed.
+// - Threads. A runner is neither Send nor Sync (see runner.rs), so neither is
+//   a FrozenRunner. `Rc<FrozenRunner>` or `Arc<FrozenRunner>` share one
+//   between owners on the same thread without letting any of them edit it.
+
+/// A runner whose configuration can no longer change; see
+/// [`LuaChainRunner::freeze`].
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+/// use lua_chains::LuaChainRunner;
+///
+/// let runner = LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), "return { events = {} }").unwrap();
+/// let mut frozen = runner.freeze();
+/// frozen.remove_event("a").unwrap(); // no such method
+/// ```
+pub struct FrozenRunner(LuaChainRunner);
+
+impl LuaChainRunner {
+    /// Fix the chain as configured. The returned runner can execute but has
+    /// no mutators, and cannot be turned back into a `LuaChainRunner`.
+    pub fn freeze(self) -> FrozenRunner { FrozenRunner(self) }
+}
+
+impl FrozenRunner {
+    pub fn execute(&self) -> Result<ChainOutcome, ChainError> { self.0.execute() }
+
+    pub fn execute_with_id(&self, run_id: &str) -> Result<ChainOutcome, ChainError> {
+        self.0.execute_with_id(run_id)
+    }
+
+    pub fn execute_with_context<'lua>(&'lua self, context: LuaTable<'lua>) -> Result<ChainOutcome, ChainError> {
+        self.0.execute_with_context(context)
+    }
+
+    pub fn execute_from<'lua>(
+        &'lua self,
+        first_event: usize,
+        context: LuaTable<'lua>,
+    ) -> Result<ChainOutcome, ChainError> {
+        self.0.execute_from(first_event, context)
+    }
+
+    pub fn execute_with_store(&self, store: &mut dyn ContextStore) -> Result<ChainOutcome, ChainError> {
+        self.0.execute_with_store(store)
+    }
+
+    pub fn execute_batch<'lua>(&'lua self, inputs: Vec<LuaTable<'lua>>) -> Vec<Result<ChainOutcome, ChainError>> {
+        self.0.execute_batch(inputs)
+    }
+
+    pub fn trial(&self, overrides: ContextMap) -> Result<TrialOutcome, ChainError> { self.0.trial(overrides) }
+
+    pub fn preview_context(&self) -> Result<PreviewResult, ChainError> { self.0.preview_context() }
+
+    pub fn run_middleware(&self, name: &str, context: ContextMap) -> Result<(ContextMap, Vec<String>), ChainError> {
+        self.0.run_middleware(name, context)
+    }
+
+    pub fn stats(&self) -> Option<ChainStats> { self.0.stats() }
+
+    pub fn metrics_prometheus(&self) -> Option<String> { self.0.metrics_prometheus() }
+
+    pub fn boundary_stats(&self) -> Option<BoundaryStats> { self.0.boundary_stats() }
+
+    pub fn reset_stats(&self) { self.0.reset_stats() }
+
+    pub fn last_outcome(&self) -> Option<ChainOutcome> { self.0.last_outcome() }
+
+    pub fn parse_report(&self) -> &ParseReport { self.0.parse_report() }
+
+    pub fn setup_duration(&self) -> Duration { self.0.setup_duration() }
+
+    pub fn meta(&self) -> &ChainMeta { self.0.meta() }
+
+    pub fn execution_mode(&self) -> ExecutionMode { self.0.execution_mode() }
+
+    pub fn event_names(&self) -> &[String] { self.0.event_names() }
+
+    pub fn middleware_names(&self) -> &[String] { self.0.middleware_names() }
+
+    pub fn topology(&self) -> Topology { self.0.topology() }
+
+    pub fn config_snapshot(&self) -> Result<ConfigSnapshot, ChainError> { self.0.config_snapshot() }
+
+    pub fn config_fingerprint(&self) -> Result<String, ChainError> { self.0.config_fingerprint() }
+
+    pub fn event_tags(&self, index: usize) -> &[String] { self.0.event_tags(index) }
+
+    pub fn breaker_state(&self, name: &str) -> Result<Option<BreakerState>, ChainError> { self.0.breaker_state(name) }
+}
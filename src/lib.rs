@@ -0,0 +1,61 @@
+pub mod access;
+pub mod adapter;
+pub mod breaker;
+pub mod chain;
+pub mod cipher;
+pub mod context;
+pub mod definition;
+pub mod error;
+pub mod events;
+pub mod fault;
+pub mod fingerprint;
+pub mod frozen;
+pub mod memo;
+pub mod native;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+pub mod processor;
+pub mod runner;
+pub mod scheduler;
+pub mod stats;
+pub mod store;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod topology;
+pub mod value;
+pub mod verify;
+
+pub use access::{AccessReport, KeyPolicy, PreviewResult};
+pub use adapter::LuaEvent;
+pub use breaker::BreakerState;
+pub use chain::{
+    chain_depth, max_chain_depth, set_max_chain_depth, with_nested_chain, ChainBuilder, ChainRunResult, Collect,
+    EventChainExt, Payload, PayloadEvent, SubChain, SwitchEvent, DEFAULT_MAX_CHAIN_DEPTH,
+};
+pub use cipher::ContextCipher;
+pub use context::{Checkpoint, ContextView, ContextViewError, Entry, EventContextExt};
+pub use definition::{ChainDefinition, ChainMeta, ParseReport};
+pub use error::ChainError;
+pub use events::{AppendEvent, DelayEvent, IncrementEvent};
+pub use fault::{Fault, FaultInjector};
+pub use fingerprint::{ConfigDiff, ConfigSnapshot};
+pub use frozen::FrozenRunner;
+pub use memo::DEFAULT_MEMO_CAPACITY;
+pub use native::NativeRegistry;
+#[cfg(feature = "opentelemetry")]
+pub use otel::TRACEPARENT_KEY;
+pub use processor::{EventInput, StreamEvent};
+pub use runner::{
+    run_chain_file, ChainOutcome, ExecutionMode, LuaChainRunner, OutcomeStatus, TrialOutcome, MAX_MIDDLEWARE_DEPTH,
+};
+pub use scheduler::ChainScheduler;
+pub use stats::{BoundaryStats, ChainStats, EventStats, GcCollection, GcStats, DURATION_BUCKETS};
+pub use store::ContextStore;
+pub use topology::{EventNode, MiddlewareNode, Topology};
+pub use value::{
+    display_context, from_serialize, resolve_ref, ContextDisplay, ContextMap, ContextRef, ContextStream, ContextValue,
+    LuaTimestamp, MAX_REF_DEPTH,
+};
+#[cfg(feature = "decimal")]
+pub use value::LuaDecimal;
+pub use verify::ContextMismatch;
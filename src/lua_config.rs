@@ -1,30 +1,8 @@
 use std::time::Instant;
 use mlua::prelude::*;
 use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
-
-// ============================================================================
-// REGISTERED EVENTS (Rust implementations)
-// ============================================================================
-
-struct IncrementEvent;
-impl ChainableEvent for IncrementEvent {
-    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
-        let counter: i64 = context.get("counter").unwrap_or(0);
-        context.set("counter", counter + 1);
-        EventResult::Success(())
-    }
-    fn name(&self) -> &str { "increment" }
-}
-
-struct AppendEvent;
-impl ChainableEvent for AppendEvent {
-    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
-        let message: String = context.get("message").unwrap_or_default();
-        context.set("message", message + " -> processed");
-        EventResult::Success(())
-    }
-    fn name(&self) -> &str { "append" }
-}
+use lua_chains::value::table_to_map;
+use lua_chains::{AppendEvent, EventContextExt, IncrementEvent};
 
 // ============================================================================
 // ENUM WRAPPER (allows EventChain to work with any registered event)
@@ -57,19 +35,18 @@ fn main() -> LuaResult<()> {
 
     let hardcoded_start = Instant::now();
     let mut ctx = EventContext::new();
-    ctx.set("counter", 0i64);
-    ctx.set("message", "start".to_string());
+    ctx.set_value("counter", 0);
+    ctx.set_value("message", "start");
 
     let chain = EventChain::new()
-        .event(IncrementEvent)
-        .event(AppendEvent);
+        .event(IncrementEvent::default())
+        .event(AppendEvent::default());
 
     let result = chain.execute(&mut ctx);
     let hardcoded_duration = hardcoded_start.elapsed();
 
     println!("Execution time: {:?}", hardcoded_duration);
-    println!("Final counter: {:?}", ctx.get::<i64>("counter"));
-    println!("Final message: {:?}", ctx.get::<String>("message"));
+    println!("{}", ctx.display(&["counter", "message"]));
     println!("Result: {:?}\n", result.status);
 
     // ========================================================================
@@ -102,16 +79,13 @@ return {
     println!("Lua parsing time: {:?}", lua_parse_duration);
 
     // === EXTRACT CONTEXT FROM LUA ===
+    // nil means absent: keys set to nil never show up in `pairs`, so they are
+    // simply not present in the EventContext
     let context_table: LuaTable = chain_def.get("context")?;
     let mut context = EventContext::new();
 
-    for pair in context_table.pairs::<String, LuaValue>() {
-        let (key, value) = pair?;
-        match value {
-            LuaValue::Integer(i) => context.set(&key, i),
-            LuaValue::String(s) => context.set(&key, s.to_string_lossy().to_string()),
-            _ => {}
-        }
+    for (key, value) in table_to_map(&context_table)? {
+        value.write(&mut context, &key);
     }
     println!("Context extracted from Lua");
 
@@ -122,8 +96,8 @@ return {
 
     for name in event_names {
         let event: RegisteredEvent = match name.as_str() {
-            "increment" => RegisteredEvent::Increment(IncrementEvent),
-            "append" => RegisteredEvent::Append(AppendEvent),
+            "increment" => RegisteredEvent::Increment(IncrementEvent::default()),
+            "append" => RegisteredEvent::Append(AppendEvent::default()),
             _ => return Err(LuaError::RuntimeError(format!("Unknown event: {}", name))),
         };
         lua_chain = lua_chain.event(event);
@@ -137,8 +111,7 @@ return {
     let lua_exec_duration = lua_exec_start.elapsed();
 
     println!("EventChains execution time: {:?}", lua_exec_duration);
-    println!("Final counter: {:?}", context.get::<i64>("counter"));
-    println!("Final message: {:?}", context.get::<String>("message"));
+    println!("{}", context.display(&["counter", "message"]));
     println!("Result: {:?}\n", result.status);
 
     // ========================================================================
@@ -154,12 +127,12 @@ return {
     let hardcoded_repeated_start = Instant::now();
     for _ in 0..iterations {
         let mut ctx = EventContext::new();
-        ctx.set("counter", 0i64);
-        ctx.set("message", "start".to_string());
+        ctx.set_value("counter", 0);
+        ctx.set_value("message", "start");
 
         let chain = EventChain::new()
-            .event(IncrementEvent)
-            .event(AppendEvent);
+            .event(IncrementEvent::default())
+            .event(AppendEvent::default());
 
         let _result = chain.execute(&mut ctx);
     }
@@ -176,13 +149,8 @@ return {
         let context_table: LuaTable = chain_def.get("context")?;
         let mut context = EventContext::new();
 
-        for pair in context_table.pairs::<String, LuaValue>() {
-            let (key, value) = pair?;
-            match value {
-                LuaValue::Integer(i) => context.set(&key, i),
-                LuaValue::String(s) => context.set(&key, s.to_string_lossy().to_string()),
-                _ => {}
-            }
+        for (key, value) in table_to_map(&context_table)? {
+            value.write(&mut context, &key);
         }
 
         let event_names: Vec<String> = chain_def.get("events")?;
@@ -190,8 +158,8 @@ return {
 
         for name in event_names {
             let event: RegisteredEvent = match name.as_str() {
-                "increment" => RegisteredEvent::Increment(IncrementEvent),
-                "append" => RegisteredEvent::Append(AppendEvent),
+                "increment" => RegisteredEvent::Increment(IncrementEvent::default()),
+                "append" => RegisteredEvent::Append(AppendEvent::default()),
                 _ => return Err(LuaError::RuntimeError(format!("Unknown event: {}", name))),
             };
             lua_chain = lua_chain.event(event);
@@ -1,6 +1,8 @@
+use std::rc::Rc;
 use std::time::Instant;
 use mlua::prelude::*;
 use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
+use lua_chains::{display_context, AppendEvent, EventContextExt, IncrementEvent, LuaChainRunner};
 
 // ============================================================================
 // THREAD-LOCAL LUA CONTEXT
@@ -9,7 +11,7 @@ use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
 // This works within a single thread (production scenario: one thread per Lua chain)
 
 thread_local! {
-    static LUA_VM: std::cell::RefCell<Option<Lua>> = std::cell::RefCell::new(None);
+    static LUA_VM: std::cell::RefCell<Option<Lua>> = const { std::cell::RefCell::new(None) };
 }
 
 fn init_lua_vm(script: &str) -> LuaResult<()> {
@@ -76,31 +78,66 @@ impl ChainableEvent for LuaEventWrapper {
     fn name(&self) -> &str { &self.name }
 }
 
-fn main() -> LuaResult<()> {
-    println!("{}\n", "=".repeat(70));
-    println!("HARDCODED RUST CHAIN (baseline):");
-    println!("{}\n", "=".repeat(70));
+// Two events over a counter and a message, shared by both modes
+const SCRIPT: &str = r#"
+return {
+  context = {
+    counter = 0,
+    message = "start"
+  },
+  events = {
+    {
+      name = "increment",
+      handler = function(ctx)
+        ctx.counter = ctx.counter + 1
+        return ctx
+      end
+    },
+    {
+      name = "append",
+      handler = function(ctx)
+        ctx.message = ctx.message .. " -> processed"
+        return ctx
+      end
+    }
+  }
+}
+"#;
 
-    // Hardcoded events for baseline
-    struct IncrementEvent;
-    impl ChainableEvent for IncrementEvent {
-        fn execute(&self, context: &mut EventContext) -> EventResult<()> {
-            let counter: i64 = context.get("counter").unwrap_or(0);
-            context.set("counter", counter + 1);
-            EventResult::Success(())
-        }
-        fn name(&self) -> &str { "increment" }
+// Run a chain once and print its final context: the script at the path
+// given as the first argument, the one piped to stdin if that is `-`, or the
+// built-in SCRIPT without arguments. With `--bench`, compare the built-in
+// chain against the hardcoded native chain and time repeated and batch
+// execution.
+fn main() -> LuaResult<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--bench") {
+        return run_benchmarks();
     }
 
-    struct AppendEvent;
-    impl ChainableEvent for AppendEvent {
-        fn execute(&self, context: &mut EventContext) -> EventResult<()> {
-            let message: String = context.get("message").unwrap_or_default();
-            context.set("message", message + " -> processed");
-            EventResult::Success(())
+    let script = match args.first().map(String::as_str) {
+        None => SCRIPT.to_string(),
+        Some("-") => {
+            let script = std::io::read_to_string(std::io::stdin()).map_err(LuaError::external)?;
+            if script.trim().is_empty() {
+                return Err(LuaError::external("no chain definition on stdin"));
+            }
+            script
         }
-        fn name(&self) -> &str { "append" }
-    }
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| LuaError::external(format!("cannot read {}: {}", path, e)))?,
+    };
+    let runner = LuaChainRunner::from_script(Rc::new(Lua::new()), &script).map_err(LuaError::external)?;
+    let outcome = runner.execute().map_err(LuaError::external)?;
+    println!("{}", display_context(&outcome.context));
+    println!("Status: {:?} ({} events in {:?})", outcome.status, outcome.events_executed, outcome.duration);
+    Ok(())
+}
+
+fn run_benchmarks() -> LuaResult<()> {
+    println!("{}\n", "=".repeat(70));
+    println!("HARDCODED RUST CHAIN (baseline):");
+    println!("{}\n", "=".repeat(70));
 
     let hardcoded_start = Instant::now();
     let mut ctx = EventContext::new();
@@ -108,15 +145,14 @@ fn main() -> LuaResult<()> {
     ctx.set("message", "start".to_string());
 
     let chain = EventChain::new()
-        .event(IncrementEvent)
-        .event(AppendEvent);
+        .event(IncrementEvent::default())
+        .event(AppendEvent::default());
 
     let result = chain.execute(&mut ctx);
     let hardcoded_duration = hardcoded_start.elapsed();
 
     println!("Execution time: {:?}", hardcoded_duration);
-    println!("Final counter: {:?}", ctx.get::<i64>("counter"));
-    println!("Final message: {:?}", ctx.get::<String>("message"));
+    println!("{}", ctx.display(&["counter", "message"]));
     println!("Result: {:?}\n", result.status);
 
     // ========================================================================
@@ -126,35 +162,10 @@ fn main() -> LuaResult<()> {
     println!("LUA-DEFINED EVENTS (handlers in Lua, executed via EventChains):");
     println!("{}\n", "=".repeat(70));
 
-    // Lua script: define events with their handlers
-    let script = r#"
-return {
-  context = {
-    counter = 0,
-    message = "start"
-  },
-  events = {
-    {
-      name = "increment",
-      handler = function(ctx)
-        ctx.counter = ctx.counter + 1
-        return ctx
-      end
-    },
-    {
-      name = "append",
-      handler = function(ctx)
-        ctx.message = ctx.message .. " -> processed"
-        return ctx
-      end
-    }
-  }
-}
-"#;
 
     // === PARSE & INITIALIZE LUA ===
     let lua_parse_start = Instant::now();
-    init_lua_vm(script)?;
+    init_lua_vm(SCRIPT)?;
     let lua_parse_duration = lua_parse_start.elapsed();
     println!("Lua parsing & initialization: {:?}", lua_parse_duration);
 
@@ -224,8 +235,8 @@ return {
         ctx.set("message", "start".to_string());
 
         let chain = EventChain::new()
-            .event(IncrementEvent)
-            .event(AppendEvent);
+            .event(IncrementEvent::default())
+            .event(AppendEvent::default());
 
         let _result = chain.execute(&mut ctx);
     }
@@ -257,6 +268,37 @@ return {
     println!("Lua ({}x, chain reused): {:?}", iterations, lua_repeated_duration);
     println!("Lua per-iteration: {:.2}µs\n", lua_per_iter as f64);
 
+    // ========================================================================
+    // BATCH EXECUTION (one runner, fresh context per input)
+    // ========================================================================
+    println!("{}\n", "=".repeat(70));
+    println!("BATCH EXECUTION ({} inputs, LuaChainRunner):", iterations);
+    println!("{}\n", "=".repeat(70));
+
+    let runner_lua = Rc::new(Lua::new());
+    let runner = LuaChainRunner::from_script(runner_lua.clone(), SCRIPT).map_err(LuaError::external)?;
+
+    let mut inputs = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let input = runner_lua.create_table()?;
+        input.set("counter", 0i64)?;
+        input.set("message", "start")?;
+        inputs.push(input);
+    }
+
+    let batch_start = Instant::now();
+    let outcomes = runner.execute_batch(inputs);
+    let batch_duration = batch_start.elapsed();
+    let batch_per_input = batch_duration.as_micros() / iterations as u128;
+    let all_ok = outcomes.iter().all(|outcome| {
+        matches!(outcome, Ok(o) if o.context.get("counter") == Some(&lua_chains::ContextValue::Int(1)))
+    });
+
+    println!("Setup (parse + register, paid once): {:?}", runner.setup_duration());
+    println!("Batch ({}x): {:?}", iterations, batch_duration);
+    println!("Batch per-input: {:.2}µs", batch_per_input as f64);
+    println!("Every input started from its own context: {}\n", all_ok);
+
     // ========================================================================
     // FINAL COMPARISON
     // ========================================================================
@@ -0,0 +1,136 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use mlua::prelude::*;
+
+use crate::value::{ContextMap, ContextValue};
+
+// ============================================================================
+// MEMOIZED PURE EVENTS
+// ============================================================================
+// An event declared `pure = true` promises that its result depends only on
+// the context it is given. The runner then keeps, per pure event, a cache
+// from input context to output context; when the same input comes round
+// again (in this run or a later one) the cached output is applied and the
+// handler is not called. Middleware still runs around the event.
+//
+// The whole context is the input: keys are hashed in sorted order and a hit
+// also compares the stored input, so a hash collision never returns another
+// input's output. Only contexts made entirely of owned values are memoized;
+// a context holding tables, functions or streams (whose next item is not
+// part of its value), or with lazy keys, aliases or references in its
+// metatable, runs the handler as usual. Failures are never cached, nor are
+// results produced by `on_error`.
+//
+// Each cache holds at most `with_memo_capacity` entries (DEFAULT_MEMO_CAPACITY
+// unless set) and forgets the oldest entry first; `clear_memo` empties them.
+
+/// Entries each pure event's cache holds unless the runner is configured
+/// otherwise.
+pub const DEFAULT_MEMO_CAPACITY: usize = 128;
+
+#[derive(Debug)]
+pub(crate) struct MemoCache {
+    capacity: usize,
+    entries: HashMap<u64, (ContextMap, ContextMap)>,
+    // Hashes in insertion order, oldest first
+    order: VecDeque<u64>,
+}
+
+impl MemoCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    pub(crate) fn get(&self, hash: u64, input: &ContextMap) -> Option<&ContextMap> {
+        match self.entries.get(&hash) {
+            Some((stored, output)) if stored == input => Some(output),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, hash: u64, input: ContextMap, output: ContextMap) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(hash, (input, output)).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize { self.capacity }
+
+    pub(crate) fn len(&self) -> usize { self.entries.len() }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// The owned form of `context` and its hash, or `None` if it cannot be
+/// memoized.
+pub(crate) fn memo_input(context: &LuaTable) -> LuaResult<Option<(u64, ContextMap)>> {
+    if context.get_metatable().is_some() {
+        return Ok(None);
+    }
+    let mut input = ContextMap::new();
+    for pair in context.clone().pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        let (LuaValue::String(key), Some(value)) = (key, ContextValue::from_lua(&value)) else {
+            return Ok(None);
+        };
+        if matches!(value, ContextValue::Stream(_)) {
+            return Ok(None);
+        }
+        input.insert(key.to_str()?.to_string(), value);
+    }
+    let mut keys: Vec<&String> = input.keys().collect();
+    keys.sort();
+    let mut hasher = DefaultHasher::new();
+    for key in keys {
+        key.hash(&mut hasher);
+        hash_value(&input[key], &mut hasher);
+    }
+    Ok(Some((hasher.finish(), input)))
+}
+
+fn hash_value(value: &ContextValue, hasher: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        ContextValue::Int(i) => i.hash(hasher),
+        ContextValue::Float(n) => n.to_bits().hash(hasher),
+        ContextValue::Str(s) | ContextValue::Ref(s) => s.hash(hasher),
+        ContextValue::Bool(b) => b.hash(hasher),
+        ContextValue::Bytes(bytes) => bytes.hash(hasher),
+        ContextValue::Timestamp(time) => time.hash(hasher),
+        #[cfg(feature = "decimal")]
+        ContextValue::Decimal(decimal) => decimal.hash(hasher),
+        ContextValue::Stream(_) => {}
+    }
+}
+
+/// Turn `context`, which held `input`, into `output`.
+pub(crate) fn apply_output(context: &LuaTable, input: &ContextMap, output: &ContextMap) -> LuaResult<()> {
+    for key in input.keys().filter(|key| !output.contains_key(*key)) {
+        context.raw_set(key.as_str(), LuaValue::Nil)?;
+    }
+    for (key, value) in output {
+        context.raw_set(key.as_str(), value.clone())?;
+    }
+    Ok(())
+}
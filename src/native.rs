@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use event_chains::{ChainableEvent, EventContext, EventResult};
+use mlua::prelude::*;
+
+use crate::access;
+use crate::events::{AppendEvent, IncrementEvent};
+use crate::runner;
+use crate::value::{table_to_map, ContextValue};
+
+// ============================================================================
+// NATIVE EVENT REGISTRY
+// ============================================================================
+// Rust events that chain definitions can reference by name. The registry is
+// stored per Lua VM (as app data) and starts with the demo events
+// "increment" and "append".
+//
+// A native event is bridged as a Lua function: the Lua context is copied into
+// an EventContext, the event runs, and the keys that were present are copied
+// back. EventContext cannot enumerate or remove keys, so keys a native event
+// creates are not visible to the Lua side, and a native event cannot delete
+// a key; keys already absent (nil) in Lua are never passed in, and neither
+// are values with no ContextValue form (userdata, functions, tables), which
+// stay in the Lua table untouched.
+
+pub struct NativeRegistry {
+    events: HashMap<String, Rc<dyn ChainableEvent>>,
+}
+
+impl NativeRegistry {
+    pub fn register(&mut self, name: &str, event: Rc<dyn ChainableEvent>) {
+        self.events.insert(name.to_string(), event);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn ChainableEvent>> {
+        self.events.get(name).cloned()
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.events.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl Default for NativeRegistry {
+    fn default() -> Self {
+        let mut registry = Self { events: HashMap::new() };
+        registry.register("increment", Rc::new(IncrementEvent::default()));
+        registry.register("append", Rc::new(AppendEvent::default()));
+        registry
+    }
+}
+
+/// Register `event` under `name` in the registry attached to `lua`.
+pub fn register(lua: &Lua, name: &str, event: Rc<dyn ChainableEvent>) {
+    install(lua);
+    if let Some(mut registry) = lua.app_data_mut::<NativeRegistry>() {
+        registry.register(name, event);
+    }
+}
+
+/// Look up `name` in the registry attached to `lua` (built-ins included).
+pub fn lookup(lua: &Lua, name: &str) -> Option<Rc<dyn ChainableEvent>> {
+    install(lua);
+    lua.app_data_ref::<NativeRegistry>()?.get(name)
+}
+
+/// Names registered in the registry attached to `lua` (built-ins included),
+/// sorted.
+pub fn names(lua: &Lua) -> Vec<String> {
+    install(lua);
+    lua.app_data_ref::<NativeRegistry>().map(|registry| registry.names()).unwrap_or_default()
+}
+
+// Attach the default registry the first time a VM's registry is used
+fn install(lua: &Lua) {
+    if lua.app_data_ref::<NativeRegistry>().is_none() {
+        lua.set_app_data(NativeRegistry::default());
+    }
+}
+
+/// Wrap a native event as a Lua handler `function(ctx) -> ctx`.
+pub fn bridge<'lua>(lua: &'lua Lua, event: Rc<dyn ChainableEvent>) -> LuaResult<LuaFunction<'lua>> {
+    lua.create_function(move |lua, ctx: LuaTable| {
+        let ctx = access::unwrap(ctx)?;
+        let mut context = EventContext::new();
+        let mut given = Vec::new();
+        for (key, value) in table_to_map(&ctx)? {
+            // References are handed over as the values they resolve to;
+            // one that does not resolve (a cycle) is left out
+            let value = match value {
+                ContextValue::Ref(_) => match ctx.get(key.as_str()).ok().and_then(|v| ContextValue::from_lua(&v)) {
+                    Some(value) => value,
+                    None => continue,
+                },
+                value => value,
+            };
+            value.clone().write(&mut context, &key);
+            given.push((key, value));
+        }
+
+        match event.execute(&mut context) {
+            EventResult::Success(()) => {}
+            EventResult::Failure(msg) | EventResult::MiddlewareFailure(msg) => {
+                return Err(LuaError::RuntimeError(format!("{}: {}", event.name(), msg)));
+            }
+        }
+
+        // Only changed keys are written back, so unchanged refs stay refs
+        for (key, value) in given {
+            if let Some(updated) = ContextValue::read(&context, &key)
+                && updated != value
+            {
+                runner::store_value(lua, &ctx, key, updated)?;
+            }
+        }
+        Ok(ctx)
+    })
+}
@@ -0,0 +1,164 @@
+use mlua::prelude::*;
+use opentelemetry::trace::{SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+// ============================================================================
+// OPENTELEMETRY
+// ============================================================================
+// With the `opentelemetry` feature (which implies `tracing`) the runner's
+// `tracing` spans carry what an OpenTelemetry backend needs. The crate does
+// not export anything itself: the host installs a
+// `tracing_opentelemetry::layer()` with its own tracer and exporter, and
+// every `chain_run` span then becomes an OTel span whose `event` spans are
+// its children, each with the `name`, `index`, `status` and `duration_ms`
+// attributes and an OTel status (Ok, or Error with the message; an abort
+// leaves it unset).
+//
+// A run joins an incoming trace when its starting context (from
+// `execute_with_context`, a store or the definition) holds a W3C
+// `traceparent` string under TRACEPARENT_KEY, e.g.
+// "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01": the run span
+// becomes a child of that remote span. A malformed header is ignored and
+// the run span nests under the caller's current span as usual. The key
+// stays in the context like any other.
+
+/// Context key whose W3C `traceparent` value parents a run's span.
+pub const TRACEPARENT_KEY: &str = "traceparent";
+
+/// Make `span` a child of the remote span named in `context`, if any.
+pub(crate) fn adopt_parent(span: &tracing::Span, context: &LuaTable) {
+    let Ok(Some(header)) = context.raw_get::<_, Option<String>>(TRACEPARENT_KEY) else {
+        return;
+    };
+    if let Some(parent) = parse_traceparent(&header) {
+        // Fails only if the span is disabled or already started
+        let _ = span.set_parent(Context::new().with_remote_span_context(parent));
+    }
+}
+
+/// Set an event span's OTel status from how the event ended.
+pub(crate) fn set_status(span: &tracing::Span, status: &str, error: Option<&LuaError>) {
+    match (status, error) {
+        ("ok", _) => span.set_status(Status::Ok),
+        ("error", Some(error)) => span.set_status(Status::error(error.to_string())),
+        _ => {}
+    }
+}
+
+// version-trace_id-parent_id-flags, all lowercase hex
+fn parse_traceparent(header: &str) -> Option<SpanContext> {
+    let mut parts = header.trim().split('-');
+    let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    let hex = |part: &str, len: usize| part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    if !hex(version, 2) || version == "ff" || !hex(trace_id, 32) || !hex(span_id, 16) || !hex(flags, 2) {
+        return None;
+    }
+    // Version 00 has exactly four fields; later versions may append more
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+    let trace_id = TraceId::from_hex(trace_id).ok()?;
+    let span_id = SpanId::from_hex(span_id).ok()?;
+    if trace_id == TraceId::INVALID || span_id == SpanId::INVALID {
+        return None;
+    }
+    let flags = TraceFlags::new(u8::from_str_radix(flags, 16).ok()? & TraceFlags::SAMPLED.to_u8());
+    Some(SpanContext::new(trace_id, span_id, flags, true, TraceState::default()))
+}
+
+#[cfg(all(test, feature = "opentelemetry"))]
+mod tests {
+    use std::rc::Rc;
+
+    use opentelemetry::trace::{Status, TracerProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::runner::LuaChainRunner;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    const CHAIN: &str = r#"return {
+        events = {
+            { name = "ok", handler = function(ctx) return ctx end },
+            { name = "fails", handler = function(ctx) error("boom") end },
+        },
+    }"#;
+
+    // The spans `run` produces, as exported through an OTel layer
+    fn exported(run: impl FnOnce(&LuaChainRunner)) -> Vec<SpanData> {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("lua_chains"));
+        let runner = LuaChainRunner::from_script(Rc::new(Lua::new()), CHAIN).unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || run(&runner));
+        provider.force_flush().unwrap();
+        exporter.get_finished_spans().unwrap()
+    }
+
+    fn span<'a>(spans: &'a [SpanData], name: &str, event: Option<&str>) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|span| {
+                span.name == name
+                    && event.is_none_or(|event| {
+                        span.attributes.iter().any(|kv| kv.key.as_str() == "name" && kv.value.as_str() == event)
+                    })
+            })
+            .unwrap_or_else(|| panic!("no {name} span for {event:?}"))
+    }
+
+    #[test]
+    fn event_spans_are_children_of_the_run_span() {
+        let spans = exported(|runner| assert!(runner.execute().is_err()));
+        let run = span(&spans, "chain_run", None);
+        let (ok, fails) = (span(&spans, "event", Some("ok")), span(&spans, "event", Some("fails")));
+        for event in [ok, fails] {
+            assert_eq!(event.parent_span_id, run.span_context.span_id());
+            assert_eq!(event.span_context.trace_id(), run.span_context.trace_id());
+        }
+        assert_eq!(ok.status, Status::Ok);
+        assert!(matches!(&fails.status, Status::Error { description } if description.contains("boom")));
+    }
+
+    #[test]
+    fn a_traceparent_in_the_context_parents_the_run() {
+        let spans = exported(|runner| {
+            let context = runner.lua().create_table().unwrap();
+            context.set(TRACEPARENT_KEY, TRACEPARENT).unwrap();
+            let _ = runner.execute_with_context(context);
+        });
+        let run = span(&spans, "chain_run", None);
+        let parent = parse_traceparent(TRACEPARENT).unwrap();
+        assert_eq!(run.span_context.trace_id(), parent.trace_id());
+        assert_eq!(run.parent_span_id, parent.span_id());
+        assert!(run.parent_span_is_remote);
+    }
+
+    #[test]
+    fn a_malformed_traceparent_is_ignored() {
+        let spans = exported(|runner| {
+            let context = runner.lua().create_table().unwrap();
+            context.set(TRACEPARENT_KEY, "00-not-a-header-01").unwrap();
+            let _ = runner.execute_with_context(context);
+        });
+        assert_eq!(span(&spans, "chain_run", None).parent_span_id, SpanId::INVALID);
+    }
+
+    #[test]
+    fn traceparent_parsing() {
+        let parsed = parse_traceparent(TRACEPARENT).unwrap();
+        assert_eq!(parsed.trace_id(), TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap());
+        assert!(parsed.is_sampled() && parsed.is_remote());
+        for bad in [
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert!(parse_traceparent(bad).is_none(), "{bad}");
+        }
+    }
+}
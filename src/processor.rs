@@ -0,0 +1,146 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Instant, SystemTime};
+
+use crate::error::ChainError;
+use crate::runner::{new_run_id, ChainOutcome, LuaChainRunner, OutcomeStatus};
+use crate::value::{ContextMap, ContextValue};
+
+// ============================================================================
+// STREAMING EXECUTION
+// ============================================================================
+// `LuaChainRunner::execute_stream` turns a runner into a long-lived
+// processor: it takes EventInputs off a channel one at a time and runs each
+// against a context that evolves from message to message, starting from
+// the definition's context and defaults. Every message produces exactly one
+// result on the output channel:
+//
+// - the event's overrides are applied to the context, then the event runs
+//   once; the outcome reports the context after it, with status Completed
+//   and `events_executed` 1;
+// - a failed event (or an unknown name, or an inline source that does not
+//   compile) sends the error and leaves the context as it was before the
+//   message, overrides included; the stream carries on;
+// - `chain.abort_chain()` sends an Aborted outcome with that context and
+//   ends the stream, leaving any later messages in the channel.
+//
+// A message names one of the chain's events, which runs as in a
+// ChainScheduler (handler, args, on_error, return mode, middleware), or
+// carries an inline handler body compiled like an event's `source`, which
+// runs bare: no middleware, no event options. Its errors report the chain's
+// event count as their index, since it is not one of the chain's events.
+// Run-wide features (finalize, cleanup, once, stop_when, checkpoints,
+// stats) do not apply. All outcomes share one run ID.
+//
+// The stream ends when every sender of the input channel is dropped, or the
+// receiver of the output channel is. The runner is not Send, so
+// `execute_stream` blocks the calling thread; producers and consumers run
+// on other threads. Backpressure is the caller's choice of input channel:
+// with `mpsc::sync_channel(n)` producers block once `n` messages wait, while
+// `mpsc::channel()` queues without bound. Outputs go to an unbounded
+// Sender, so the runner never waits on a slow consumer; its queue grows
+// instead.
+
+/// What a streamed message runs; see [`EventInput`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// The first event of the chain with this name
+    Named(String),
+    /// A handler body, as in an event's `source`
+    Inline { name: String, source: String },
+}
+
+/// One message for [`LuaChainRunner::execute_stream`].
+#[derive(Debug, Clone)]
+pub struct EventInput {
+    pub event: StreamEvent,
+    /// Set on the context before the event runs
+    pub overrides: ContextMap,
+}
+
+impl EventInput {
+    pub fn named(event: &str) -> Self {
+        Self { event: StreamEvent::Named(event.to_string()), overrides: ContextMap::new() }
+    }
+
+    pub fn inline(name: &str, source: &str) -> Self {
+        Self {
+            event: StreamEvent::Inline { name: name.to_string(), source: source.to_string() },
+            overrides: ContextMap::new(),
+        }
+    }
+
+    pub fn with_override(mut self, key: &str, value: impl Into<ContextValue>) -> Self {
+        self.overrides.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+impl LuaChainRunner {
+    /// Process messages from `inputs` until it closes (or `outputs` does),
+    /// sending one result per message to `outputs`; see processor.rs.
+    /// Returns the context as the last message left it.
+    pub fn execute_stream(
+        &self,
+        inputs: Receiver<EventInput>,
+        outputs: Sender<Result<ChainOutcome, ChainError>>,
+    ) -> Result<ContextMap, ChainError> {
+        let run_id = new_run_id();
+        let mut context = self.initial_map()?;
+        for input in inputs {
+            context.extend(input.overrides);
+            let start = Instant::now();
+            let result = match &input.event {
+                StreamEvent::Named(name) => self
+                    .event_names()
+                    .iter()
+                    .position(|event| event == name)
+                    .ok_or_else(|| ChainError::UnknownEvent(name.clone()))
+                    .and_then(|index| match self.execute_event_map(index, &context, &run_id) {
+                        Err(ChainError::Lua(e)) if !self.abort_requested() => Err(self.event_error(index, &run_id, e)),
+                        result => result,
+                    }),
+                StreamEvent::Inline { name, source } => match self.execute_source_map(name, source, &context, &run_id) {
+                    Err(ChainError::Lua(e)) if !self.abort_requested() => Err(ChainError::EventFailed {
+                        chain: self.meta().name.clone(),
+                        run_id: run_id.clone(),
+                        index: self.event_names().len(),
+                        name: name.clone(),
+                        message: e.to_string(),
+                    }),
+                    result => result,
+                },
+            };
+            // Checked before the result: an abort unwinds as a Lua error
+            let aborted = self.abort_requested();
+            let sent = match result {
+                _ if aborted => outputs.send(Ok(outcome(&run_id, OutcomeStatus::Aborted, context.clone(), 0, start))),
+                Ok(updated) => {
+                    context = updated;
+                    outputs.send(Ok(outcome(&run_id, OutcomeStatus::Completed, context.clone(), 1, start)))
+                }
+                Err(e) => outputs.send(Err(e)),
+            };
+            if aborted || sent.is_err() {
+                break;
+            }
+        }
+        Ok(context)
+    }
+}
+
+fn outcome(run_id: &str, status: OutcomeStatus, context: ContextMap, events_executed: usize, start: Instant) -> ChainOutcome {
+    ChainOutcome {
+        run_id: run_id.to_string(),
+        status,
+        context,
+        events_executed,
+        duration: start.elapsed(),
+        finished_at: SystemTime::now(),
+        middleware_trace: None,
+        access_report: None,
+        gc: None,
+        boundary: None,
+        no_change_warnings: None,
+        lua_warnings: None,
+    }
+}
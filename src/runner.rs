@@ -0,0 +1,3288 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::c_void;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use event_chains::ChainableEvent;
+use mlua::prelude::*;
+use mlua::ChunkMode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::access::{self, AccessReport, AccessTracker, KeyPolicy, PreviewResult};
+use crate::breaker::{BreakerState, CircuitBreaker};
+use crate::cipher::{self, ContextCipher};
+use crate::definition::{ChainDefinition, ChainMeta, ParseReport};
+use crate::error::ChainError;
+use crate::fault::FaultInjector;
+use crate::fingerprint::ConfigSnapshot;
+use crate::memo::{apply_output, memo_input, MemoCache, DEFAULT_MEMO_CAPACITY};
+use crate::native;
+use crate::stats::{BoundaryStats, ChainStats, GcCollection, GcStats};
+use crate::store::ContextStore;
+use crate::topology::{EventNode, MiddlewareNode, Topology};
+use crate::value::{
+    deserialize_map, map_to_table, table_to_map, table_to_map_with, ContextMap, ContextRef, ContextValue, LuaTimestamp, MAX_REF_DEPTH,
+    REFS_FIELD,
+};
+
+// ============================================================================
+// LUA CHAIN RUNNER
+// ============================================================================
+// Parses a chain definition once and keeps its handlers in the Lua registry.
+// Events run FIFO; middleware wraps every event LIFO (last declared is the
+// outermost layer). The working context lives in the `__context` global.
+//
+// Shared middleware (or handlers) can live in modules that definitions pull
+// in with `require`. Hosts register them on the VM before loading a chain:
+// `preload_module` for Lua source, `preload_module_table` for a table built
+// in Rust, or `add_module_path` to search a directory for `<name>.lua`.
+//
+// Threading: a runner holds an `Rc<Lua>` and Cell-based run state, so it is
+// neither Send nor Sync. mlua 0.9's `send` feature makes `Lua` Send but never
+// Sync, and one Lua state cannot execute two coroutines in parallel, so
+// concurrent runs need one VM (and runner) per thread.
+//
+// Definition format:
+//
+//   return {
+//     meta = { name = "...", version = "...",            -- optional
+//              description = "...", tags = { ... } },
+//     context = { ... },                                  -- optional
+//     events = { { name = "...", handler = function(ctx, args) ... end }, ... },
+//                                          -- or `native = "increment"`
+//                                          -- or `source = "ctx.n = 1"`
+//                                          -- optional `tags = { "cleanup" }`
+//                                          -- optional `args = { factor = 3 }`
+//                                          -- optional `weight = 2` (default 1)
+//                                          -- optional `return_mode = "patch"`
+//                                          -- optional `on_error = function(ctx, err)`
+//                                          -- optional `once = true`
+//                                          -- optional `allowed_keys = { ... }`
+//                                          -- optional `denied_keys = { ... }`
+//                                          -- optional `bypass_middleware = true`
+//                                          -- optional `circuit_breaker = { threshold = 3, cooldown_ms = 1000 }`
+//                                          -- optional `pure = true` (see memo.rs)
+//                                          -- optional `pre = function(ctx)`, `post = function(ctx)`
+//                                          -- optional `on_pre_failure = "skip"` (default "error")
+//     middleware = { { name = "...", handler = function(ctx, next) ... end } },
+//                                          -- or `observational = true` with
+//                                          -- `handler = function(ctx) ... end`
+//                                          -- optional `sample_rate = 0.1` (default 1)
+//                                          -- optional `applies_to = function(event_name, ctx)`
+//     stop_when = function(ctx) ... end,                  -- optional
+//     expect = { key = value, ... },                      -- optional, see verify.rs
+//     finalize = function(ctx) ... end,                   -- optional
+//     on_shutdown = function() ... end,                   -- optional
+//     execution_mode = "weighted_random",                 -- optional, or "shuffle"
+//     return_mode = "patch",                  -- optional default for events
+//     immutable_context = true,                           -- optional
+//   }
+//
+// An event's `source` is the body of its handler, compiled at load time as
+// `function(ctx, args) <source> end` under the chunk name "event[i]:name",
+// so it may end with `return ctx` like any handler.
+// Handlers return the (possibly new) context table; returning nil keeps the
+// table that was passed in. Setting a key to nil removes it from the
+// context. Any Lua value can live in the context, including userdata
+// wrapping a Rust object: it reaches later events by reference,
+// unserialized. Owned snapshots (the outcome's context, checkpoints, stores)
+// only hold scalars and skip the rest; after a run the full table is still
+// in the `__context` global of `lua()`. Userdata in the definition's
+// `context` is shared by every run, since only tables are deep-copied.
+// In "patch" return mode a handler instead returns only the keys it changed,
+// and they are merged onto the context; a patch cannot remove keys, since a
+// returned table cannot hold nil.
+// With `immutable_context = true` handlers, `on_error` and middleware get a
+// read-only proxy of the context (assigning to it raises) and every event
+// runs in patch mode, so the returned table is the event's exact set of
+// changes. Native events and `finalize` still see the real table.
+// An event with `allowed_keys` and/or `denied_keys` gets a proxy that raises
+// when its handler (or `on_error`) reads or assigns a refused key, and that
+// `pairs` iterates without them. Such an event always runs in patch mode, and
+// a returned patch touching a refused key fails the event. While its handler
+// (or `on_error`) runs, the `__context` global holds the same proxy, and
+// `chain.lazy`, `chain.ref` and `chain.set_ttl` refuse keys the policy
+// refuses; a producer passed to `chain.lazy` reads through the proxy too,
+// whenever it runs. The policy does not bind middleware, and native events
+// cannot declare one.
+// `args` is the event's own `args` table (empty if none was declared), so one
+// handler function can back several differently-configured events. It is
+// shared by every run; handlers should treat it as read-only.
+//
+// Events tagged "cleanup" run in their normal position, and also when the run
+// stops early (failure, abort or timeout): every cleanup event after the stop
+// point then runs in declaration order against the context as it stands, and
+// the original error is returned. Errors raised by cleanup events are ignored.
+//
+// Observational middleware (logging, metrics) stays out of the LIFO stack:
+// before each event, every observer is called in declaration order with a
+// read-only proxy of the context as it stands before the event, so they all
+// see the same input and cannot change it. Their return values are ignored;
+// raising still fails the event. The middleware trace lists observers first.
+//
+// A `once` event runs on the first execute that reaches it and completes it,
+// then is skipped (and never drawn in "weighted_random" mode) by later
+// executes of the same runner, until `reset_once_flags`. A once event that
+// fails stays armed.
+//
+// An event declared `bypass_middleware = true` is called directly: no
+// middleware layer or observer runs around it and it does not appear in
+// middleware traces. This saves the `next` closures and calls per layer for
+// hot events that need no cross-cutting behaviour.
+//
+// A middleware with `sample_rate` below 1 runs around only that fraction of
+// events: before each event it would wrap (or observe), the runner draws from
+// its RNG and skips the layer on a miss, so it is left out of that event's
+// trace too. Rate 0 never runs and rate 1 (the default) never draws. Draws
+// share the RNG with the execution modes, so with `with_seed` the same
+// sequence of runs over the same inputs samples the same events.
+//
+// A middleware's `applies_to(event_name, ctx)` decides at run time whether
+// it wraps (or observes) the event about to run, e.g. tracing only while
+// `ctx.debug` is set. It gets a read-only view of the live context, and a
+// falsy result skips the layer for that event as a sample miss does. It is
+// checked after a hit on `sample_rate`, before the layer would be entered,
+// so it costs one Lua call per middleware per event on every run, whether
+// or not the layer then runs; an error raised by it fails the event.
+//
+// An event's `on_error` runs when its handler raises (not on abort) with the
+// context and the error message. Its return value is treated like the
+// handler's, so the event succeeds with that context; raising from `on_error`
+// fails the event as before. Middleware around the event sees the recovered
+// result, not the error.
+//
+// An event's `pre` and `post` predicates state its contract. `pre` is called
+// with a read-only view of the context before the event (ahead of observers
+// and middleware) and `post` with the context the event produced, after its
+// middleware and `on_error` have run. A falsy `pre` fails the event with
+// "precondition failed for event[i]:name", or with `on_pre_failure = "skip"`
+// skips it, leaving the context as it was. A falsy `post` always fails the
+// event with "postcondition violated by event[i]:name": the handler broke
+// its contract, so `on_error` is not consulted. Errors raised by either
+// predicate fail the event too.
+//
+// `stop_when` is called with a read-only context after every successful
+// event, once its checkpoint has fired. When it returns a truthy value the
+// run ends there with status `StoppedEarly`, like a loop guard: cleanup
+// events still run, the rest do not. An error raised by it fails the run
+// like a failure of the event just run, cleanup events included.
+//
+// `finalize` runs once after the last event of a completed, aborted or
+// stopped run (not a failed one) and may transform the context, e.g. to strip scratch
+// keys; its result is what the outcome reports. It sees the context after
+// every checkpoint has fired.
+//
+// `on_shutdown` runs when the runner is dropped, before any teardown
+// registered with `with_teardown`, to release resources opened during setup.
+//
+// In "weighted_random" mode each execute runs exactly one event, drawn with
+// probability proportional to its weight; cleanup tags have no effect. Seed
+// the runner with `with_seed` for reproducible draws.
+//
+// "shuffle" mode runs every event in a fresh random order each execute, to
+// flush out handlers that silently depend on running after another one. It is
+// a testing aid, not a production mode. Middleware wraps each event as usual
+// and cleanup tags have no effect. With `with_seed` the sequence of orders is
+// reproducible, so a failing order can be replayed.
+//
+// During a run the `chain` global exposes helpers to handlers and middleware:
+//
+//   chain.abort_chain()   -- stop the whole run; remaining events never start
+//   chain.run_id()        -- identifier of the current run, for correlation
+//   chain.sleep(ms)       -- block the run for `ms` milliseconds
+//   chain.now()           -- the current time, as a timestamp
+//   chain.timestamp(ms)   -- a timestamp `ms` milliseconds after the epoch
+//   chain.decimal(s)      -- an exact decimal from a string or integer
+//                            (with the `decimal` feature)
+//   chain.set_ttl(ctx, key, value, ttl)
+//                         -- set ctx[key] and remove it once `ttl` expires
+//   chain.lazy(ctx, key, producer)
+//                         -- compute ctx[key] as producer(ctx) on first read
+//   chain.ref(ctx, key, target)
+//                         -- make ctx[key] read ctx[target] (see below)
+//   chain.stream(producer)
+//                         -- a single-pass iterator over what producer yields
+//
+// `chain.stream` runs `producer` as a coroutine, one `coroutine.yield(item)`
+// per call of the returned iterator, so an event can store the stream in the
+// context and a later event consume it with `for item in ctx.items do`
+// without the items ever being held together. The stream ends (returning
+// nil from then on) when the producer returns; nil cannot be an item.
+//
+// A TTL is a number of events, or `{ ms = n }` for wall-clock time. Events
+// are counted as the run completes them, so the count means the same in
+// every execution mode. With `ttl = 1` the key is visible to the next event
+// and removed after it; a duration is checked after each event, so an
+// expired key stays readable until the event that is running finishes.
+// Expiry is tracked by key name for the rest of the run: assigning the key
+// normally keeps its TTL, and `set_ttl` again replaces it.
+//
+// Aliases added with `add_alias(old, new)` let handlers keep using a renamed
+// key: reading or assigning `ctx.old` reads or assigns `ctx.new`. They are
+// resolved at access time through the context table's metatable, so `old`
+// is never stored, `pairs(ctx)` and the outcome only show `new`, and native
+// events (which copy stored keys) only see `new`. A context that arrives
+// with a metatable of its own keeps working: its `__index` and `__newindex`
+// still handle the keys that are not aliases, refs or lazy.
+//
+// A lazy key is absent until it is read: the producer then runs once, its
+// result is stored in the context and later reads see the stored value.
+// Keys never read never run their producer and do not appear in the
+// outcome. A producer that raises stays in place, so the next read runs it
+// again. Assigning the key first discards the producer. Laziness lives in
+// the context table's metatable, so it is lost if a handler replaces the
+// table (return mode "patch" keeps it), and the producer receives the real
+// context even under access tracking or `immutable_context` (but not under a
+// key policy, see above). A runner is
+// single-threaded, so producers never race; they must not yield.
+//
+// References (`ContextValue::Ref`, or `chain.ref(ctx, key, target)` from
+// Lua) live in the same metatable. A run moves every Ref it finds in its
+// starting context there, so `ctx.key` reads `ctx.target` at the time of the
+// read, following refs to refs; a chain longer than MAX_REF_DEPTH raises.
+// Assigning `ctx.key` replaces the reference with the value (`target` is
+// unchanged). Like aliases, refs are not visible to `pairs(ctx)`; the
+// outcome keeps them as `Ref`s and native events see the resolved values.
+//
+// With the `tracing` feature every run executes inside a `chain_run` span
+// (fields `chain` and `run_id`), each event inside an `event` span and each
+// middleware layer or observer inside a `middleware` span, with the event or
+// middleware name as the `name` field (span names are static in `tracing`).
+// Events record `status` ("ok", "error" or "aborted") and `duration_ms` when
+// they finish. The spans are entered on the current thread, so they nest
+// under whatever span the caller of `execute` has entered. The
+// `opentelemetry` feature adds OTel status and `traceparent` propagation;
+// see otel.rs.
+
+const CONTEXT_GLOBAL: &str = "__context";
+const HELPERS_GLOBAL: &str = "chain";
+const CLEANUP_TAG: &str = "cleanup";
+const LAZY_FIELD: &str = "__lazy";
+const ALIASES_FIELD: &str = "__aliases";
+// A context's own `__index`/`__newindex` from before the runner's metatable
+const FALLBACK_INDEX_FIELD: &str = "__fallback_index";
+const FALLBACK_NEWINDEX_FIELD: &str = "__fallback_newindex";
+const CONTEXT_INDEX_KEY: &str = "lua_chains.context_index";
+const CONTEXT_NEWINDEX_KEY: &str = "lua_chains.context_newindex";
+
+/// Most middleware layers a definition may declare. Each layer nests a
+/// Lua -> Rust -> Lua call through `next`, and Lua 5.4 refuses to nest C
+/// calls about 200 deep, so deeper stacks fail at run time with
+/// "C stack overflow"; the limit leaves headroom for handlers' own calls.
+pub const MAX_MIDDLEWARE_DEPTH: usize = 128;
+
+/// Called after each successful event with its index and the context so far.
+pub type CheckpointFn = Box<dyn Fn(usize, &ContextMap)>;
+
+/// Turns a Lua context value into its owned form, or `None` to leave the key
+/// out; see [`LuaChainRunner::with_value_coercion`].
+pub type CoercionFn = Box<dyn Fn(&str, &LuaValue) -> Option<ContextValue>>;
+
+/// Called with a middleware's declaration index and the context it returned.
+pub type MiddlewareInspectorFn = Box<dyn for<'lua> Fn(usize, &LuaTable<'lua>)>;
+
+/// Called once when the runner is dropped.
+pub type TeardownFn = Box<dyn FnOnce()>;
+
+/// Receives one JSON object per line for every event and middleware
+/// boundary; see [`LuaChainRunner::with_trace_sink`].
+pub type TraceSink = Box<dyn Write>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionMode {
+    /// Every event runs, in declaration order
+    #[default]
+    Sequential,
+    /// One event runs per execute, chosen by weight
+    WeightedRandom,
+    /// Every event runs, in a new random order each execute (testing aid)
+    Shuffle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutcomeStatus {
+    /// Every event ran
+    Completed,
+    /// A handler or middleware called `chain.abort_chain()`
+    Aborted,
+    /// The definition's `stop_when` returned true after the last event run
+    StoppedEarly,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainOutcome {
+    pub run_id: String,
+    pub status: OutcomeStatus,
+    pub context: ContextMap,
+    pub events_executed: usize,
+    pub duration: Duration,
+    /// Wall-clock time the run ended.
+    pub finished_at: SystemTime,
+    /// Middleware entered for each event, outermost first. `None` unless
+    /// the runner was built with `with_trace(true)`.
+    pub middleware_trace: Option<Vec<Vec<String>>>,
+    /// Context keys read and written by Lua code. `None` unless the runner
+    /// was built with `with_access_log(true)`.
+    pub access_report: Option<AccessReport>,
+    /// Lua heap usage around the run. `None` unless the runner was built
+    /// with `with_gc_stats(true)`.
+    pub gc: Option<GcStats>,
+    /// Rust/Lua boundary crossings during the run. `None` unless the
+    /// runner was built with `with_boundary_stats(true)`.
+    pub boundary: Option<BoundaryStats>,
+    /// One line per event that completed without changing the context.
+    /// `None` unless the runner was built with `with_warn_on_no_change(true)`.
+    pub no_change_warnings: Option<Vec<String>>,
+    /// Messages Lua code passed to `warn` during the run. `None` unless the
+    /// runner was built with `with_lua_warnings(true)`.
+    pub lua_warnings: Option<Vec<String>>,
+}
+
+impl ChainOutcome {
+    /// Deserialize the final context into `T`.
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Out { counter: i64, message: String }
+    ///
+    /// let out: Out = runner.execute()?.deserialize()?;
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, ChainError> {
+        deserialize_map(&self.context)
+    }
+}
+
+/// The result of [`LuaChainRunner::trial`].
+#[derive(Debug, Clone)]
+pub struct TrialOutcome {
+    pub outcome: ChainOutcome,
+    /// Keys the run would add or change, with their new values
+    pub changed: ContextMap,
+    /// Keys the run would remove
+    pub removed: Vec<String>,
+}
+
+pub struct LuaChainRunner {
+    lua: Rc<Lua>,
+    meta: ChainMeta,
+    initial_context: LuaRegistryKey,
+    event_names: Vec<String>,
+    event_handlers: Vec<LuaRegistryKey>,
+    event_tags: Vec<Vec<String>>,
+    event_args: Vec<LuaRegistryKey>,
+    event_weights: Vec<f64>,
+    event_patch: Vec<bool>,
+    event_error_handlers: Vec<Option<LuaRegistryKey>>,
+    // `Some(fired)` for events declared `once`
+    event_once: Vec<Option<Cell<bool>>>,
+    event_key_policy: Vec<Option<KeyPolicy>>,
+    event_bypass_middleware: Vec<bool>,
+    event_breakers: Vec<Option<CircuitBreaker>>,
+    // Contract predicates; a failed `pre` skips the event if flagged
+    event_pre: Vec<Option<LuaRegistryKey>>,
+    event_post: Vec<Option<LuaRegistryKey>>,
+    event_pre_skips: Vec<bool>,
+    // A cache for each event declared `pure`
+    event_memo: Vec<Option<RefCell<MemoCache>>>,
+    middleware_names: Vec<String>,
+    middleware_handlers: Vec<LuaRegistryKey>,
+    // Chance (0 to 1) that a middleware runs around a given event
+    middleware_sample_rates: Vec<f64>,
+    // `applies_to(event_name, ctx)` predicates, checked per event
+    middleware_applies_to: Vec<Option<LuaRegistryKey>>,
+    // Declaration indices of nesting and observational middleware
+    middleware_stack: Vec<usize>,
+    observers: Vec<usize>,
+    stop_when: Option<LuaRegistryKey>,
+    // The definition's `expect` table, for `verify`
+    expect: Option<ContextMap>,
+    finalize: Option<LuaRegistryKey>,
+    on_shutdown: Option<LuaRegistryKey>,
+    execution_mode: ExecutionMode,
+    immutable_context: bool,
+    rng: Cell<u64>,
+    helpers: LuaRegistryKey,
+    run_state: Rc<RunState>,
+    trace: bool,
+    checkpoint: Option<CheckpointFn>,
+    persistent_context: Option<RefCell<Box<dyn ContextStore>>>,
+    cipher: Option<Box<dyn ContextCipher>>,
+    sensitive_keys: HashSet<String>,
+    value_coercion: Option<CoercionFn>,
+    fault_injector: Option<FaultInjector>,
+    max_total_duration: Option<Duration>,
+    middleware_inspector: Option<MiddlewareInspectorFn>,
+    access_tracker: AccessTracker,
+    track_access: Cell<bool>,
+    stats: Option<RefCell<ChainStats>>,
+    teardown: Option<TeardownFn>,
+    defaults: ContextMap,
+    gc_stats: bool,
+    collect_errors: bool,
+    // Crossings since the runner was built or its stats were reset
+    boundary: Option<Cell<BoundaryStats>>,
+    last_outcome: Option<RefCell<Option<ChainOutcome>>>,
+    warn_on_no_change: bool,
+    parse_report: ParseReport,
+    capture_warnings: bool,
+    strict_warnings: bool,
+    // Lua table of alias -> canonical key, if any alias was added
+    aliases: Option<LuaRegistryKey>,
+    trace_sink: Option<RefCell<TraceSink>>,
+    setup_duration: Duration,
+}
+
+impl LuaChainRunner {
+    /// Load and evaluate a chain script that returns a definition table.
+    pub fn from_script(lua: Rc<Lua>, script: &str) -> Result<Self, ChainError> {
+        let started = Instant::now();
+        let definition: LuaTable = lua
+            .load(script)
+            .set_name("chain_definition")
+            .set_mode(ChunkMode::Text)
+            .eval()?;
+        let parts = Self::extract(&lua, definition)?;
+        Self::assemble(lua, parts, started)
+    }
+
+    /// Precompile a chain script to Lua bytecode for [`from_bytecode`](Self::from_bytecode),
+    /// skipping the parse on every later construction. Debug info is kept
+    /// so errors still carry line numbers.
+    pub fn compile(script: &str) -> Result<Vec<u8>, ChainError> {
+        let lua = Lua::new();
+        let chunk = lua
+            .load(script)
+            .set_name("chain_definition")
+            .set_mode(ChunkMode::Text)
+            .into_function()?;
+        Ok(chunk.dump(false))
+    }
+
+    /// Load a chain script precompiled with [`compile`](Self::compile).
+    ///
+    /// # Safety
+    ///
+    /// Lua does not verify bytecode: a malformed or malicious chunk can
+    /// corrupt memory or crash the process. Only load bytecode produced by
+    /// `compile` from a trusted source, with the same Lua version and build.
+    pub unsafe fn from_bytecode(lua: Rc<Lua>, bytecode: &[u8]) -> Result<Self, ChainError> {
+        let started = Instant::now();
+        let definition: LuaTable = lua
+            .load(bytecode)
+            .set_name("chain_definition")
+            .set_mode(ChunkMode::Binary)
+            .eval()?;
+        let parts = Self::extract(&lua, definition)?;
+        Self::assemble(lua, parts, started)
+    }
+
+    /// Build a runner from an already-evaluated definition table.
+    pub fn from_definition(lua: Rc<Lua>, definition: LuaTable) -> Result<Self, ChainError> {
+        let started = Instant::now();
+        let parts = Self::extract(&lua, definition)?;
+        Self::assemble(lua, parts, started)
+    }
+
+    /// Build a runner from a JSON [`ChainDefinition`] of native event names.
+    pub fn from_json(lua: Rc<Lua>, json: &str) -> Result<Self, ChainError> {
+        Self::from_chain_definition(lua, &ChainDefinition::from_json(json)?)
+    }
+
+    /// Build a runner from a TOML [`ChainDefinition`] of native event names.
+    pub fn from_toml(lua: Rc<Lua>, toml: &str) -> Result<Self, ChainError> {
+        Self::from_chain_definition(lua, &ChainDefinition::from_toml(toml)?)
+    }
+
+    /// Build a runner whose events are resolved from the native registry.
+    pub fn from_chain_definition(
+        lua: Rc<Lua>,
+        definition: &ChainDefinition,
+    ) -> Result<Self, ChainError> {
+        let started = Instant::now();
+        let mut event_handlers = Vec::with_capacity(definition.events.len());
+        let mut event_args = Vec::with_capacity(definition.events.len());
+        for (index, name) in definition.events.iter().enumerate() {
+            event_handlers.push(resolve_native(&lua, index, name, name)?);
+            event_args.push(lua.create_registry_value(lua.create_table()?)?);
+        }
+
+        let context = map_to_table(&lua, &definition.context)?;
+        let parts = DefinitionParts {
+            meta: definition.meta.clone(),
+            initial_context: lua.create_registry_value(context)?,
+            event_names: definition.events.clone(),
+            event_handlers,
+            event_tags: vec![Vec::new(); definition.events.len()],
+            event_args,
+            event_weights: vec![1.0; definition.events.len()],
+            event_patch: vec![false; definition.events.len()],
+            event_error_handlers: (0..definition.events.len()).map(|_| None).collect(),
+            event_once: vec![false; definition.events.len()],
+            event_key_policy: vec![None; definition.events.len()],
+            event_bypass_middleware: vec![false; definition.events.len()],
+            event_breakers: (0..definition.events.len()).map(|_| None).collect(),
+            event_pre: (0..definition.events.len()).map(|_| None).collect(),
+            event_post: (0..definition.events.len()).map(|_| None).collect(),
+            event_pre_skips: vec![false; definition.events.len()],
+            event_pure: vec![false; definition.events.len()],
+            middleware_names: Vec::new(),
+            middleware_handlers: Vec::new(),
+            middleware_observational: Vec::new(),
+            middleware_sample_rates: Vec::new(),
+            middleware_applies_to: Vec::new(),
+            stop_when: None,
+            expect: None,
+            finalize: None,
+            on_shutdown: None,
+            execution_mode: ExecutionMode::Sequential,
+            immutable_context: false,
+            warnings: Vec::new(),
+        };
+        Self::assemble(lua, parts, started)
+    }
+
+    /// Make a native event available to definitions loaded into `lua`,
+    /// by `native = "<name>"` in Lua or by name in JSON/TOML.
+    pub fn register_event<E: ChainableEvent + 'static>(lua: &Lua, name: &str, event: E) {
+        native::register(lua, name, Rc::new(event));
+    }
+
+    /// Register every event in `events` under its key, as `register_event`
+    /// does one at a time, e.g. implementations a plugin system discovered.
+    pub fn register_events(lua: &Lua, events: HashMap<String, Box<dyn ChainableEvent>>) {
+        for (name, event) in events {
+            native::register(lua, &name, Rc::from(event));
+        }
+    }
+
+    /// Names of the native events definitions loaded into `lua` can use,
+    /// built-ins included, sorted.
+    pub fn registered_event_names(lua: &Lua) -> Vec<String> {
+        native::names(lua)
+    }
+
+    /// Make `require(name)` in chain scripts loaded into `lua` evaluate
+    /// `source` (once per VM, as Lua caches modules) and return its result,
+    /// e.g. a table of shared middleware:
+    ///
+    /// ```ignore
+    /// LuaChainRunner::preload_module(&lua, "common_middleware", r#"
+    ///     local M = {}
+    ///     function M.timing(ctx, next) ... end
+    ///     return M
+    /// "#)?;
+    /// // in a chain script:
+    /// //   local common = require("common_middleware")
+    /// //   middleware = { { name = "timing", handler = common.timing } }
+    /// ```
+    pub fn preload_module(lua: &Lua, name: &str, source: &str) -> Result<(), ChainError> {
+        let chunk_name = format!("={}", name);
+        let source = source.to_string();
+        let loader = lua.create_function(move |lua, ()| {
+            lua.load(source.as_str())
+                .set_name(chunk_name.as_str())
+                .set_mode(ChunkMode::Text)
+                .call::<_, LuaValue>(())
+        })?;
+        Self::preload(lua, name, loader)
+    }
+
+    /// Make `require(name)` return `module`, e.g. a table of middleware
+    /// built in Rust with `Lua::create_function`.
+    pub fn preload_module_table(lua: &Lua, name: &str, module: LuaTable) -> Result<(), ChainError> {
+        let key = lua.create_registry_value(module)?;
+        Self::preload(lua, name, lua.create_function(move |lua, ()| lua.registry_value::<LuaTable>(&key))?)
+    }
+
+    /// Let `require` find module files in `dir`, as `dir/<name>.lua`.
+    pub fn add_module_path(lua: &Lua, dir: &str) -> Result<(), ChainError> {
+        let package: LuaTable = lua.globals().get("package")?;
+        let path: String = package.get("path")?;
+        package.set("path", format!("{}/?.lua;{}", dir.trim_end_matches('/'), path))?;
+        Ok(())
+    }
+
+    fn preload(lua: &Lua, name: &str, loader: LuaFunction) -> Result<(), ChainError> {
+        let package: LuaTable = lua.globals().get("package")?;
+        let preload: LuaTable = package.get("preload")?;
+        preload.set(name, loader)?;
+        Ok(())
+    }
+
+    fn extract(lua: &Lua, definition: LuaTable) -> Result<DefinitionParts, ChainError> {
+        let context = match definition.get::<_, Option<LuaTable>>("context")? {
+            Some(table) => table,
+            None => lua.create_table()?,
+        };
+
+        let events: LuaTable = definition
+            .get::<_, Option<LuaTable>>("events")?
+            .ok_or_else(|| ChainError::InvalidDefinition("missing `events` table".to_string()))?;
+        let events = extract_handlers(lua, events, "event", true)?;
+        let patch_by_default = parse_return_mode(definition.get("return_mode")?)?.unwrap_or(false);
+        let event_patch = events.patch.iter().map(|p| p.unwrap_or(patch_by_default)).collect();
+
+        let middleware = match definition.get::<_, Option<LuaTable>>("middleware")? {
+            Some(table) => extract_handlers(lua, table, "middleware", false)?,
+            None => HandlerList::default(),
+        };
+
+        let execution_mode = match definition.get::<_, Option<String>>("execution_mode")?.as_deref() {
+            None | Some("sequential") => ExecutionMode::Sequential,
+            Some("weighted_random") => {
+                if events.weights.iter().sum::<f64>() <= 0.0 {
+                    return Err(ChainError::InvalidDefinition(
+                        "weighted_random needs at least one event with a positive weight".to_string(),
+                    ));
+                }
+                ExecutionMode::WeightedRandom
+            }
+            Some("shuffle") => ExecutionMode::Shuffle,
+            Some(other) => {
+                return Err(ChainError::InvalidDefinition(format!(
+                    "unknown execution_mode '{}'",
+                    other
+                )));
+            }
+        };
+
+        let meta = match definition.get::<_, Option<LuaTable>>("meta")? {
+            Some(table) => ChainMeta {
+                name: table.get("name")?,
+                version: table.get("version")?,
+                description: table.get("description")?,
+                tags: table.get::<_, Option<Vec<String>>>("tags")?.unwrap_or_default(),
+            },
+            None => ChainMeta::default(),
+        };
+
+        Ok(DefinitionParts {
+            meta,
+            initial_context: lua.create_registry_value(context)?,
+            event_names: events.names,
+            event_handlers: events.handlers,
+            event_tags: events.tags,
+            event_args: events.args,
+            event_weights: events.weights,
+            event_patch,
+            event_error_handlers: events.error_handlers,
+            event_once: events.once,
+            event_key_policy: events.key_policies,
+            event_bypass_middleware: events.bypass_middleware,
+            event_breakers: events.breakers,
+            event_pre: events.pre,
+            event_post: events.post,
+            event_pre_skips: events.pre_skips,
+            event_pure: events.pure,
+            middleware_names: middleware.names,
+            middleware_handlers: middleware.handlers,
+            middleware_observational: middleware.observational,
+            middleware_sample_rates: middleware.sample_rates,
+            middleware_applies_to: middleware.applies_to,
+            stop_when: definition
+                .get::<_, Option<LuaFunction>>("stop_when")?
+                .map(|f| lua.create_registry_value(f))
+                .transpose()?,
+            expect: definition.get::<_, Option<LuaTable>>("expect")?.map(|t| table_to_map(&t)).transpose()?,
+            finalize: definition
+                .get::<_, Option<LuaFunction>>("finalize")?
+                .map(|f| lua.create_registry_value(f))
+                .transpose()?,
+            on_shutdown: definition
+                .get::<_, Option<LuaFunction>>("on_shutdown")?
+                .map(|f| lua.create_registry_value(f))
+                .transpose()?,
+            execution_mode,
+            immutable_context: definition.get::<_, Option<bool>>("immutable_context")?.unwrap_or(false),
+            warnings: events.warnings.into_iter().chain(middleware.warnings).collect(),
+        })
+    }
+
+    fn assemble(lua: Rc<Lua>, parts: DefinitionParts, started: Instant) -> Result<Self, ChainError> {
+        let (observers, middleware_stack): (Vec<usize>, Vec<usize>) =
+            (0..parts.middleware_handlers.len()).partition(|&i| parts.middleware_observational[i]);
+        if middleware_stack.len() > MAX_MIDDLEWARE_DEPTH {
+            return Err(ChainError::InvalidDefinition(format!(
+                "{} middleware layers exceed the maximum depth of {}",
+                middleware_stack.len(),
+                MAX_MIDDLEWARE_DEPTH
+            )));
+        }
+        let mut warnings = parts.warnings;
+        for (kind, names) in [("event", &parts.event_names), ("middleware", &parts.middleware_names)] {
+            for (index, name) in names.iter().enumerate() {
+                if let Some(first) = names[..index].iter().position(|n| n == name) {
+                    warnings.push(format!(
+                        "{}[{}] reuses the name '{}' of {}[{}]; lookups by name find the first",
+                        kind, index, name, kind, first
+                    ));
+                }
+            }
+        }
+        let parse_report = ParseReport {
+            events: parts.event_names.clone(),
+            middleware: parts.middleware_names.clone(),
+            warnings,
+        };
+        let (helpers, run_state) = create_helpers(&lua)?;
+        let access_tracker = AccessTracker::new(&lua)?;
+        Ok(Self {
+            lua,
+            meta: parts.meta,
+            initial_context: parts.initial_context,
+            event_names: parts.event_names,
+            event_handlers: parts.event_handlers,
+            event_tags: parts.event_tags,
+            event_args: parts.event_args,
+            event_weights: parts.event_weights,
+            event_patch: parts.event_patch,
+            event_error_handlers: parts.event_error_handlers,
+            event_once: parts.event_once.into_iter().map(|once| once.then(|| Cell::new(false))).collect(),
+            event_key_policy: parts.event_key_policy,
+            event_bypass_middleware: parts.event_bypass_middleware,
+            event_breakers: parts.event_breakers,
+            event_pre: parts.event_pre,
+            event_post: parts.event_post,
+            event_pre_skips: parts.event_pre_skips,
+            event_memo: parts
+                .event_pure
+                .into_iter()
+                .map(|pure| pure.then(|| RefCell::new(MemoCache::new(DEFAULT_MEMO_CAPACITY))))
+                .collect(),
+            middleware_names: parts.middleware_names,
+            middleware_handlers: parts.middleware_handlers,
+            middleware_sample_rates: parts.middleware_sample_rates,
+            middleware_applies_to: parts.middleware_applies_to,
+            middleware_stack,
+            observers,
+            stop_when: parts.stop_when,
+            expect: parts.expect,
+            finalize: parts.finalize,
+            on_shutdown: parts.on_shutdown,
+            execution_mode: parts.execution_mode,
+            immutable_context: parts.immutable_context,
+            rng: Cell::new(uuid::Uuid::new_v4().as_u64_pair().0),
+            helpers,
+            run_state,
+            trace: false,
+            checkpoint: None,
+            persistent_context: None,
+            value_coercion: None,
+            fault_injector: None,
+            cipher: None,
+            sensitive_keys: HashSet::new(),
+            max_total_duration: None,
+            middleware_inspector: None,
+            access_tracker,
+            track_access: Cell::new(false),
+            stats: None,
+            teardown: None,
+            defaults: ContextMap::new(),
+            gc_stats: false,
+            collect_errors: false,
+            boundary: None,
+            last_outcome: None,
+            warn_on_no_change: false,
+            parse_report,
+            capture_warnings: false,
+            strict_warnings: false,
+            aliases: None,
+            trace_sink: None,
+            setup_duration: started.elapsed(),
+        })
+    }
+
+    /// Record which middleware layers run for each event. Off by default.
+    pub fn with_trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    /// Write a JSON object per line to `sink` when each event or middleware
+    /// layer is entered and exited, for observability tooling:
+    ///
+    /// ```text
+    /// {"timestamp_us":..,"run_id":"..","event_idx":0,"kind":"event","name":"increment","phase":"enter"}
+    /// {"timestamp_us":..,"run_id":"..","event_idx":0,"kind":"event","name":"increment","phase":"exit","duration_us":41,"status":"ok"}
+    /// ```
+    ///
+    /// `kind` is "event" or "middleware" (observers included) and `status`
+    /// is "ok", "error" or, for events, "aborted". Cleanup events and
+    /// previews are not traced. Write errors are ignored, and the sink is
+    /// flushed when the run ends.
+    pub fn with_trace_sink(mut self, sink: TraceSink) -> Self {
+        self.trace_sink = Some(RefCell::new(sink));
+        self
+    }
+
+    /// Invoke `checkpoint` after every successful event, e.g. to persist
+    /// the context so a restarted process can resume with `execute_from`.
+    /// The owned context is only built when a checkpoint is installed.
+    pub fn with_checkpoint<F>(mut self, checkpoint: F) -> Self
+    where
+        F: Fn(usize, &ContextMap) + 'static,
+    {
+        self.checkpoint = Some(Box::new(checkpoint));
+        self
+    }
+
+    /// Carry the context from one `execute` to the next through `store`.
+    /// While the store is empty a run starts from the definition's context;
+    /// after each successful run the final context is written to the store
+    /// as by `execute_with_store`, and the next `execute` (or
+    /// `execute_with_id`) starts from the store's contents instead. A failed
+    /// run leaves the store as it was. Other entry points that take a
+    /// context ignore the store.
+    pub fn with_persistent_context(mut self, store: impl ContextStore + 'static) -> Self {
+        self.persistent_context = Some(RefCell::new(Box::new(store)));
+        self
+    }
+
+    /// Encrypt the keys marked `with_sensitive_key` with `cipher` when
+    /// writing them to a context store, and decrypt them on load; see
+    /// cipher.rs. Managing the cipher's keys is up to the caller.
+    pub fn with_cipher(mut self, cipher: impl ContextCipher + 'static) -> Self {
+        self.cipher = Some(Box::new(cipher));
+        self
+    }
+
+    /// Store `key` encrypted; requires `with_cipher`.
+    pub fn with_sensitive_key(mut self, key: &str) -> Self {
+        self.sensitive_keys.insert(key.to_string());
+        self
+    }
+
+    /// Convert each key of the Lua context with `coerce` instead of
+    /// `ContextValue::from_lua` whenever an owned context is handed out: the
+    /// outcome, checkpoints, collected errors and `run_middleware`. It sees
+    /// every stored key with its raw Lua value, so it can, e.g., parse date
+    /// strings into timestamps; fall back to `ContextValue::from_lua` for
+    /// the standard mapping. References are not passed through it.
+    pub fn with_value_coercion<F>(mut self, coerce: F) -> Self
+    where
+        F: Fn(&str, &LuaValue) -> Option<ContextValue> + 'static,
+    {
+        self.value_coercion = Some(Box::new(coerce));
+        self
+    }
+
+    /// Fail or delay events by name at the given rates, for resilience tests;
+    /// see fault.rs. Not meant for production runners.
+    pub fn with_fault_injector(mut self, faults: FaultInjector) -> Self {
+        self.fault_injector = Some(faults);
+        self
+    }
+
+    /// Wall-clock budget for a whole run. Checked before each event after
+    /// the first, so a single slow handler is only caught once it returns.
+    pub fn with_max_total_duration(mut self, limit: Duration) -> Self {
+        self.max_total_duration = Some(limit);
+        self
+    }
+
+    /// Invoke `inspector` each time a middleware layer returns, with the
+    /// layer's declaration index and the context it handed back. Useful for
+    /// spotting middleware that rewrites the context on the way out.
+    pub fn with_middleware_inspector<F>(mut self, inspector: F) -> Self
+    where
+        F: for<'lua> Fn(usize, &LuaTable<'lua>) + 'static,
+    {
+        self.middleware_inspector = Some(Box::new(inspector));
+        self
+    }
+
+    /// Record which context keys Lua handlers and middleware read and
+    /// write, to spot dead keys and implicit inputs. Off by default: every
+    /// handler call goes through a proxy table while enabled.
+    pub fn with_access_log(mut self, enabled: bool) -> Self {
+        *self.track_access.get_mut() = enabled;
+        self
+    }
+
+    /// Keep running after a failed event instead of stopping: the event's
+    /// changes are rolled back (to a deep copy of the context taken before
+    /// it ran, lazy keys, aliases and references included) and the run
+    /// continues with the next event. If any event failed, the run then
+    /// returns `ChainError::Multiple` with every failure and the final
+    /// context, and `finalize` does not run. Off by default, since each
+    /// event pays for the copy.
+    pub fn with_collect_errors(mut self, enabled: bool) -> Self {
+        self.collect_errors = enabled;
+        self
+    }
+
+    /// Reject values stored under `key` that `validator` returns an error
+    /// for. A Lua assignment to the key is checked as it happens and raises there;
+    /// any other change (a returned table, a patch, a native event) is checked
+    /// when the event returns. Either way the run fails with
+    /// `ChainError::ValidationFailed`. Only changes are checked, not the
+    /// initial context, and removing the key is always allowed. Lua code gets
+    /// a proxy of the context while any validator is installed.
+    pub fn with_validator<F>(self, key: &str, validator: F) -> Self
+    where
+        F: Fn(&ContextValue) -> Result<(), String> + 'static,
+    {
+        self.access_tracker.add_validator(key, Box::new(validator));
+        self
+    }
+
+    /// Seed the generator behind `weighted_random` draws, `shuffle` orders
+    /// and middleware `sample_rate` draws, so a sequence of runs picks (and
+    /// samples) the same events in the same order every time. Seeded
+    /// randomly by default.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.rng.set(seed);
+        self
+    }
+
+    /// Accumulate per-event timings and success/failure counts across
+    /// executions, read back with [`stats`](Self::stats). Off by default.
+    pub fn with_stats(mut self, enabled: bool) -> Self {
+        self.stats = enabled.then(|| RefCell::new(ChainStats::new(&self.event_names)));
+        self
+    }
+
+    /// Snapshot of the statistics so far; `None` unless built `with_stats(true)`.
+    pub fn stats(&self) -> Option<ChainStats> {
+        self.stats.as_ref().map(|stats| stats.borrow().clone())
+    }
+
+    /// The statistics so far in the Prometheus text format, labelled with
+    /// the chain's name; `None` unless built `with_stats(true)`.
+    pub fn metrics_prometheus(&self) -> Option<String> {
+        let stats = self.stats.as_ref()?;
+        Some(stats.borrow().to_prometheus(self.meta().name.as_deref()))
+    }
+
+    /// Rust/Lua boundary crossings accumulated over every run, if enabled
+    /// with `with_boundary_stats(true)`.
+    pub fn boundary_stats(&self) -> Option<BoundaryStats> {
+        self.boundary.as_ref().map(Cell::get)
+    }
+
+    /// Clear the accumulated [`stats`](Self::stats) and
+    /// [`boundary_stats`](Self::boundary_stats).
+    pub fn reset_stats(&self) {
+        if let Some(stats) = &self.stats {
+            *stats.borrow_mut() = ChainStats::new(&self.event_names);
+        }
+        if let Some(boundary) = &self.boundary {
+            boundary.set(BoundaryStats::default());
+        }
+    }
+
+    /// Record Lua heap usage before and after each run in the outcome.
+    /// Off by default.
+    pub fn with_gc_stats(mut self, enabled: bool) -> Self {
+        self.gc_stats = enabled;
+        self
+    }
+
+    /// Count the runner's Rust/Lua boundary crossings, per run in the
+    /// outcome and in total in [`boundary_stats`](Self::boundary_stats).
+    /// Off by default.
+    pub fn with_boundary_stats(mut self, enabled: bool) -> Self {
+        self.boundary = enabled.then(|| Cell::new(BoundaryStats::default()));
+        self
+    }
+
+    /// Flag events that complete without changing any context key, which
+    /// usually means a handler forgot to write its result. Off by default:
+    /// the context is copied before every event to compare against. Only
+    /// values with an owned form are compared, so a change inside a nested
+    /// table is not seen.
+    pub fn with_warn_on_no_change(mut self, enabled: bool) -> Self {
+        self.warn_on_no_change = enabled;
+        self
+    }
+
+    /// Collect the messages Lua code emits with `warn(...)` during a run into
+    /// the outcome. Off by default. The warning function belongs to the Lua
+    /// state, so it is installed for the duration of each run only; outside
+    /// runs `warn` then does nothing, even after `warn("@on")`.
+    pub fn with_lua_warnings(mut self, enabled: bool) -> Self {
+        self.capture_warnings = enabled;
+        self
+    }
+
+    /// Treat every Lua warning as an error raised at the `warn` call, so the
+    /// event that emitted it fails. Off by default.
+    pub fn with_strict_warnings(mut self, enabled: bool) -> Self {
+        self.strict_warnings = enabled;
+        self
+    }
+
+    /// Keep a copy of the most recent successful outcome, read back with
+    /// [`last_outcome`](Self::last_outcome). Off by default, since it holds
+    /// the final context in memory between runs.
+    pub fn with_last_outcome(mut self, enabled: bool) -> Self {
+        self.last_outcome = enabled.then(|| RefCell::new(None));
+        self
+    }
+
+    /// The outcome of the latest run, or `None` if it failed, nothing has
+    /// run yet, or the runner was not built `with_last_outcome(true)`.
+    pub fn last_outcome(&self) -> Option<ChainOutcome> {
+        self.last_outcome.as_ref().and_then(|last| last.borrow().clone())
+    }
+
+    /// Run a full Lua garbage collection now, e.g. between benchmark runs
+    /// so deferred collection is not billed to the next run.
+    pub fn collect_garbage(&self) -> Result<GcCollection, ChainError> {
+        let before = self.lua.used_memory();
+        let start = Instant::now();
+        self.lua.gc_collect()?;
+        Ok(GcCollection {
+            reclaimed_bytes: before.saturating_sub(self.lua.used_memory()),
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Base values present in every run. A key is only filled in from
+    /// `defaults` when the run's own context (the definition's, or the one
+    /// passed to `execute_with_context`/`execute_from`) lacks it, so per-run
+    /// values always win.
+    pub fn with_defaults(mut self, defaults: ContextMap) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Invoke `teardown` when the runner is dropped, after the definition's
+    /// `on_shutdown`. It must not panic: a panic is caught and discarded, and
+    /// one raised while the thread is already unwinding aborts the process.
+    pub fn with_teardown<F>(mut self, teardown: F) -> Self
+    where
+        F: FnOnce() + 'static,
+    {
+        self.teardown = Some(Box::new(teardown));
+        self
+    }
+
+    /// What the definition declared and any warnings raised while loading
+    /// it. It describes the definition as loaded, not later event edits.
+    pub fn parse_report(&self) -> &ParseReport { &self.parse_report }
+
+    /// The definition's `expect` table, if it has one; see `verify`.
+    pub fn expectations(&self) -> Option<&ContextMap> { self.expect.as_ref() }
+
+    /// How long construction took: evaluating the definition, registering
+    /// its handlers and setting up the helpers. This is the cold-start cost;
+    /// `ChainOutcome::duration` covers only the events of one run.
+    pub fn setup_duration(&self) -> Duration { self.setup_duration }
+
+    pub fn lua(&self) -> &Lua { &self.lua }
+
+    pub fn meta(&self) -> &ChainMeta { &self.meta }
+
+    pub fn execution_mode(&self) -> ExecutionMode { self.execution_mode }
+
+    pub fn event_names(&self) -> &[String] { &self.event_names }
+
+    pub fn middleware_names(&self) -> &[String] { &self.middleware_names }
+
+    /// The events and middleware of the chain and how they nest; see
+    /// topology.rs.
+    pub fn topology(&self) -> Topology {
+        // Entered first to last: the last declared layer is outermost
+        let layers: Vec<usize> = self.middleware_stack.iter().rev().copied().collect();
+        Topology {
+            chain: self.meta.name.clone(),
+            execution_mode: self.execution_mode,
+            observers: self.observers.iter().map(|&i| self.middleware_names[i].clone()).collect(),
+            middleware: layers
+                .iter()
+                .enumerate()
+                .map(|(depth, &declared)| MiddlewareNode {
+                    name: self.middleware_names[declared].clone(),
+                    declared,
+                    depth,
+                    wraps: layers.get(depth + 1).map(|&inner| self.middleware_names[inner].clone()),
+                })
+                .collect(),
+            events: self
+                .event_names
+                .iter()
+                .enumerate()
+                .map(|(order, name)| EventNode {
+                    name: name.clone(),
+                    order,
+                    tags: self.event_tags[order].clone(),
+                    bypass_middleware: self.event_bypass_middleware[order],
+                })
+                .collect(),
+        }
+    }
+
+    /// The structural shape of this runner, for fingerprinting and diffing
+    /// configurations; see fingerprint.rs.
+    pub fn config_snapshot(&self) -> Result<ConfigSnapshot, ChainError> {
+        let initial: LuaTable = self.lua.registry_value(&self.initial_context)?;
+        let mut context_keys = Vec::new();
+        for pair in initial.pairs::<LuaValue, LuaValue>() {
+            if let (LuaValue::String(key), _) = pair? {
+                context_keys.push(key.to_str()?.to_string());
+            }
+        }
+        context_keys.sort();
+
+        let sorted = |keys: &mut dyn Iterator<Item = &String>| {
+            let mut keys: Vec<&String> = keys.collect();
+            keys.sort();
+            keys.iter().map(|key| key.as_str()).collect::<Vec<_>>().join(",")
+        };
+        let mut options = BTreeMap::new();
+        let mut set = |option: String, value: String| {
+            options.insert(option, value);
+        };
+        // The same names as in definitions and topologies
+        if let Ok(serde_json::Value::String(mode)) = serde_json::to_value(self.execution_mode) {
+            set("execution_mode".to_string(), mode);
+        }
+        set("immutable_context".to_string(), self.immutable_context.to_string());
+        set("collect_errors".to_string(), self.collect_errors.to_string());
+        set("strict_warnings".to_string(), self.strict_warnings.to_string());
+        if let Some(limit) = self.max_total_duration {
+            set("max_total_duration_ms".to_string(), limit.as_millis().to_string());
+        }
+        for (field, declared) in [
+            ("stop_when", self.stop_when.is_some()),
+            ("finalize", self.finalize.is_some()),
+            ("on_shutdown", self.on_shutdown.is_some()),
+        ] {
+            if declared {
+                set(field.to_string(), "true".to_string());
+            }
+        }
+        if let Some(expect) = &self.expect {
+            set("expect".to_string(), sorted(&mut expect.keys()));
+        }
+        if !self.defaults.is_empty() {
+            set("defaults".to_string(), sorted(&mut self.defaults.keys()));
+        }
+        let validated = self.access_tracker.validated_keys();
+        if !validated.is_empty() {
+            set("validators".to_string(), sorted(&mut validated.iter()));
+        }
+        if let Some(aliases) = &self.aliases {
+            let aliases: LuaTable = self.lua.registry_value(aliases)?;
+            for pair in aliases.pairs::<String, String>() {
+                let (alias, key) = pair?;
+                set(format!("alias.{}", alias), key);
+            }
+        }
+
+        for (index, name) in self.event_names.iter().enumerate() {
+            let mut event = |field: &str, value: String| set(format!("event.{}.{}", name, field), value);
+            if !self.event_tags[index].is_empty() {
+                event("tags", self.event_tags[index].join(","));
+            }
+            if self.event_weights[index] != 1.0 {
+                event("weight", self.event_weights[index].to_string());
+            }
+            if self.event_patch[index] {
+                event("return_mode", "patch".to_string());
+            }
+            if self.event_error_handlers[index].is_some() {
+                event("on_error", "true".to_string());
+            }
+            if self.event_once[index].is_some() {
+                event("once", "true".to_string());
+            }
+            if let Some(policy) = &self.event_key_policy[index] {
+                if let Some(allowed) = &policy.allowed {
+                    event("allowed_keys", sorted(&mut allowed.iter()));
+                }
+                if !policy.denied.is_empty() {
+                    event("denied_keys", sorted(&mut policy.denied.iter()));
+                }
+            }
+            if self.event_bypass_middleware[index] {
+                event("bypass_middleware", "true".to_string());
+            }
+            if let Some(breaker) = &self.event_breakers[index] {
+                event(
+                    "circuit_breaker",
+                    format!("{}/{}ms", breaker.threshold(), breaker.cooldown().as_millis()),
+                );
+            }
+            if self.event_pre[index].is_some() {
+                event("pre", if self.event_pre_skips[index] { "skip" } else { "error" }.to_string());
+            }
+            if self.event_post[index].is_some() {
+                event("post", "true".to_string());
+            }
+            if let Some(memo) = &self.event_memo[index] {
+                event("pure", format!("capacity {}", memo.borrow().capacity()));
+            }
+        }
+        for (index, name) in self.middleware_names.iter().enumerate() {
+            if self.observers.contains(&index) {
+                set(format!("middleware.{}.observational", name), "true".to_string());
+            }
+            if self.middleware_applies_to[index].is_some() {
+                set(format!("middleware.{}.applies_to", name), "true".to_string());
+            }
+            if self.middleware_sample_rates[index] < 1.0 {
+                set(format!("middleware.{}.sample_rate", name), self.middleware_sample_rates[index].to_string());
+            }
+        }
+
+        Ok(ConfigSnapshot {
+            chain: self.meta.name.clone(),
+            version: self.meta.version.clone(),
+            context_keys,
+            events: self.event_names.clone(),
+            middleware: self.middleware_names.clone(),
+            options,
+        })
+    }
+
+    /// Tags declared on event `index`, empty if none or out of range.
+    pub fn event_tags(&self, index: usize) -> &[String] {
+        self.event_tags.get(index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a Lua event right after the first event named `after`.
+    pub fn insert_event_after(
+        &mut self,
+        after: &str,
+        name: &str,
+        handler: LuaFunction,
+    ) -> Result<(), ChainError> {
+        let index = self.event_index(after)? + 1;
+        self.insert_event_at(index, name, handler)
+    }
+
+    /// Add a Lua event right before the first event named `before`.
+    pub fn insert_event_before(
+        &mut self,
+        before: &str,
+        name: &str,
+        handler: LuaFunction,
+    ) -> Result<(), ChainError> {
+        let index = self.event_index(before)?;
+        self.insert_event_at(index, name, handler)
+    }
+
+    /// Add a Lua event at position `index` (`0..=len`). It has no tags, args
+    /// or `on_error`, weight 1, and replaces the context with its return
+    /// value (unless the context is immutable).
+    pub fn insert_event_at(
+        &mut self,
+        index: usize,
+        name: &str,
+        handler: LuaFunction,
+    ) -> Result<(), ChainError> {
+        if index > self.event_handlers.len() {
+            return Err(ChainError::EventIndexOutOfRange { index, len: self.event_handlers.len() });
+        }
+        let handler = self.lua.create_registry_value(handler)?;
+        let args = self.lua.create_registry_value(self.lua.create_table()?)?;
+
+        self.event_names.insert(index, name.to_string());
+        self.event_handlers.insert(index, handler);
+        self.event_tags.insert(index, Vec::new());
+        self.event_args.insert(index, args);
+        self.event_weights.insert(index, 1.0);
+        self.event_patch.insert(index, false);
+        self.event_error_handlers.insert(index, None);
+        self.event_once.insert(index, None);
+        self.event_key_policy.insert(index, None);
+        self.event_bypass_middleware.insert(index, false);
+        self.event_breakers.insert(index, None);
+        self.event_pre.insert(index, None);
+        self.event_post.insert(index, None);
+        self.event_pre_skips.insert(index, false);
+        self.event_memo.insert(index, None);
+        // Per-event statistics are indexed by position, so they restart
+        self.reset_stats();
+        Ok(())
+    }
+
+    /// Remove the first event named `name`.
+    pub fn remove_event(&mut self, name: &str) -> Result<(), ChainError> {
+        let index = self.event_index(name)?;
+        self.event_names.remove(index);
+        self.event_handlers.remove(index);
+        self.event_tags.remove(index);
+        self.event_args.remove(index);
+        self.event_weights.remove(index);
+        self.event_patch.remove(index);
+        self.event_error_handlers.remove(index);
+        self.event_once.remove(index);
+        self.event_key_policy.remove(index);
+        self.event_bypass_middleware.remove(index);
+        self.event_breakers.remove(index);
+        self.event_pre.remove(index);
+        self.event_post.remove(index);
+        self.event_pre_skips.remove(index);
+        self.event_memo.remove(index);
+        // Per-event statistics are indexed by position, so they restart
+        self.reset_stats();
+        Ok(())
+    }
+
+    /// Move the first event named `name` so it ends up at position `index`.
+    pub fn move_event(&mut self, name: &str, index: usize) -> Result<(), ChainError> {
+        let from = self.event_index(name)?;
+        if index >= self.event_handlers.len() {
+            return Err(ChainError::EventIndexOutOfRange { index, len: self.event_handlers.len() });
+        }
+        fn shift<T>(items: &mut Vec<T>, from: usize, to: usize) {
+            let item = items.remove(from);
+            items.insert(to, item);
+        }
+        shift(&mut self.event_names, from, index);
+        shift(&mut self.event_handlers, from, index);
+        shift(&mut self.event_tags, from, index);
+        shift(&mut self.event_args, from, index);
+        shift(&mut self.event_weights, from, index);
+        shift(&mut self.event_patch, from, index);
+        shift(&mut self.event_error_handlers, from, index);
+        shift(&mut self.event_once, from, index);
+        shift(&mut self.event_key_policy, from, index);
+        shift(&mut self.event_bypass_middleware, from, index);
+        shift(&mut self.event_breakers, from, index);
+        shift(&mut self.event_pre, from, index);
+        shift(&mut self.event_post, from, index);
+        shift(&mut self.event_pre_skips, from, index);
+        shift(&mut self.event_memo, from, index);
+        // Per-event statistics are indexed by position, so they restart
+        self.reset_stats();
+        Ok(())
+    }
+
+    /// Make `old` an alias of the context key `new` for every later run, so
+    /// handlers still using the old name read and write the new one. Fails
+    /// if `old` is already an alias, or if the alias would chain onto another.
+    pub fn add_alias(&mut self, old: &str, new: &str) -> Result<(), ChainError> {
+        let aliases: LuaTable = match &self.aliases {
+            Some(aliases) => self.lua.registry_value(aliases)?,
+            None => {
+                let aliases = self.lua.create_table()?;
+                self.aliases = Some(self.lua.create_registry_value(aliases.clone())?);
+                aliases
+            }
+        };
+        let is_alias = |key: &str| aliases.contains_key(key);
+        let is_target = |key: &str| -> LuaResult<bool> {
+            for pair in aliases.clone().pairs::<String, String>() {
+                if pair?.1 == key {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        };
+        if let Some(existing) = aliases.raw_get::<_, Option<String>>(old)? {
+            return Err(ChainError::InvalidDefinition(format!(
+                "'{}' is already an alias of '{}'",
+                old, existing
+            )));
+        }
+        if old == new || is_alias(new)? || is_target(old)? {
+            return Err(ChainError::InvalidDefinition(format!(
+                "alias '{}' -> '{}' would form a chain or cycle of aliases",
+                old, new
+            )));
+        }
+        aliases.raw_set(old, new)?;
+        Ok(())
+    }
+
+    /// The state of the circuit breaker of the first event named `name`, or
+    /// `None` if it declares none.
+    pub fn breaker_state(&self, name: &str) -> Result<Option<BreakerState>, ChainError> {
+        Ok(self.event_breakers[self.event_index(name)?].as_ref().map(CircuitBreaker::state))
+    }
+
+    /// Close every circuit breaker and forget the failures counted so far.
+    pub fn reset_breakers(&self) {
+        for breaker in self.event_breakers.iter().flatten() {
+            breaker.reset();
+        }
+    }
+
+    /// Keep at most `capacity` results per pure event (see memo.rs); 0
+    /// turns memoization off. Defaults to `DEFAULT_MEMO_CAPACITY`.
+    pub fn with_memo_capacity(self, capacity: usize) -> Self {
+        for memo in self.event_memo.iter().flatten() {
+            memo.borrow_mut().set_capacity(capacity);
+        }
+        self
+    }
+
+    /// Forget every memoized result.
+    pub fn clear_memo(&self) {
+        for memo in self.event_memo.iter().flatten() {
+            memo.borrow_mut().clear();
+        }
+    }
+
+    /// How many results the first event named `name` has memoized, or
+    /// `None` if it is not pure.
+    pub fn memo_len(&self, name: &str) -> Result<Option<usize>, ChainError> {
+        Ok(self.event_memo[self.event_index(name)?].as_ref().map(|memo| memo.borrow().len()))
+    }
+
+    /// Let every `once` event run again on the next execute.
+    pub fn reset_once_flags(&self) {
+        for fired in self.event_once.iter().flatten() {
+            fired.set(false);
+        }
+    }
+
+    // A `once` event that has already completed in an earlier run
+    fn once_fired(&self, index: usize) -> bool {
+        self.event_once[index].as_ref().is_some_and(Cell::get)
+    }
+
+    fn event_index(&self, name: &str) -> Result<usize, ChainError> {
+        self.event_names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| ChainError::UnknownEvent(name.to_string()))
+    }
+
+    /// Run the chain against a fresh copy of the definition's context.
+    pub fn execute(&self) -> Result<ChainOutcome, ChainError> {
+        self.execute_fresh(new_run_id())
+    }
+
+    /// Like [`execute`](Self::execute), but with a caller-supplied run ID,
+    /// e.g. to propagate an upstream request ID.
+    pub fn execute_with_id(&self, run_id: &str) -> Result<ChainOutcome, ChainError> {
+        self.execute_fresh(run_id.to_string())
+    }
+
+    // A run from the definition's context, or from the persistent store
+    // once a run has saved to it
+    fn execute_fresh(&self, run_id: String) -> Result<ChainOutcome, ChainError> {
+        let Some(store) = &self.persistent_context else {
+            return self.run(0, self.fresh_context()?, run_id);
+        };
+        let mut store = store.try_borrow_mut().map_err(|_| ChainError::AlreadyRunning)?;
+        let context = if store.keys().is_empty() { self.fresh_context()? } else { self.load_store(&**store)? };
+        self.run_with_store(&mut **store, context, run_id)
+    }
+
+    /// Run the chain using `context` as the working context.
+    pub fn execute_with_context<'lua>(
+        &'lua self,
+        context: LuaTable<'lua>,
+    ) -> Result<ChainOutcome, ChainError> {
+        self.execute_from(0, context)
+    }
+
+    /// Run events `first_event..` against `context`, skipping the ones
+    /// before it. Pair with a checkpoint to resume an interrupted run.
+    pub fn execute_from<'lua>(
+        &'lua self,
+        first_event: usize,
+        context: LuaTable<'lua>,
+    ) -> Result<ChainOutcome, ChainError> {
+        self.run(first_event, context, new_run_id())
+    }
+
+    /// Run the chain against the contents of `store`, then write the final
+    /// context back: changed keys are set and removed keys are removed. The
+    /// store is left untouched if the run fails.
+    pub fn execute_with_store(&self, store: &mut dyn ContextStore) -> Result<ChainOutcome, ChainError> {
+        let context = self.load_store(store)?;
+        self.run_with_store(store, context, new_run_id())
+    }
+
+    /// What-if run: fork the context `execute` would start from (the
+    /// persistent store's, if it holds any, else the definition's), apply
+    /// `overrides` to the fork and run the chain on it. The working copy is
+    /// then discarded, so the persistent context is left untouched; the
+    /// result reports the outcome and how the run would have changed the
+    /// starting context (before overrides).
+    ///
+    /// Only the context is forked. Handlers really run, so their external
+    /// side effects (I/O, host callbacks, userdata they mutate) still
+    /// happen, and stats, checkpoints, the last outcome, `once` flags,
+    /// circuit breakers and memo caches are updated as by any run.
+    pub fn trial(&self, overrides: ContextMap) -> Result<TrialOutcome, ChainError> {
+        let context = match &self.persistent_context {
+            Some(store) => {
+                let store = store.try_borrow().map_err(|_| ChainError::AlreadyRunning)?;
+                if store.keys().is_empty() { self.fresh_context()? } else { self.load_store(&**store)? }
+            }
+            None => self.fresh_context()?,
+        };
+        let before = self.owned(&context)?;
+        for (key, value) in overrides {
+            context.set(key, value)?;
+        }
+        let outcome = self.run(0, context, new_run_id())?;
+        let changed = outcome
+            .context
+            .iter()
+            .filter(|&(key, value)| before.get(key) != Some(value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let removed = before.keys().filter(|key| !outcome.context.contains_key(*key)).cloned().collect();
+        Ok(TrialOutcome { outcome, changed, removed })
+    }
+
+    fn load_store(&self, store: &dyn ContextStore) -> Result<LuaTable<'_>, ChainError> {
+        let context = self.lua.create_table()?;
+        for key in store.keys() {
+            if let Some(value) = store.get_raw(&key) {
+                let value = match self.cipher_for(&key)? {
+                    Some(cipher) => cipher::open(cipher, &key, value)?,
+                    None => value,
+                };
+                context.set(key, value)?;
+            }
+        }
+        Ok(context)
+    }
+
+    // The cipher if `key` is sensitive
+    fn cipher_for(&self, key: &str) -> Result<Option<&dyn ContextCipher>, ChainError> {
+        if !self.sensitive_keys.contains(key) {
+            return Ok(None);
+        }
+        match &self.cipher {
+            Some(cipher) => Ok(Some(&**cipher)),
+            None => Err(cipher::failure(key, "marked sensitive but no cipher is configured".to_string())),
+        }
+    }
+
+    fn run_with_store<'lua>(
+        &'lua self,
+        store: &mut dyn ContextStore,
+        context: LuaTable<'lua>,
+        run_id: String,
+    ) -> Result<ChainOutcome, ChainError> {
+        // Without a cipher, fail before the run rather than after it
+        if let Some(key) = self.sensitive_keys.iter().next() {
+            self.cipher_for(key)?;
+        }
+        let outcome = self.run(0, context, run_id)?;
+        // Sealed up front, so a cipher error leaves the store as it was
+        let mut sealed = HashMap::new();
+        for (key, value) in &outcome.context {
+            if let Some(cipher) = self.cipher_for(key)? {
+                sealed.insert(key, cipher::seal(cipher, key, value)?);
+            }
+        }
+        for key in store.keys() {
+            if !outcome.context.contains_key(&key) {
+                store.remove(&key);
+            }
+        }
+        for (key, value) in &outcome.context {
+            match sealed.remove(key) {
+                // Ciphertexts may differ for equal values, so always written
+                Some(sealed) => store.set_raw(key, sealed),
+                None if store.get_raw(key).as_ref() != Some(value) => store.set_raw(key, value.clone()),
+                None => {}
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Run the middleware named `name` alone around a no-op event, to test
+    /// it in isolation. Returns the context it produced and the names
+    /// entered: its own, then "next" if it passed control on. Observational
+    /// middleware is called as in a run and gets a read-only context.
+    ///
+    /// ```ignore
+    /// let (context, trace) = runner.run_middleware("auth", from_serialize(&request)?)?;
+    /// assert_eq!(trace, ["auth"]); // rejected: the event was never reached
+    /// ```
+    pub fn run_middleware(
+        &self,
+        name: &str,
+        context: ContextMap,
+    ) -> Result<(ContextMap, Vec<String>), ChainError> {
+        let middleware_index = self
+            .middleware_names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| ChainError::UnknownMiddleware(name.to_string()))?;
+        let _running = RunningGuard::enter(&self.run_state)?;
+        let context = map_to_table(&self.lua, &context)?;
+        install_refs(&self.lua, &context)?;
+        let globals = self.lua.globals();
+        globals.set(CONTEXT_GLOBAL, context.clone())?;
+        globals.set(HELPERS_GLOBAL, self.lua.registry_value::<LuaTable>(&self.helpers)?)?;
+        self.cross(|b| b.global_writes += 2);
+        self.run_state.abort_requested.set(false);
+
+        let middleware: LuaFunction = self.lua.registry_value(&self.middleware_handlers[middleware_index])?;
+        let trace = RefCell::new(vec![name.to_string()]);
+        self.cross(|b| b.lua_calls += 1);
+        if self.observers.contains(&middleware_index) {
+            middleware.call::<_, ()>(self.visible(&self.lua, &context, true, None)?)?;
+            return Ok((self.owned(&context)?, trace.into_inner()));
+        }
+        let returned = self.lua.scope(|scope| {
+            let next = scope.create_function(|_, next_context: LuaTable| {
+                trace.borrow_mut().push("next".to_string());
+                Ok(next_context)
+            })?;
+            self.cross(|b| b.functions_created += 1);
+            let returned: Option<LuaTable> =
+                middleware.call((self.visible(&self.lua, &context, self.immutable_context, None)?, next))?;
+            match returned {
+                Some(table) => self.hidden(table),
+                None => Ok(context),
+            }
+        })?;
+        Ok((self.owned(&returned)?, trace.into_inner()))
+    }
+
+    /// The context a run would start from: the definition's, with defaults
+    /// filled in.
+    pub(crate) fn initial_map(&self) -> LuaResult<ContextMap> {
+        let mut map = table_to_map(&self.fresh_context()?)?;
+        for (key, value) in &self.defaults {
+            map.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        Ok(map)
+    }
+
+    /// Run event `index` (with its middleware) once against an owned copy
+    /// of the context, outside any run.
+    pub(crate) fn execute_event_map(&self, index: usize, map: &ContextMap, run_id: &str) -> Result<ContextMap, ChainError> {
+        self.with_map_context(map, run_id, |context| {
+            self.run_state.current_event.set(index);
+            self.execute_with_middleware(index, context, None)
+        })
+    }
+
+    /// Run Lua `source` once as a bare handler body, `function(ctx, args)`
+    /// like an event's `source`, against an owned copy of the context. No
+    /// middleware or event options apply.
+    pub(crate) fn execute_source_map(
+        &self,
+        name: &str,
+        source: &str,
+        map: &ContextMap,
+        run_id: &str,
+    ) -> Result<ContextMap, ChainError> {
+        let handler = compile_source(&self.lua, &format!("inline:{}", name), source)
+            .map_err(|e| ChainError::InvalidDefinition(format!("inline event ({}) has invalid source: {}", name, e)))?;
+        self.with_map_context(map, run_id, |context| {
+            self.cross(|b| b.lua_calls += 1);
+            let returned: Option<LuaTable> = handler.call((context.clone(), self.lua.create_table()?))?;
+            let updated = match returned {
+                Some(table) => self.hidden(table)?,
+                None => context,
+            };
+            self.lua.globals().set(CONTEXT_GLOBAL, updated.clone())?;
+            Ok(updated)
+        })
+    }
+
+    // Set up `map` as the working context outside any run, as part of run
+    // `run_id`, call `step` on it and hand back the owned result
+    fn with_map_context<'lua>(
+        &'lua self,
+        map: &ContextMap,
+        run_id: &str,
+        step: impl FnOnce(LuaTable<'lua>) -> LuaResult<LuaTable<'lua>>,
+    ) -> Result<ContextMap, ChainError> {
+        let _running = RunningGuard::enter(&self.run_state)?;
+        let context = map_to_table(&self.lua, map)?;
+        install_refs(&self.lua, &context)?;
+        let globals = self.lua.globals();
+        globals.set(CONTEXT_GLOBAL, context.clone())?;
+        globals.set(HELPERS_GLOBAL, self.lua.registry_value::<LuaTable>(&self.helpers)?)?;
+        self.cross(|b| b.global_writes += 2);
+        self.run_state.abort_requested.set(false);
+        *self.run_state.run_id.borrow_mut() = run_id.to_string();
+        let updated = step(context)?;
+        Ok(self.owned(&updated)?)
+    }
+
+    // The owned form of a context handed to callers
+    fn owned(&self, context: &LuaTable) -> LuaResult<ContextMap> {
+        match &self.value_coercion {
+            Some(coerce) => table_to_map_with(context, coerce),
+            None => table_to_map(context),
+        }
+    }
+
+    fn fresh_context(&self) -> LuaResult<LuaTable<'_>> {
+        let initial: LuaTable = self.lua.registry_value(&self.initial_context)?;
+        copy_table(&self.lua, &initial)
+    }
+
+    fn run<'lua>(
+        &'lua self,
+        first_event: usize,
+        context: LuaTable<'lua>,
+        run_id: String,
+    ) -> Result<ChainOutcome, ChainError> {
+        if first_event > self.event_handlers.len() {
+            return Err(ChainError::EventIndexOutOfRange {
+                index: first_event,
+                len: self.event_handlers.len(),
+            });
+        }
+
+        let _running = RunningGuard::enter(&self.run_state)?;
+        let _flush = FlushGuard(self.trace_sink.as_ref());
+        #[cfg(feature = "tracing")]
+        let _run_span = {
+            let span = tracing::info_span!("chain_run", chain = self.meta.name.as_deref(), run_id = %run_id);
+            #[cfg(feature = "opentelemetry")]
+            crate::otel::adopt_parent(&span, &context);
+            span.entered()
+        };
+        // A failed run leaves nothing behind rather than a stale outcome
+        if let Some(last) = &self.last_outcome {
+            last.borrow_mut().take();
+        }
+        let _warnings = (self.capture_warnings || self.strict_warnings)
+            .then(|| WarningGuard::install(&self.lua, &self.run_state, self.strict_warnings));
+        let memory_before = self.gc_stats.then(|| self.lua.used_memory());
+        let boundary_before = self.boundary_stats();
+        let start = Instant::now();
+        for (key, value) in &self.defaults {
+            if !context.contains_key(key.as_str())? {
+                context.set(key.as_str(), value.clone())?;
+            }
+        }
+        install_refs(&self.lua, &context)?;
+        let globals = self.lua.globals();
+        globals.set(CONTEXT_GLOBAL, context.clone())?;
+        let mut context = context;
+        let helpers: LuaTable = self.lua.registry_value(&self.helpers)?;
+        globals.set(HELPERS_GLOBAL, helpers)?;
+        self.cross(|b| b.global_writes += 2);
+        self.run_state.abort_requested.set(false);
+        self.run_state.expiry.borrow_mut().clear();
+        self.run_state.events_completed.set(0);
+        *self.run_state.run_id.borrow_mut() = run_id.clone();
+        self.access_tracker.reset();
+        if let Some(stats) = &self.stats {
+            stats.borrow_mut().runs += 1;
+        }
+
+        let mut status = OutcomeStatus::Completed;
+        let mut events_executed = 0;
+        let mut middleware_trace = self.trace.then(Vec::new);
+        let mut no_change_warnings = self.warn_on_no_change.then(Vec::new);
+        let mut collected = self.collect_errors.then(Vec::new);
+
+        let indices: Vec<usize> = match self.execution_mode {
+            ExecutionMode::Sequential => (first_event..self.event_handlers.len()).collect(),
+            ExecutionMode::WeightedRandom => self.draw_event(first_event).into_iter().collect(),
+            ExecutionMode::Shuffle => self.shuffle_events(first_event),
+        };
+
+        for (position, index) in indices.into_iter().enumerate() {
+            if self.once_fired(index) {
+                continue;
+            }
+            if let Some(limit) = self.max_total_duration {
+                let elapsed = start.elapsed();
+                if position > 0 && elapsed > limit {
+                    self.run_cleanup(index);
+                    return Err(ChainError::TotalTimeout {
+                        chain: self.meta.name.clone(),
+                        run_id,
+                        limit,
+                        elapsed,
+                        next_event: index,
+                    });
+                }
+            }
+
+            let layers = self.trace.then(|| RefCell::new(Vec::new()));
+            let event_start = Instant::now();
+            let before = match no_change_warnings {
+                Some(_) => Some(self.or_cleanup(index, table_to_map(&context))?),
+                None => None,
+            };
+            let validated_before = match self.access_tracker.has_validators() {
+                true => Some(self.or_cleanup(index, self.validated_values(&context))?),
+                false => None,
+            };
+            let snapshot = match self.collect_errors {
+                true => Some(self.or_cleanup(index, snapshot_context(&self.lua, &context))?),
+                false => None,
+            };
+            self.access_tracker.take_violation();
+            self.run_state.current_event.set(index);
+            self.trace_boundary("event", index, &self.event_names[index], None);
+            #[cfg(feature = "tracing")]
+            let event_span = tracing::info_span!(
+                "event",
+                name = self.event_names[index].as_str(),
+                index,
+                status = tracing::field::Empty,
+                duration_ms = tracing::field::Empty
+            )
+            .entered();
+            let result = self.execute_with_middleware(index, context.clone(), layers.as_ref());
+            let event_status = match &result {
+                _ if self.run_state.abort_requested.get() => "aborted",
+                Ok(_) => "ok",
+                Err(_) => "error",
+            };
+            #[cfg(feature = "tracing")]
+            {
+                event_span.record("status", event_status);
+                event_span.record("duration_ms", event_start.elapsed().as_secs_f64() * 1000.0);
+                #[cfg(feature = "opentelemetry")]
+                crate::otel::set_status(&event_span, event_status, result.as_ref().err());
+                drop(event_span);
+            }
+            self.trace_boundary("event", index, &self.event_names[index], Some((event_start, event_status)));
+            if let Some(stats) = &self.stats
+                && !self.run_state.abort_requested.get()
+            {
+                stats.borrow_mut().record(index, event_start.elapsed(), result.is_ok());
+            }
+            if let (Some(trace), Some(layers)) = (middleware_trace.as_mut(), layers) {
+                trace.push(layers.into_inner());
+            }
+            // Checked before the result: an abort unwinds as a Lua error
+            if self.run_state.abort_requested.get() {
+                status = OutcomeStatus::Aborted;
+                self.run_cleanup(index + 1);
+                break;
+            }
+            let failure = match result {
+                Ok(updated) => {
+                    context = updated;
+                    match validated_before {
+                        Some(before) => {
+                            self.or_cleanup(index + 1, self.check_validated(index, &run_id, before, &context))?
+                        }
+                        None => None,
+                    }
+                }
+                Err(e) => Some(self.event_error(index, &run_id, e)),
+            };
+            if let Some(error) = failure {
+                match (collected.as_mut(), snapshot) {
+                    (Some(collected), Some(snapshot)) => {
+                        collected.push(error);
+                        context = snapshot;
+                        self.or_cleanup(index + 1, globals.set(CONTEXT_GLOBAL, context.clone()))?;
+                        continue;
+                    }
+                    _ => {
+                        self.run_cleanup(index + 1);
+                        return Err(error);
+                    }
+                }
+            }
+            events_executed += 1;
+            if let Some(fired) = &self.event_once[index] {
+                fired.set(true);
+            }
+            if let (Some(warnings), Some(before)) = (no_change_warnings.as_mut(), before)
+                && self.or_cleanup(index + 1, table_to_map(&context))? == before
+            {
+                warnings.push(format!("{} completed without changing the context", self.event_label(index)));
+            }
+            self.run_state.events_completed.set(events_executed);
+            self.or_cleanup(index + 1, self.evict_expired(&context))?;
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint(index, &self.or_cleanup(index + 1, self.owned(&context))?);
+            }
+            if let Some(stop_when) = &self.stop_when {
+                let stop_when: LuaFunction = self.or_cleanup(
+                    index + 1,
+                    self.lua.registry_value(stop_when).context("failed to resolve stop_when predicate"),
+                )?;
+                self.cross(|b| b.lua_calls += 1);
+                let stop = self
+                    .visible(&self.lua, &context, true, None)
+                    .and_then(|visible| stop_when.call::<_, bool>(visible))
+                    .context("stop_when failed");
+                match stop {
+                    Ok(true) => {
+                        status = OutcomeStatus::StoppedEarly;
+                        self.run_cleanup(index + 1);
+                        break;
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        let error = self.event_error(index, &run_id, e);
+                        self.run_cleanup(index + 1);
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        if let Some(errors) = collected
+            && !errors.is_empty()
+        {
+            return Err(ChainError::Multiple { errors, context: self.owned(&context)? });
+        }
+
+        let mut final_context = context;
+        if let Some(finalize) = &self.finalize {
+            let finalize: LuaFunction = self
+                .lua
+                .registry_value(finalize)
+                .context("failed to resolve finalize function")?;
+            let returned: Option<LuaTable> =
+                finalize.call(self.visible(&self.lua, &final_context, false, None)?)?;
+            if let Some(table) = returned {
+                final_context = self.hidden(table)?;
+            }
+            globals.set(CONTEXT_GLOBAL, final_context.clone())?;
+            self.cross(|b| {
+                b.lua_calls += 1;
+                b.global_writes += 1;
+            });
+        }
+        let outcome = ChainOutcome {
+            run_id,
+            status,
+            context: self.owned(&final_context)?,
+            events_executed,
+            duration: start.elapsed(),
+            finished_at: SystemTime::now(),
+            middleware_trace,
+            access_report: self.track_access.get().then(|| self.access_tracker.report()),
+            gc: memory_before.map(|memory_before| GcStats {
+                memory_before,
+                memory_after: self.lua.used_memory(),
+            }),
+            boundary: boundary_before.and_then(|before| Some(self.boundary_stats()?.since(&before))),
+            no_change_warnings,
+            lua_warnings: self
+                .capture_warnings
+                .then(|| std::mem::take(&mut *self.run_state.warnings.borrow_mut())),
+        };
+        if let Some(last) = &self.last_outcome {
+            *last.borrow_mut() = Some(outcome.clone());
+        }
+        Ok(outcome)
+    }
+
+    /// `result`, after running the cleanup events from `from` if it is an
+    /// error: the run's own bookkeeping between events failing stops the
+    /// run like a failed event does.
+    fn or_cleanup<T>(&self, from: usize, result: LuaResult<T>) -> Result<T, ChainError> {
+        result.map_err(|e| {
+            self.run_cleanup(from);
+            e.into()
+        })
+    }
+
+    /// Run the cleanup-tagged events from `from` onwards after the run
+    /// stopped early. They are not counted, checkpointed or timed, and
+    /// their errors are dropped so the original error is the one reported.
+    fn run_cleanup(&self, from: usize) {
+        if self.execution_mode != ExecutionMode::Sequential {
+            return;
+        }
+        self.run_state.abort_requested.set(false);
+        for index in from..self.event_handlers.len() {
+            if self.event_tags(index).iter().any(|tag| tag == CLEANUP_TAG)
+                && !self.once_fired(index)
+                && let Ok(context) = self.lua.globals().get::<_, LuaTable>(CONTEXT_GLOBAL)
+            {
+                self.cross(|b| b.global_reads += 1);
+                let _ = self.execute_with_middleware(index, context, None);
+            }
+        }
+    }
+
+    /// Whether Lua code called `chain.abort_chain()` since the last event
+    /// started.
+    pub(crate) fn abort_requested(&self) -> bool { self.run_state.abort_requested.get() }
+
+    /// The error for event `index` raising `error`: a validation failure if
+    /// a validator rejected an assignment, otherwise a plain event failure.
+    pub(crate) fn event_error(&self, index: usize, run_id: &str, error: LuaError) -> ChainError {
+        let (chain, run_id, name) = (self.meta.name.clone(), run_id.to_string(), self.event_names[index].clone());
+        match self.access_tracker.take_violation() {
+            Some((key, message)) => ChainError::ValidationFailed {
+                chain,
+                run_id,
+                index,
+                name,
+                message: format!("'{}': {}", key, message),
+            },
+            None => ChainError::EventFailed { chain, run_id, index, name, message: error.to_string() },
+        }
+    }
+
+    /// Validate the keys event `index` changed from `before`. This catches
+    /// what the proxy cannot see: returned tables, patches, native events
+    /// and `rawset`.
+    fn check_validated(
+        &self,
+        index: usize,
+        run_id: &str,
+        before: Vec<(String, Option<ContextValue>)>,
+        context: &LuaTable,
+    ) -> LuaResult<Option<ChainError>> {
+        for ((key, old), (_, new)) in before.into_iter().zip(self.validated_values(context)?) {
+            if let Some(value) = new.filter(|new| old.as_ref() != Some(new))
+                && let Err(message) = self.access_tracker.validate(&key, &value)
+            {
+                return Ok(Some(ChainError::ValidationFailed {
+                    chain: self.meta.name.clone(),
+                    run_id: run_id.to_string(),
+                    index,
+                    name: self.event_names[index].clone(),
+                    message: format!("'{}': {}", key, message),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Owned values of the keys that have validators, read raw so lazy keys
+    /// are not produced just to be checked.
+    fn validated_values(&self, context: &LuaTable) -> LuaResult<Vec<(String, Option<ContextValue>)>> {
+        let mut values = Vec::new();
+        for key in self.access_tracker.validated_keys() {
+            let value = ContextValue::from_lua(&context.raw_get(key.as_str())?);
+            values.push((key, value));
+        }
+        Ok(values)
+    }
+
+    /// Remove the keys whose TTL ran out with the event that just completed.
+    fn evict_expired(&self, context: &LuaTable) -> LuaResult<()> {
+        let mut expiry = self.run_state.expiry.borrow_mut();
+        if expiry.is_empty() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut result = Ok(());
+        expiry.retain(|key, expiry| {
+            let expired = match expiry {
+                Expiry::AfterEvents(count) => self.run_state.events_completed.get() >= *count,
+                Expiry::At(deadline) => now >= *deadline,
+            };
+            if expired && result.is_ok() {
+                result = context.set(key.as_str(), LuaValue::Nil);
+            }
+            !expired
+        });
+        result
+    }
+
+    /// Events `first_event..` in a random order (Fisher-Yates).
+    fn shuffle_events(&self, first_event: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (first_event..self.event_handlers.len()).collect();
+        for i in (1..order.len()).rev() {
+            let j = ((next_unit(&self.rng) * (i + 1) as f64) as usize).min(i);
+            order.swap(i, j);
+        }
+        order
+    }
+
+    /// Pick an event from `first_event..` with probability proportional to
+    /// its weight, or `None` if none of them has a positive weight.
+    fn draw_event(&self, first_event: usize) -> Option<usize> {
+        let weights: Vec<f64> = (first_event..self.event_weights.len())
+            .map(|index| if self.once_fired(index) { 0.0 } else { self.event_weights[index] })
+            .collect();
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut remaining = next_unit(&self.rng) * total;
+        let mut picked = None;
+        for (offset, weight) in weights.iter().enumerate() {
+            if *weight > 0.0 {
+                // Rounding can leave `remaining` just past the last bucket
+                picked = Some(first_event + offset);
+                if remaining < *weight {
+                    break;
+                }
+            }
+            remaining -= weight;
+        }
+        picked
+    }
+
+    /// Predict the context keys each event touches with a trial run.
+    ///
+    /// Every event runs once, in declaration order and through its
+    /// middleware, against a fresh context with access tracking on; stats,
+    /// checkpoints, `finalize` and the last outcome are left untouched. This
+    /// is best-effort: the trial really executes the handlers (side effects
+    /// included), and handlers that pick keys from data or take branches
+    /// this context does not exercise may touch other keys in a real run.
+    pub fn preview_context(&self) -> Result<PreviewResult, ChainError> {
+        let _running = RunningGuard::enter(&self.run_state)?;
+        let mut context = self.fresh_context()?;
+        for (key, value) in &self.defaults {
+            if !context.contains_key(key.as_str())? {
+                context.set(key.as_str(), value.clone())?;
+            }
+        }
+        install_refs(&self.lua, &context)?;
+        let globals = self.lua.globals();
+        globals.set(CONTEXT_GLOBAL, context.clone())?;
+        globals.set(HELPERS_GLOBAL, self.lua.registry_value::<LuaTable>(&self.helpers)?)?;
+        self.cross(|b| b.global_writes += 2);
+        self.run_state.abort_requested.set(false);
+        self.run_state.expiry.borrow_mut().clear();
+        self.run_state.events_completed.set(0);
+        *self.run_state.run_id.borrow_mut() = "preview".to_string();
+
+        let tracking = self.track_access.replace(true);
+        let mut preview = PreviewResult::default();
+        for index in 0..self.event_handlers.len() {
+            let before = table_to_map(&context)?;
+            self.run_state.current_event.set(index);
+            self.access_tracker.reset();
+            let result = self.execute_with_middleware(index, context.clone(), None);
+            let report = self.access_tracker.report();
+            for key in report.read.difference(&report.written) {
+                if !before.contains_key(key) {
+                    preview.warnings.push(format!(
+                        "{} reads '{}', which is never set before it",
+                        self.event_label(index),
+                        key
+                    ));
+                }
+            }
+            preview.events.push((self.event_names[index].clone(), report));
+            if self.run_state.abort_requested.get() {
+                break;
+            }
+            context = match result {
+                Ok(updated) => updated,
+                Err(e) => {
+                    self.track_access.set(tracking);
+                    return Err(ChainError::EventFailed {
+                        chain: self.meta.name.clone(),
+                        run_id: "preview".to_string(),
+                        index,
+                        name: self.event_names[index].clone(),
+                        message: e.to_string(),
+                    });
+                }
+            };
+        }
+        self.track_access.set(tracking);
+        self.access_tracker.reset();
+        preview.final_keys = table_to_map(&context)?.into_keys().collect();
+        Ok(preview)
+    }
+
+    /// Run the chain once per input, reusing the parsed handlers.
+    ///
+    /// Each input becomes the working context for its own run, so no state
+    /// leaks from one input to the next. Failures are reported per input.
+    pub fn execute_batch<'lua>(
+        &'lua self,
+        inputs: Vec<LuaTable<'lua>>,
+    ) -> Vec<Result<ChainOutcome, ChainError>> {
+        inputs
+            .into_iter()
+            .map(|input| self.execute_with_context(input))
+            .collect()
+    }
+
+    // The context is threaded through the run as a local; the global is only
+    // written back so Lua code reading `__context` sees the latest table.
+    fn execute_with_middleware<'lua>(
+        &'lua self,
+        index: usize,
+        context: LuaTable<'lua>,
+        trace: Option<&RefCell<Vec<String>>>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        if !self.holds(self.event_pre[index].as_ref(), &context)? {
+            if self.event_pre_skips[index] {
+                return Ok(context);
+            }
+            return Err(LuaError::RuntimeError(format!("precondition failed for {}", self.event_label(index))));
+        }
+        let updated = if self.event_bypass_middleware[index] {
+            // Straight to the base case of the stack
+            self.execute_middleware_stack(&self.lua, index, self.middleware_stack.len(), context, trace)?
+        } else {
+            self.notify_observers(index, &context, trace)?;
+            self.execute_middleware_stack(&self.lua, index, 0, context, trace)?
+        };
+        if !self.holds(self.event_post[index].as_ref(), &updated)? {
+            return Err(LuaError::RuntimeError(format!("postcondition violated by {}", self.event_label(index))));
+        }
+        self.lua.globals().set(CONTEXT_GLOBAL, updated.clone())?;
+        self.cross(|b| b.global_writes += 1);
+        Ok(updated)
+    }
+
+    // Whether a `pre` or `post` predicate, if any, accepts `context`
+    fn holds(&self, condition: Option<&LuaRegistryKey>, context: &LuaTable) -> LuaResult<bool> {
+        let Some(condition) = condition else {
+            return Ok(true);
+        };
+        let condition: LuaFunction = self.lua.registry_value(condition)?;
+        self.cross(|b| b.lua_calls += 1);
+        condition.call(self.visible(&self.lua, context, true, None)?)
+    }
+
+    // Call event `index`'s handler (or `on_error`) and apply its result to
+    // `context`. The flag is set when `on_error` supplied the result.
+    fn call_handler<'lua>(
+        &self,
+        lua: &'lua Lua,
+        index: usize,
+        context: LuaTable<'lua>,
+    ) -> LuaResult<(LuaTable<'lua>, bool)> {
+        let handler: LuaFunction = lua
+            .registry_value(&self.event_handlers[index])
+            .with_context(|_| format!("failed to resolve handler for {}", self.event_label(index)))?;
+        let args: LuaTable = lua
+            .registry_value(&self.event_args[index])
+            .with_context(|_| format!("failed to resolve args for {}", self.event_label(index)))?;
+        let policy = self.event_key_policy[index].as_ref();
+        let visible = self.visible(lua, &context, self.immutable_context, policy)?;
+        // A policy event must not reach the real table through the global
+        if policy.is_some() {
+            lua.globals().set(CONTEXT_GLOBAL, visible.clone())?;
+        }
+        let called = self.call_guarded(lua, index, &handler, args, &visible);
+        if policy.is_some() {
+            lua.globals().set(CONTEXT_GLOBAL, context.clone())?;
+        }
+        let (returned, recovered) = called?;
+        let patch_mode = self.event_patch[index] || self.immutable_context || policy.is_some();
+        let updated = match returned.map(|table| self.hidden(table)).transpose()? {
+            Some(patch) if patch_mode && patch != context => {
+                for pair in patch.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = pair?;
+                    if let (Some(policy), LuaValue::String(name)) = (policy, &key)
+                        && !policy.permits(&name.to_string_lossy())
+                    {
+                        return Err(LuaError::RuntimeError(format!(
+                            "{} may not write context key '{}'",
+                            self.event_label(index),
+                            name.to_string_lossy()
+                        )));
+                    }
+                    context.set(key, value)?;
+                }
+                context
+            }
+            Some(table) => table,
+            None => context,
+        };
+        Ok((updated, recovered))
+    }
+
+    // Call event `index`'s handler through its circuit breaker and fault
+    // injector, falling back to `on_error`. The flag is set when `on_error`
+    // supplied the result.
+    fn call_guarded<'lua>(
+        &self,
+        lua: &'lua Lua,
+        index: usize,
+        handler: &LuaFunction<'lua>,
+        args: LuaTable<'lua>,
+        visible: &LuaTable<'lua>,
+    ) -> LuaResult<(Option<LuaTable<'lua>>, bool)> {
+        let breaker = self.event_breakers[index].as_ref();
+        let result = match breaker {
+            Some(breaker) if !breaker.allows() => Err(LuaError::RuntimeError(format!(
+                "circuit breaker open for {}",
+                self.event_label(index)
+            ))),
+            _ => {
+                let faulted =
+                    self.fault_injector.as_ref().is_some_and(|faults| faults.inject(&self.event_names[index], &self.rng));
+                let result = if faulted {
+                    Err(LuaError::RuntimeError(format!("injected fault in {}", self.event_label(index))))
+                } else {
+                    self.cross(|b| b.lua_calls += 1);
+                    handler.call((visible.clone(), args))
+                };
+                if let Some(breaker) = breaker
+                    && !self.run_state.abort_requested.get()
+                {
+                    breaker.record(result.is_ok());
+                }
+                result
+            }
+        };
+        match result {
+            Ok(returned) => Ok((returned, false)),
+            Err(e) => match &self.event_error_handlers[index] {
+                Some(on_error) if !self.run_state.abort_requested.get() => {
+                    let on_error: LuaFunction = lua.registry_value(on_error).with_context(|_| {
+                        format!("failed to resolve on_error for {}", self.event_label(index))
+                    })?;
+                    self.cross(|b| b.lua_calls += 1);
+                    Ok((on_error.call((visible.clone(), e.to_string()))?, true))
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    fn execute_middleware_stack<'lua>(
+        &self,
+        lua: &'lua Lua,
+        index: usize,
+        depth: usize,
+        context: LuaTable<'lua>,
+        trace: Option<&RefCell<Vec<String>>>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        // `next` may be handed the proxy a middleware received
+        let context = self.hidden(context)?;
+        if depth == self.middleware_stack.len() {
+            // Base case: the event handler itself, or its memoized result
+            let Some(memo) = &self.event_memo[index] else {
+                return self.call_handler(lua, index, context).map(|(updated, _)| updated);
+            };
+            let Some((hash, input)) = memo_input(&context)? else {
+                return self.call_handler(lua, index, context).map(|(updated, _)| updated);
+            };
+            if let Some(output) = memo.borrow().get(hash, &input) {
+                apply_output(&context, &input, output)?;
+                return Ok(context);
+            }
+            let (updated, recovered) = self.call_handler(lua, index, context)?;
+            if !recovered && let Some((_, output)) = memo_input(&updated)? {
+                memo.borrow_mut().insert(hash, input, output);
+            }
+            return Ok(updated);
+        }
+
+        // LIFO: the last declared middleware is entered first
+        let middleware_index = self.middleware_stack[self.middleware_stack.len() - 1 - depth];
+        if !self.applies(lua, middleware_index, index, &context)? {
+            return self.execute_middleware_stack(lua, index, depth + 1, context, trace);
+        }
+        let middleware: LuaFunction = lua
+            .registry_value(&self.middleware_handlers[middleware_index])
+            .with_context(|_| {
+                format!(
+                    "failed to resolve handler for middleware[{}]:{}",
+                    middleware_index, self.middleware_names[middleware_index]
+                )
+            })?;
+        if let Some(trace) = trace {
+            trace.borrow_mut().push(self.middleware_names[middleware_index].clone());
+        }
+
+        lua.scope(|scope| {
+            let next = scope.create_function(|lua, next_context: LuaTable| {
+                self.execute_middleware_stack(lua, index, depth + 1, next_context, trace)
+            })?;
+            self.cross(|b| {
+                b.functions_created += 1;
+                b.lua_calls += 1;
+            });
+            let name = &self.middleware_names[middleware_index];
+            let started = Instant::now();
+            self.trace_boundary("middleware", index, name, None);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("middleware", name = name.as_str()).entered();
+            let returned = middleware.call((self.visible(lua, &context, self.immutable_context, None)?, next));
+            let status = if returned.is_ok() { "ok" } else { "error" };
+            self.trace_boundary("middleware", index, name, Some((started, status)));
+            let returned: Option<LuaTable> = returned?;
+            let returned = match returned {
+                Some(table) => self.hidden(table)?,
+                None => context,
+            };
+            if let Some(inspector) = &self.middleware_inspector {
+                inspector(middleware_index, &returned);
+            }
+            Ok(returned)
+        })
+    }
+
+    // Whether a middleware runs around event `index` this time: its sample
+    // draw hits, then its `applies_to` predicate accepts the live context
+    fn applies(&self, lua: &Lua, middleware_index: usize, index: usize, context: &LuaTable) -> LuaResult<bool> {
+        let rate = self.middleware_sample_rates[middleware_index];
+        if rate < 1.0 && next_unit(&self.rng) >= rate {
+            return Ok(false);
+        }
+        let Some(predicate) = &self.middleware_applies_to[middleware_index] else {
+            return Ok(true);
+        };
+        let predicate: LuaFunction = lua.registry_value(predicate)?;
+        self.cross(|b| b.lua_calls += 1);
+        predicate
+            .call::<_, bool>((self.event_names[index].as_str(), self.visible(lua, context, true, None)?))
+            .with_context(|_| format!("applies_to of middleware[{}]:{}", middleware_index, self.middleware_names[middleware_index]))
+    }
+
+    // Each observer gets its own read-only proxy, so a `rawset` on one
+    // cannot leak into what the next one sees
+    fn notify_observers(
+        &self,
+        index: usize,
+        context: &LuaTable,
+        trace: Option<&RefCell<Vec<String>>>,
+    ) -> LuaResult<()> {
+        for &middleware_index in &self.observers {
+            if !self.applies(&self.lua, middleware_index, index, context)? {
+                continue;
+            }
+            let observer: LuaFunction = self
+                .lua
+                .registry_value(&self.middleware_handlers[middleware_index])
+                .with_context(|_| {
+                    format!(
+                        "failed to resolve handler for middleware[{}]:{}",
+                        middleware_index, self.middleware_names[middleware_index]
+                    )
+                })?;
+            if let Some(trace) = trace {
+                trace.borrow_mut().push(self.middleware_names[middleware_index].clone());
+            }
+            let name = &self.middleware_names[middleware_index];
+            let started = Instant::now();
+            self.trace_boundary("middleware", self.run_state.current_event.get(), name, None);
+            self.cross(|b| b.lua_calls += 1);
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("middleware", name = name.as_str(), observer = true).entered();
+            let result = observer.call::<_, ()>(self.visible(&self.lua, context, true, None)?);
+            let status = if result.is_ok() { "ok" } else { "error" };
+            self.trace_boundary("middleware", self.run_state.current_event.get(), name, Some((started, status)));
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Record boundary crossings when `with_boundary_stats` is on.
+    fn cross(&self, record: impl FnOnce(&mut BoundaryStats)) {
+        if let Some(boundary) = &self.boundary {
+            let mut stats = boundary.get();
+            record(&mut stats);
+            boundary.set(stats);
+        }
+    }
+
+    /// Write one trace record: an enter, or an exit with the start time and
+    /// status of the boundary being left.
+    fn trace_boundary(&self, kind: &str, event_idx: usize, name: &str, exit: Option<(Instant, &str)>) {
+        let Some(sink) = &self.trace_sink else {
+            return;
+        };
+        let timestamp_us = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+        let mut record = serde_json::json!({
+            "timestamp_us": timestamp_us,
+            "run_id": *self.run_state.run_id.borrow(),
+            "event_idx": event_idx,
+            "kind": kind,
+            "name": name,
+            "phase": if exit.is_some() { "exit" } else { "enter" },
+        });
+        if let Some((started, status)) = exit {
+            record["duration_us"] = (started.elapsed().as_micros() as u64).into();
+            record["status"] = status.into();
+        }
+        let _ = writeln!(sink.borrow_mut(), "{}", record);
+    }
+
+    // "event[2]:increment", for errors that need to point at a definition entry
+    fn event_label(&self, index: usize) -> String {
+        format!("event[{}]:{}", index, self.event_names[index])
+    }
+
+    /// Undo [`visible`](Self::visible). Without access tracking, an
+    /// immutable context, key policies or validators there are no proxies,
+    /// so the lookup is skipped.
+    fn hidden<'lua>(&self, table: LuaTable<'lua>) -> LuaResult<LuaTable<'lua>> {
+        if self.track_access.get()
+            || self.immutable_context
+            || self.access_tracker.has_validators()
+            || self.event_key_policy.iter().any(Option::is_some)
+        {
+            access::unwrap(table)
+        } else {
+            Ok(table)
+        }
+    }
+
+    /// The table Lua code sees for `context`: a proxy while access tracking
+    /// is on, when `frozen`, under a key policy or with validators installed,
+    /// otherwise the context itself.
+    fn visible<'lua>(
+        &self,
+        lua: &'lua Lua,
+        context: &LuaTable<'lua>,
+        frozen: bool,
+        policy: Option<&KeyPolicy>,
+    ) -> LuaResult<LuaTable<'lua>> {
+        if let Some(aliases) = &self.aliases {
+            context_metatable(lua, context)?.raw_set(ALIASES_FIELD, lua.registry_value::<LuaTable>(aliases)?)?;
+        }
+        if self.track_access.get() || frozen || policy.is_some() || self.access_tracker.has_validators() {
+            self.access_tracker.proxy(lua, context.clone(), frozen, policy)
+        } else {
+            Ok(context.clone())
+        }
+    }
+}
+
+/// Load the chain script at `path` into a fresh Lua VM, run it once and
+/// return the final context.
+pub fn run_chain_file(path: &str) -> Result<ContextMap, ChainError> {
+    let script = std::fs::read_to_string(path)?;
+    let runner = LuaChainRunner::from_script(Rc::new(Lua::new()), &script)?;
+    Ok(runner.execute()?.context)
+}
+
+impl Drop for LuaChainRunner {
+    fn drop(&mut self) {
+        // Errors have nowhere to go from drop, so shutdown is best-effort
+        if let Some(on_shutdown) = &self.on_shutdown
+            && let Ok(on_shutdown) = self.lua.registry_value::<LuaFunction>(on_shutdown)
+        {
+            let _ = on_shutdown.call::<_, ()>(());
+        }
+        if let Some(teardown) = self.teardown.take() {
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(teardown));
+        }
+    }
+}
+
+struct DefinitionParts {
+    meta: ChainMeta,
+    initial_context: LuaRegistryKey,
+    event_names: Vec<String>,
+    event_handlers: Vec<LuaRegistryKey>,
+    event_tags: Vec<Vec<String>>,
+    event_args: Vec<LuaRegistryKey>,
+    event_weights: Vec<f64>,
+    event_patch: Vec<bool>,
+    event_error_handlers: Vec<Option<LuaRegistryKey>>,
+    event_once: Vec<bool>,
+    event_key_policy: Vec<Option<KeyPolicy>>,
+    event_bypass_middleware: Vec<bool>,
+    event_breakers: Vec<Option<CircuitBreaker>>,
+    event_pre: Vec<Option<LuaRegistryKey>>,
+    event_post: Vec<Option<LuaRegistryKey>>,
+    event_pre_skips: Vec<bool>,
+    event_pure: Vec<bool>,
+    middleware_names: Vec<String>,
+    middleware_handlers: Vec<LuaRegistryKey>,
+    middleware_observational: Vec<bool>,
+    middleware_sample_rates: Vec<f64>,
+    middleware_applies_to: Vec<Option<LuaRegistryKey>>,
+    stop_when: Option<LuaRegistryKey>,
+    expect: Option<ContextMap>,
+    finalize: Option<LuaRegistryKey>,
+    on_shutdown: Option<LuaRegistryKey>,
+    execution_mode: ExecutionMode,
+    immutable_context: bool,
+    warnings: Vec<String>,
+}
+
+#[derive(Default)]
+struct HandlerList {
+    names: Vec<String>,
+    handlers: Vec<LuaRegistryKey>,
+    tags: Vec<Vec<String>>,
+    args: Vec<LuaRegistryKey>,
+    weights: Vec<f64>,
+    sample_rates: Vec<f64>,
+    applies_to: Vec<Option<LuaRegistryKey>>,
+    patch: Vec<Option<bool>>,
+    error_handlers: Vec<Option<LuaRegistryKey>>,
+    observational: Vec<bool>,
+    once: Vec<bool>,
+    key_policies: Vec<Option<KeyPolicy>>,
+    bypass_middleware: Vec<bool>,
+    breakers: Vec<Option<CircuitBreaker>>,
+    pre: Vec<Option<LuaRegistryKey>>,
+    post: Vec<Option<LuaRegistryKey>>,
+    pre_skips: Vec<bool>,
+    pure: Vec<bool>,
+    warnings: Vec<String>,
+}
+
+// Per-run state shared with the `chain` helper functions
+#[derive(Default)]
+struct RunState {
+    running: Cell<bool>,
+    abort_requested: Cell<bool>,
+    run_id: RefCell<String>,
+    current_event: Cell<usize>,
+    // Events completed so far in the run, for event-count TTLs
+    events_completed: Cell<usize>,
+    expiry: RefCell<HashMap<String, Expiry>>,
+    warnings: RefCell<Vec<String>>,
+    // A warning still being assembled from `warn` pieces
+    partial_warning: RefCell<String>,
+}
+
+// When a key set with `chain.set_ttl` is removed
+enum Expiry {
+    /// Once this many events of the run have completed
+    AfterEvents(usize),
+    /// After the first event that completes at or past this instant
+    At(Instant),
+}
+
+// Marks a run as in progress; a second run started from inside a handler
+// would overwrite the shared context global, so it is refused instead.
+struct RunningGuard<'a>(&'a RunState);
+
+impl<'a> RunningGuard<'a> {
+    fn enter(state: &'a RunState) -> Result<Self, ChainError> {
+        if state.running.replace(true) {
+            return Err(ChainError::AlreadyRunning);
+        }
+        Ok(RunningGuard(state))
+    }
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.running.set(false);
+    }
+}
+
+// Routes Lua's `warn` into the run state while a run is in progress. Lua
+// hands a multi-argument `warn` over in pieces, and `warn("@on")`-style
+// control messages (which only the default warning function understands)
+// are dropped.
+struct WarningGuard<'a>(&'a Lua);
+
+impl<'a> WarningGuard<'a> {
+    fn install(lua: &'a Lua, state: &Rc<RunState>, strict: bool) -> Self {
+        state.warnings.borrow_mut().clear();
+        state.partial_warning.borrow_mut().clear();
+        let state = state.clone();
+        lua.set_warning_function(move |_, piece, incomplete| {
+            let mut partial = state.partial_warning.borrow_mut();
+            if partial.is_empty() && !incomplete && piece.starts_with('@') {
+                return Ok(());
+            }
+            partial.push_str(piece);
+            if incomplete {
+                return Ok(());
+            }
+            let message = std::mem::take(&mut *partial);
+            if strict {
+                return Err(LuaError::RuntimeError(format!("Lua warning: {}", message)));
+            }
+            state.warnings.borrow_mut().push(message);
+            Ok(())
+        });
+        WarningGuard(lua)
+    }
+}
+
+impl Drop for WarningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.remove_warning_function();
+    }
+}
+
+// Flushes the trace sink however the run ends
+struct FlushGuard<'a>(Option<&'a RefCell<TraceSink>>);
+
+impl Drop for FlushGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.0 {
+            let _ = sink.borrow_mut().flush();
+        }
+    }
+}
+
+pub(crate) fn new_run_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// SplitMix64 step mapped to a float in [0, 1).
+pub(crate) fn next_unit(state: &Cell<u64>) -> f64 {
+    let seed = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+    state.set(seed);
+    let mut z = seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn create_helpers(lua: &Lua) -> LuaResult<(LuaRegistryKey, Rc<RunState>)> {
+    let run_state = Rc::new(RunState::default());
+    let helpers = lua.create_table()?;
+
+    let state = run_state.clone();
+    helpers.set(
+        "abort_chain",
+        lua.create_function(move |_, ()| -> LuaResult<()> {
+            state.abort_requested.set(true);
+            Err(LuaError::RuntimeError("chain aborted".to_string()))
+        })?,
+    )?;
+
+    let state = run_state.clone();
+    helpers.set(
+        "run_id",
+        lua.create_function(move |_, ()| Ok(state.run_id.borrow().clone()))?,
+    )?;
+
+    helpers.set("now", lua.create_function(|_, ()| Ok(LuaTimestamp(SystemTime::now())))?)?;
+    helpers.set("timestamp", lua.create_function(|_, millis: i64| Ok(LuaTimestamp::from_millis(millis)))?)?;
+    #[cfg(feature = "decimal")]
+    helpers.set(
+        "decimal",
+        lua.create_function(|_, value: LuaValue| Ok(crate::value::LuaDecimal(crate::value::decimal_of(&value)?)))?,
+    )?;
+
+    helpers.set(
+        "sleep",
+        lua.create_function(|_, ms: f64| {
+            // Rejects negative, NaN and out-of-range durations
+            let duration = Duration::try_from_secs_f64(ms / 1000.0)
+                .map_err(|_| LuaError::RuntimeError(format!("chain.sleep: invalid duration {}", ms)))?;
+            std::thread::sleep(duration);
+            Ok(())
+        })?,
+    )?;
+
+    let state = run_state.clone();
+    helpers.set(
+        "set_ttl",
+        lua.create_function(
+            move |_, (context, key, value, ttl): (LuaTable, String, LuaValue, LuaValue)| {
+                access::check_policy(&context, &key, "write")?;
+                let expiry = match ttl {
+                    // The event running now completes first, then `events` more
+                    LuaValue::Integer(events) if events > 0 => {
+                        Expiry::AfterEvents(state.events_completed.get().saturating_add(1 + events as usize))
+                    }
+                    LuaValue::Table(ttl) => {
+                        let deadline = ttl
+                            .get::<_, Option<f64>>("ms")?
+                            .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+                            .and_then(|ttl| Instant::now().checked_add(ttl));
+                        match deadline {
+                            Some(deadline) => Expiry::At(deadline),
+                            None => {
+                                return Err(LuaError::RuntimeError(
+                                    "chain.set_ttl: expected { ms = <non-negative number> } within range".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "chain.set_ttl: expected a positive event count or { ms = n }".to_string(),
+                        ));
+                    }
+                };
+                context.set(key.as_str(), value)?;
+                state.expiry.borrow_mut().insert(key, expiry);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    helpers.set(
+        "stream",
+        lua.create_function(|lua, producer: LuaFunction| {
+            let thread = lua.create_registry_value(lua.create_thread(producer)?)?;
+            lua.create_function(move |lua, _: LuaMultiValue| -> LuaResult<LuaValue> {
+                let thread: LuaThread = lua.registry_value(&thread)?;
+                if thread.status() != LuaThreadStatus::Resumable {
+                    return Ok(LuaValue::Nil);
+                }
+                let item: LuaValue = thread.resume(())?;
+                // A finished producer's return values are not items
+                Ok(if thread.status() == LuaThreadStatus::Resumable { item } else { LuaValue::Nil })
+            })
+        })?,
+    )?;
+
+    lua.set_named_registry_value(CONTEXT_INDEX_KEY, lua.create_function(resolve_key)?)?;
+    lua.set_named_registry_value(CONTEXT_NEWINDEX_KEY, lua.create_function(assign_key)?)?;
+    // Binds a producer to the policy proxy it was declared through
+    let bind = lua
+        .load("local producer, ctx = ... return function() return producer(ctx) end")
+        .set_name("=chain.lazy")
+        .into_function()?;
+    let bind = lua.create_registry_value(bind)?;
+    helpers.set(
+        "lazy",
+        lua.create_function(move |lua, (context, key, producer): (LuaTable, LuaValue, LuaFunction)| {
+            if access::is_frozen(&context)? {
+                return Err(LuaError::RuntimeError("chain.lazy: context is immutable".to_string()));
+            }
+            let producer = match access::has_policy(&context)? {
+                true => {
+                    if let LuaValue::String(name) = &key {
+                        access::check_policy(&context, &name.to_string_lossy(), "write")?;
+                    }
+                    lua.registry_value::<LuaFunction>(&bind)?.call((producer, context.clone()))?
+                }
+                false => producer,
+            };
+            let context = access::unwrap(context)?;
+            let producers: LuaTable = context_metatable(lua, &context)?.raw_get(LAZY_FIELD)?;
+            // A stored value would shadow the producer
+            context.raw_set(key.clone(), LuaValue::Nil)?;
+            producers.raw_set(key, producer)
+        })?,
+    )?;
+    helpers.set(
+        "ref",
+        lua.create_function(|lua, (context, key, target): (LuaTable, String, String)| {
+            if access::is_frozen(&context)? {
+                return Err(LuaError::RuntimeError("chain.ref: context is immutable".to_string()));
+            }
+            access::check_policy(&context, &key, "write")?;
+            access::check_policy(&context, &target, "read")?;
+            set_ref(lua, &access::unwrap(context)?, key, target)
+        })?,
+    )?;
+
+    Ok((lua.create_registry_value(helpers)?, run_state))
+}
+
+// The metatable behind lazy keys and aliases, attached to a context table on
+// first use. Its metamethods keep no state of their own, so every runner in
+// a VM shares one pair through the named registry. A context that already
+// has a metatable gets a copy of it, whose `__index` and `__newindex` are
+// kept as fallbacks for keys that are none of the runner's.
+fn context_metatable<'lua>(lua: &'lua Lua, context: &LuaTable<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let metatable = lua.create_table()?;
+    if let Some(existing) = context.get_metatable() {
+        if existing.raw_get::<_, Option<LuaTable>>(LAZY_FIELD)?.is_some() {
+            return Ok(existing);
+        }
+        for pair in existing.pairs::<LuaValue, LuaValue>() {
+            let (field, value) = pair?;
+            metatable.raw_set(field, value)?;
+        }
+        metatable.raw_set(FALLBACK_INDEX_FIELD, metatable.raw_get::<_, LuaValue>("__index")?)?;
+        metatable.raw_set(FALLBACK_NEWINDEX_FIELD, metatable.raw_get::<_, LuaValue>("__newindex")?)?;
+    }
+    metatable.raw_set("__index", lua.named_registry_value::<LuaFunction>(CONTEXT_INDEX_KEY)?)?;
+    metatable.raw_set("__newindex", lua.named_registry_value::<LuaFunction>(CONTEXT_NEWINDEX_KEY)?)?;
+    metatable.raw_set(LAZY_FIELD, lua.create_table()?)?;
+    context.set_metatable(Some(metatable.clone()));
+    Ok(metatable)
+}
+
+// Make `key` a reference to `target`, replacing any value it held
+fn set_ref(lua: &Lua, context: &LuaTable, key: String, target: String) -> LuaResult<()> {
+    let metatable = context_metatable(lua, context)?;
+    let refs = match metatable.raw_get::<_, Option<LuaTable>>(REFS_FIELD)? {
+        Some(refs) => refs,
+        None => {
+            let refs = lua.create_table()?;
+            metatable.raw_set(REFS_FIELD, refs.clone())?;
+            refs
+        }
+    };
+    context.raw_set(key.as_str(), LuaValue::Nil)?;
+    refs.raw_set(key, target)
+}
+
+// Turn the Ref values stored in `context` into references
+fn install_refs(lua: &Lua, context: &LuaTable) -> LuaResult<()> {
+    let mut found = Vec::new();
+    for pair in context.clone().pairs::<LuaValue, LuaValue>() {
+        if let (LuaValue::String(key), LuaValue::UserData(ud)) = pair?
+            && let Ok(target) = ud.borrow::<ContextRef>()
+        {
+            found.push((key.to_str()?.to_string(), target.0.clone()));
+        }
+    }
+    found.into_iter().try_for_each(|(key, target)| set_ref(lua, context, key, target))
+}
+
+/// Store `value` under `key` in a run's context, as a reference if it is a
+/// `Ref`.
+pub(crate) fn store_value(lua: &Lua, context: &LuaTable, key: String, value: ContextValue) -> LuaResult<()> {
+    match value {
+        ContextValue::Ref(target) => set_ref(lua, context, key, target),
+        value => context.set(key, value),
+    }
+}
+
+// The key `key` refers to after following at most MAX_REF_DEPTH refs, or
+// `key` itself if it is not a ref
+fn ref_target<'lua>(lua: &'lua Lua, metatable: &LuaTable<'lua>, key: &LuaValue<'lua>) -> LuaResult<LuaValue<'lua>> {
+    let Some(refs) = metatable.raw_get::<_, Option<LuaTable>>(REFS_FIELD)? else {
+        return Ok(key.clone());
+    };
+    let mut target = key.clone();
+    for _ in 0..MAX_REF_DEPTH {
+        match refs.raw_get::<_, Option<String>>(target.clone())? {
+            Some(next) => target = LuaValue::String(lua.create_string(&next)?),
+            None => return Ok(target),
+        }
+    }
+    match refs.raw_get::<_, Option<String>>(target.clone())? {
+        Some(_) => Err(LuaError::RuntimeError(format!(
+            "context key '{}' is part of a reference cycle (or a chain deeper than {})",
+            key.to_string()?,
+            MAX_REF_DEPTH
+        ))),
+        None => Ok(target),
+    }
+}
+
+// The canonical name behind `key`, if it is an alias
+fn alias_target<'lua>(metatable: &LuaTable<'lua>, key: &LuaValue<'lua>) -> LuaResult<Option<LuaValue<'lua>>> {
+    match metatable.raw_get::<_, Option<LuaTable>>(ALIASES_FIELD)? {
+        Some(aliases) => aliases.raw_get(key.clone()),
+        None => Ok(None),
+    }
+}
+
+// `__index` of a context: follow a reference or an alias, or run the key's
+// lazy producer, if any, and store its result so it only runs once.
+fn resolve_key<'lua>(
+    lua: &'lua Lua,
+    (context, key): (LuaTable<'lua>, LuaValue<'lua>),
+) -> LuaResult<LuaValue<'lua>> {
+    let Some(metatable) = context.get_metatable() else {
+        return Ok(LuaValue::Nil);
+    };
+    let target = ref_target(lua, &metatable, &key)?;
+    if target != key {
+        return context.get(target);
+    }
+    if let Some(target) = alias_target(&metatable, &key)? {
+        return context.get(target);
+    }
+    let producers: LuaTable = metatable.raw_get(LAZY_FIELD)?;
+    let Some(producer) = producers.raw_get::<_, Option<LuaFunction>>(key.clone())? else {
+        return match metatable.raw_get(FALLBACK_INDEX_FIELD)? {
+            LuaValue::Function(index) => index.call((context, key)),
+            LuaValue::Table(fallback) => fallback.get(key),
+            _ => Ok(LuaValue::Nil),
+        };
+    };
+    // Removed before the call, so a producer reading its own key sees nil,
+    // and put back if it fails, so a later read tries again
+    producers.raw_set(key.clone(), LuaValue::Nil)?;
+    let value: LuaValue = match producer.call(context.clone()) {
+        Ok(value) => value,
+        Err(e) => {
+            if producers.raw_get::<_, LuaValue>(key.clone())?.is_nil() {
+                producers.raw_set(key, producer)?;
+            }
+            return Err(e);
+        }
+    };
+    context.raw_set(key, value.clone())?;
+    Ok(value)
+}
+
+// `__newindex` of a context: writes to an alias land on its canonical key,
+// writes to a reference replace it, and other writes go to the context's
+// own `__newindex`, if it had one
+fn assign_key<'lua>(
+    _: &'lua Lua,
+    (context, key, value): (LuaTable<'lua>, LuaValue<'lua>, LuaValue<'lua>),
+) -> LuaResult<()> {
+    let Some(metatable) = context.get_metatable() else {
+        return context.raw_set(key, value);
+    };
+    let was_ref = match metatable.raw_get::<_, Option<LuaTable>>(REFS_FIELD)? {
+        Some(refs) => {
+            let was_ref = !refs.raw_get::<_, LuaValue>(key.clone())?.is_nil();
+            refs.raw_set(key.clone(), LuaValue::Nil)?;
+            was_ref
+        }
+        None => false,
+    };
+    if let Some(target) = alias_target(&metatable, &key)? {
+        return context.set(target, value);
+    }
+    match metatable.raw_get(FALLBACK_NEWINDEX_FIELD)? {
+        _ if was_ref => context.raw_set(key, value),
+        LuaValue::Function(newindex) => newindex.call((context, key, value)),
+        LuaValue::Table(fallback) => fallback.set(key, value),
+        _ => context.raw_set(key, value),
+    }
+}
+
+fn extract_handlers(
+    lua: &Lua,
+    table: LuaTable,
+    kind: &str,
+    allow_native: bool,
+) -> Result<HandlerList, ChainError> {
+    let mut list = HandlerList::default();
+
+    for (index, entry) in table.sequence_values::<LuaTable>().enumerate() {
+        let entry = entry?;
+        let name: String = entry
+            .get::<_, Option<String>>("name")?
+            .ok_or_else(|| ChainError::InvalidDefinition(format!("{}[{}] has no name", kind, index)))?;
+        // Fields that load fine but have no effect
+        let ignored: &[&str] = match (allow_native, entry.contains_key("handler")?) {
+            (true, true) => &["source", "native", "observational", "sample_rate", "applies_to"],
+            (true, false) if entry.contains_key("source")? => {
+                &["native", "observational", "sample_rate", "applies_to"]
+            }
+            (true, false) => &["observational", "sample_rate", "applies_to"],
+            (false, _) => &[
+                "native",
+                "source",
+                "args",
+                "weight",
+                "once",
+                "return_mode",
+                "on_error",
+                "tags",
+                "allowed_keys",
+                "denied_keys",
+                "bypass_middleware",
+                "circuit_breaker",
+                "pure",
+                "pre",
+                "post",
+                "on_pre_failure",
+            ],
+        };
+        for field in ignored {
+            if entry.contains_key(*field)? {
+                list.warnings.push(format!("{}[{}] ({}): `{}` is ignored", kind, index, name, field));
+            }
+        }
+
+        let handler = match entry.get::<_, Option<LuaFunction>>("handler")? {
+            Some(handler) => lua.create_registry_value(handler)?,
+            None => match (
+                entry.get::<_, Option<String>>("source")?,
+                entry.get::<_, Option<String>>("native")?,
+            ) {
+                (Some(source), _) if allow_native => compile_event_source(lua, index, &name, &source)?,
+                (None, Some(native_name)) if allow_native => {
+                    resolve_native(lua, index, &name, &native_name)?
+                }
+                _ => {
+                    return Err(ChainError::InvalidDefinition(format!(
+                        "{}[{}] ({}) has no handler function",
+                        kind, index, name
+                    )));
+                }
+            },
+        };
+
+        let weight = entry.get::<_, Option<f64>>("weight")?.unwrap_or(1.0);
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(ChainError::InvalidDefinition(format!(
+                "{}[{}] ({}) has invalid weight {}",
+                kind, index, name, weight
+            )));
+        }
+        // Events ignore `sample_rate` (with a warning above)
+        let sample_rate = match entry.get::<_, Option<f64>>("sample_rate")? {
+            Some(rate) if !allow_native => rate,
+            _ => 1.0,
+        };
+        if !(0.0..=1.0).contains(&sample_rate) {
+            return Err(ChainError::InvalidDefinition(format!(
+                "{}[{}] ({}) has invalid sample_rate {}; expected 0 to 1",
+                kind, index, name, sample_rate
+            )));
+        }
+        let args = match entry.get::<_, Option<LuaTable>>("args")? {
+            Some(args) => args,
+            None => lua.create_table()?,
+        };
+
+        let allowed = entry.get::<_, Option<Vec<String>>>("allowed_keys")?;
+        let denied = entry.get::<_, Option<Vec<String>>>("denied_keys")?;
+        let policy = match (allowed, denied) {
+            (None, None) => None,
+            (allowed, denied) => Some(KeyPolicy {
+                allowed: allowed.map(|keys| keys.into_iter().collect()),
+                denied: denied.unwrap_or_default().into_iter().collect(),
+            }),
+        };
+        if policy.is_some()
+            && allow_native
+            && entry.contains_key("native")?
+            && !entry.contains_key("handler")?
+            && !entry.contains_key("source")?
+        {
+            return Err(ChainError::InvalidDefinition(format!(
+                "{}[{}] ({}): allowed_keys/denied_keys apply to Lua handlers, not native events",
+                kind, index, name
+            )));
+        }
+
+        let breaker = match entry.get::<_, Option<LuaTable>>("circuit_breaker")? {
+            Some(config) if allow_native => Some(parse_circuit_breaker(index, &name, &config)?),
+            _ => None,
+        };
+
+        list.handlers.push(handler);
+        list.names.push(name);
+        list.tags.push(entry.get::<_, Option<Vec<String>>>("tags")?.unwrap_or_default());
+        list.args.push(lua.create_registry_value(args)?);
+        list.weights.push(weight);
+        list.sample_rates.push(sample_rate);
+        list.applies_to.push(match entry.get::<_, Option<LuaFunction>>("applies_to")? {
+            Some(predicate) if !allow_native => Some(lua.create_registry_value(predicate)?),
+            _ => None,
+        });
+        list.patch.push(parse_return_mode(entry.get("return_mode")?)?);
+        list.once.push(entry.get::<_, Option<bool>>("once")?.unwrap_or(false));
+        list.key_policies.push(if allow_native { policy } else { None });
+        list.bypass_middleware.push(entry.get::<_, Option<bool>>("bypass_middleware")?.unwrap_or(false));
+        list.breakers.push(breaker);
+        for (field, conditions) in [("pre", &mut list.pre), ("post", &mut list.post)] {
+            conditions.push(match entry.get::<_, Option<LuaFunction>>(field)? {
+                Some(condition) if allow_native => Some(lua.create_registry_value(condition)?),
+                _ => None,
+            });
+        }
+        list.pre_skips.push(match entry.get::<_, Option<String>>("on_pre_failure")?.as_deref() {
+            None | Some("error") => false,
+            Some("skip") => true,
+            Some(other) => {
+                return Err(ChainError::InvalidDefinition(format!(
+                    "{}[{}] ({}) has unknown on_pre_failure '{}'",
+                    kind, index, list.names[index], other
+                )));
+            }
+        });
+        list.pure.push(allow_native && entry.get::<_, Option<bool>>("pure")?.unwrap_or(false));
+        list.observational.push(entry.get::<_, Option<bool>>("observational")?.unwrap_or(false));
+        list.error_handlers.push(
+            entry
+                .get::<_, Option<LuaFunction>>("on_error")?
+                .map(|f| lua.create_registry_value(f))
+                .transpose()?,
+        );
+    }
+
+    Ok(list)
+}
+
+fn parse_circuit_breaker(index: usize, name: &str, config: &LuaTable) -> Result<CircuitBreaker, ChainError> {
+    let threshold = config.get::<_, Option<u32>>("threshold")?.unwrap_or(0);
+    let cooldown_ms = config.get::<_, Option<f64>>("cooldown_ms")?.unwrap_or(0.0);
+    let cooldown = Duration::try_from_secs_f64(cooldown_ms / 1000.0);
+    match (threshold, cooldown) {
+        (1.., Ok(cooldown)) => Ok(CircuitBreaker::new(threshold, cooldown)),
+        _ => Err(ChainError::InvalidDefinition(format!(
+            "event[{}] ({}): circuit_breaker needs a threshold of at least 1 and a cooldown_ms of 0 or more",
+            index, name
+        ))),
+    }
+}
+
+// `Some(true)` for "patch", `Some(false)` for "replace", `None` if unset
+fn parse_return_mode(mode: Option<String>) -> Result<Option<bool>, ChainError> {
+    match mode.as_deref() {
+        None => Ok(None),
+        Some("replace") => Ok(Some(false)),
+        Some("patch") => Ok(Some(true)),
+        Some(other) => Err(ChainError::InvalidDefinition(format!("unknown return_mode '{}'", other))),
+    }
+}
+
+// Compile a handler body `source` as `function(ctx, args) <source> end`,
+// reporting errors under `chunk_name`
+fn compile_source<'lua>(lua: &'lua Lua, chunk_name: &str, source: &str) -> LuaResult<LuaFunction<'lua>> {
+    lua.load(format!("return function(ctx, args) {}\nend", source))
+        .set_name(format!("={}", chunk_name))
+        .set_mode(ChunkMode::Text)
+        .call(())
+}
+
+fn compile_event_source(
+    lua: &Lua,
+    index: usize,
+    name: &str,
+    source: &str,
+) -> Result<LuaRegistryKey, ChainError> {
+    let handler = compile_source(lua, &format!("event[{}]:{}", index, name), source).map_err(|e| {
+        ChainError::InvalidDefinition(format!(
+            "event[{}] ({}) has invalid source: {}",
+            index, name, e
+        ))
+    })?;
+    Ok(lua.create_registry_value(handler)?)
+}
+
+fn resolve_native(
+    lua: &Lua,
+    index: usize,
+    name: &str,
+    native_name: &str,
+) -> Result<LuaRegistryKey, ChainError> {
+    let event = native::lookup(lua, native_name).ok_or_else(|| {
+        ChainError::InvalidDefinition(format!(
+            "event[{}] ({}) refers to unknown native event '{}'",
+            index, name, native_name
+        ))
+    })?;
+    Ok(lua.create_registry_value(native::bridge(lua, event)?)?)
+}
+
+/// Deep-copy a context table so runs never share mutable state with the
+/// definition. A table reached more than once, through a cycle or not, is
+/// copied once, so the copy has the original's shape.
+fn copy_table<'lua>(lua: &'lua Lua, table: &LuaTable<'lua>) -> LuaResult<LuaTable<'lua>> {
+    copy_table_with(lua, table, &mut HashMap::new())
+}
+
+// `copies` maps each table copied so far to its copy
+fn copy_table_with<'lua>(
+    lua: &'lua Lua,
+    table: &LuaTable<'lua>,
+    copies: &mut HashMap<*const c_void, LuaTable<'lua>>,
+) -> LuaResult<LuaTable<'lua>> {
+    if let Some(copy) = copies.get(&table.to_pointer()) {
+        return Ok(copy.clone());
+    }
+    let copy = lua.create_table()?;
+    copies.insert(table.to_pointer(), copy.clone());
+    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        let value = match value {
+            LuaValue::Table(nested) => LuaValue::Table(copy_table_with(lua, &nested, copies)?),
+            other => other,
+        };
+        copy.raw_set(key, value)?;
+    }
+    Ok(copy)
+}
+
+/// A copy of `context` to roll a failed event back to. The context's
+/// metatable is copied too, with its own lazy producer, alias and
+/// reference tables, so keys the failed event resolved or re-pointed
+/// come back as they were.
+fn snapshot_context<'lua>(lua: &'lua Lua, context: &LuaTable<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let snapshot = copy_table(lua, context)?;
+    let Some(metatable) = context.get_metatable() else {
+        return Ok(snapshot);
+    };
+    let metatable_copy = lua.create_table()?;
+    for pair in metatable.pairs::<LuaValue, LuaValue>() {
+        let (field, value) = pair?;
+        let value = match (&field, value) {
+            (LuaValue::String(name), LuaValue::Table(table))
+                if [LAZY_FIELD, ALIASES_FIELD, REFS_FIELD].contains(&name.to_str()?) =>
+            {
+                let table_copy = lua.create_table()?;
+                for entry in table.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = entry?;
+                    table_copy.raw_set(key, value)?;
+                }
+                LuaValue::Table(table_copy)
+            }
+            (_, value) => value,
+        };
+        metatable_copy.raw_set(field, value)?;
+    }
+    snapshot.set_metatable(Some(metatable_copy));
+    Ok(snapshot)
+}
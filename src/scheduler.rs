@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::error::ChainError;
+use crate::runner::{new_run_id, ChainOutcome, LuaChainRunner, OutcomeStatus};
+use crate::value::ContextMap;
+
+// ============================================================================
+// ROUND-ROBIN SCHEDULING
+// ============================================================================
+// A ChainScheduler interleaves several runners on one thread: each tick runs
+// the next event of every chain that has not finished, in the order the
+// chains were added, so two chains of three events run a0 b0 a1 b1 a2 b2.
+// A chain finishes when its last event has run, when an event fails or when
+// Lua code calls `chain.abort_chain()`; the others keep going.
+//
+// Each event runs through the same path as a LuaEvent on the native executor
+// (see adapter.rs): its handler, args, on_error, return mode and middleware
+// stack apply, against an owned copy of the chain's context that is carried
+// to its next event. Anything that spans a run does not: finalize, cleanup
+// tags, once, TTLs, stop_when, checkpoints, stats and the execution mode
+// (events always run in declaration order). Values with no owned form do not
+// survive between events.
+
+/// Steps several runners one event at a time, round-robin.
+#[derive(Default)]
+pub struct ChainScheduler {
+    chains: Vec<Scheduled>,
+}
+
+struct Scheduled {
+    runner: LuaChainRunner,
+    run_id: String,
+    // `None` until the chain's first event runs
+    context: Option<ContextMap>,
+    next_event: usize,
+    duration: Duration,
+    result: Option<Result<ChainOutcome, ChainError>>,
+}
+
+impl ChainScheduler {
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a chain; it starts from its definition's context and defaults.
+    pub fn chain(mut self, runner: LuaChainRunner) -> Self {
+        self.chains.push(Scheduled {
+            runner,
+            run_id: new_run_id(),
+            context: None,
+            next_event: 0,
+            duration: Duration::ZERO,
+            result: None,
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize { self.chains.len() }
+
+    pub fn is_empty(&self) -> bool { self.chains.is_empty() }
+
+    /// Run the next event of every unfinished chain, returning the
+    /// `(chain, event)` indices that ran, in order. Empty once every chain
+    /// has finished.
+    pub fn tick(&mut self) -> Vec<(usize, usize)> {
+        let mut ran = Vec::new();
+        for (chain_index, chain) in self.chains.iter_mut().enumerate() {
+            if chain.result.is_some() {
+                continue;
+            }
+            if let Some(event) = chain.step() {
+                ran.push((chain_index, event));
+            }
+        }
+        ran
+    }
+
+    /// Whether every chain has finished.
+    pub fn is_finished(&self) -> bool { self.chains.iter().all(|chain| chain.result.is_some()) }
+
+    /// Tick until every chain has finished and return their outcomes, in
+    /// the order the chains were added.
+    pub fn run(mut self) -> Vec<Result<ChainOutcome, ChainError>> {
+        while !self.is_finished() {
+            self.tick();
+        }
+        self.chains.into_iter().filter_map(|chain| chain.result).collect()
+    }
+}
+
+impl Scheduled {
+    // Run the next event, or finish the chain if there is none. Returns the
+    // index of the event that ran.
+    fn step(&mut self) -> Option<usize> {
+        let context = match self.context.take() {
+            Some(context) => context,
+            None => match self.runner.initial_map() {
+                Ok(context) => context,
+                Err(e) => {
+                    self.result = Some(Err(e.into()));
+                    return None;
+                }
+            },
+        };
+        let index = self.next_event;
+        if index == self.runner.event_names().len() {
+            self.finish(OutcomeStatus::Completed, context);
+            return None;
+        }
+
+        let start = Instant::now();
+        let result = self.runner.execute_event_map(index, &context, &self.run_id);
+        self.duration += start.elapsed();
+        // Checked before the result: an abort unwinds as a Lua error, and
+        // the chain keeps the context the aborted event started from
+        if self.runner.abort_requested() {
+            self.finish(OutcomeStatus::Aborted, context);
+            return Some(index);
+        }
+        match result {
+            Ok(updated) => {
+                self.next_event += 1;
+                if self.next_event == self.runner.event_names().len() {
+                    self.finish(OutcomeStatus::Completed, updated);
+                } else {
+                    self.context = Some(updated);
+                }
+            }
+            Err(ChainError::Lua(e)) => self.result = Some(Err(self.runner.event_error(index, &self.run_id, e))),
+            Err(e) => self.result = Some(Err(e)),
+        }
+        Some(index)
+    }
+
+    fn finish(&mut self, status: OutcomeStatus, context: ContextMap) {
+        self.result = Some(Ok(ChainOutcome {
+            run_id: self.run_id.clone(),
+            status,
+            context,
+            events_executed: self.next_event,
+            duration: self.duration,
+            finished_at: SystemTime::now(),
+            middleware_trace: None,
+            access_report: None,
+            gc: None,
+            boundary: None,
+            no_change_warnings: None,
+            lua_warnings: None,
+        }));
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+// ============================================================================
+// EXECUTION STATISTICS
+// ============================================================================
+// Accumulated by a runner across executions when built with
+// `with_stats(true)`. Only events that return count: runs that abort skip
+// the aborting event, and cleanup events run after a failure are not timed.
+//
+// GcStats and GcCollection describe Lua heap use for a single run or a forced
+// collection, to separate garbage-collection cost from handler cost.
+//
+// BoundaryStats counts the runner's own crossings into Lua (globals touched,
+// Lua functions called, Rust functions created for Lua) when built with
+// `with_boundary_stats(true)`. Calls Lua code makes back into Rust, such as
+// `chain.*` helpers or a `lazy` producer, are not counted.
+//
+// `ChainStats::to_prometheus` (or `LuaChainRunner::metrics_prometheus`)
+// renders the accumulated stats in the Prometheus text exposition format:
+// `lua_chains_runs_total`, `lua_chains_event_calls_total` by `status`
+// ("success" or "failure"), and `lua_chains_event_duration_seconds`, a
+// histogram over DURATION_BUCKETS. Event series are labelled with the
+// event's `index` and `name`, so events that share a name stay apart, and
+// every series with the chain's name as `chain` when it has one. Counters
+// restart from zero after `reset_stats`, which Prometheus treats as a
+// counter reset.
+
+/// Upper bounds, in seconds, of the event duration histogram buckets.
+pub const DURATION_BUCKETS: [f64; 12] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChainStats {
+    /// Executions started since the last reset
+    pub runs: u64,
+    /// One entry per event, in declaration order
+    pub events: Vec<EventStats>,
+}
+
+impl ChainStats {
+    pub(crate) fn new(event_names: &[String]) -> Self {
+        Self {
+            runs: 0,
+            events: event_names.iter().map(|name| EventStats::new(name)).collect(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, index: usize, duration: Duration, succeeded: bool) {
+        if let Some(event) = self.events.get_mut(index) {
+            event.record(duration, succeeded);
+        }
+    }
+
+    /// These stats in the Prometheus text format; see the module comment.
+    pub fn to_prometheus(&self, chain: Option<&str>) -> String {
+        let chain_label = chain.map(|chain| format!("chain=\"{}\"", escape_label(chain)));
+        let labels = |extra: &[String]| {
+            let all: Vec<&str> = chain_label.iter().chain(extra).map(String::as_str).collect();
+            if all.is_empty() { String::new() } else { format!("{{{}}}", all.join(",")) }
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP lua_chains_runs_total Executions started since the stats were last reset.");
+        let _ = writeln!(out, "# TYPE lua_chains_runs_total counter");
+        let _ = writeln!(out, "lua_chains_runs_total{} {}", labels(&[]), self.runs);
+
+        let _ = writeln!(out, "# HELP lua_chains_event_calls_total Event calls that returned, by status.");
+        let _ = writeln!(out, "# TYPE lua_chains_event_calls_total counter");
+        for (index, event) in self.events.iter().enumerate() {
+            for (status, count) in [("success", event.successes), ("failure", event.failures)] {
+                let series = labels(&[event_labels(index, event), format!("status=\"{status}\"")]);
+                let _ = writeln!(out, "lua_chains_event_calls_total{series} {count}");
+            }
+        }
+
+        let _ = writeln!(out, "# HELP lua_chains_event_duration_seconds Time spent in each event call.");
+        let _ = writeln!(out, "# TYPE lua_chains_event_duration_seconds histogram");
+        for (index, event) in self.events.iter().enumerate() {
+            let event_label = event_labels(index, event);
+            let mut cumulative = 0;
+            for (bound, count) in DURATION_BUCKETS.iter().zip(event.bucket_counts()) {
+                cumulative += count;
+                let series = labels(&[event_label.clone(), format!("le=\"{bound}\"")]);
+                let _ = writeln!(out, "lua_chains_event_duration_seconds_bucket{series} {cumulative}");
+            }
+            let series = labels(&[event_label.clone(), "le=\"+Inf\"".to_string()]);
+            let _ = writeln!(out, "lua_chains_event_duration_seconds_bucket{series} {}", event.calls());
+            let series = labels(&[event_label]);
+            let _ = writeln!(out, "lua_chains_event_duration_seconds_sum{series} {}", event.total.as_secs_f64());
+            let _ = writeln!(out, "lua_chains_event_duration_seconds_count{series} {}", event.calls());
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventStats {
+    pub name: String,
+    pub successes: u64,
+    pub failures: u64,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    pub total: Duration,
+    /// Calls per DURATION_BUCKETS bucket: entry `i` counts the calls longer
+    /// than bound `i - 1` and no longer than bound `i`. Slower calls are in
+    /// no bucket.
+    pub buckets: Vec<u64>,
+}
+
+impl EventStats {
+    fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Self::default() }
+    }
+
+    fn record(&mut self, duration: Duration, succeeded: bool) {
+        if succeeded {
+            self.successes += 1;
+        } else {
+            self.failures += 1;
+        }
+        self.min = Some(self.min.map_or(duration, |min| min.min(duration)));
+        self.max = Some(self.max.map_or(duration, |max| max.max(duration)));
+        self.total += duration;
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            if self.buckets.len() < DURATION_BUCKETS.len() {
+                self.buckets.resize(DURATION_BUCKETS.len(), 0);
+            }
+            self.buckets[bucket] += 1;
+        }
+    }
+
+    // One count per bucket, also for stats built without any
+    fn bucket_counts(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..DURATION_BUCKETS.len()).map(|i| self.buckets.get(i).copied().unwrap_or(0))
+    }
+
+    pub fn calls(&self) -> u64 { self.successes + self.failures }
+
+    /// Mean duration over every call, successful or not.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.calls() {
+            0 => None,
+            calls => Some(self.total.div_f64(calls as f64)),
+        }
+    }
+}
+
+fn event_labels(index: usize, event: &EventStats) -> String {
+    format!("index=\"{index}\",name=\"{}\"", escape_label(&event.name))
+}
+
+// Label values escape backslashes, double quotes and newlines
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Lua heap usage around one run. Garbage collection is incremental, so a
+/// run may pay for (or leave behind) garbage from other runs; call
+/// `LuaChainRunner::collect_garbage` between runs for comparable numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    /// Bytes in use by the Lua VM when the run started
+    pub memory_before: usize,
+    /// Bytes in use by the Lua VM when the run finished
+    pub memory_after: usize,
+}
+
+impl GcStats {
+    /// Heap growth over the run; negative when a collection during the
+    /// run freed more than the run allocated.
+    pub fn net_growth(&self) -> isize {
+        self.memory_after as isize - self.memory_before as isize
+    }
+}
+
+/// Result of a forced full collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcCollection {
+    pub reclaimed_bytes: usize,
+    pub duration: Duration,
+}
+
+/// Rust/Lua boundary crossings made by a runner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BoundaryStats {
+    /// `globals().get` calls
+    pub global_reads: u64,
+    /// `globals().set` calls
+    pub global_writes: u64,
+    /// Lua functions called from Rust: handlers, middleware, `on_error`
+    /// and `finalize`
+    pub lua_calls: u64,
+    /// Rust functions created for Lua, one `next` per middleware layer
+    pub functions_created: u64,
+}
+
+impl BoundaryStats {
+    pub fn total(&self) -> u64 {
+        self.global_reads + self.global_writes + self.lua_calls + self.functions_created
+    }
+
+    /// The crossings made since `earlier` was taken.
+    pub(crate) fn since(&self, earlier: &Self) -> Self {
+        Self {
+            global_reads: self.global_reads - earlier.global_reads,
+            global_writes: self.global_writes - earlier.global_writes,
+            lua_calls: self.lua_calls - earlier.lua_calls,
+            functions_created: self.functions_created - earlier.functions_created,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use crate::error::ChainError;
+use crate::value::{resolve_ref, ContextMap, ContextValue};
+
+// ============================================================================
+// CONTEXT STORES
+// ============================================================================
+// EventContext lives in the event_chains crate and owns its HashMap, so its
+// storage cannot be swapped out. ContextStore is the seam on this side: a
+// runner can load a run's context from any store and write the final
+// context back (`LuaChainRunner::execute_with_store`), so a chain can keep
+// its state in e.g. Redis or a database between runs.
+//
+// Stores deal in owned ContextValues; the typed `get`/`set` helpers convert
+// on the way in and out. A store is read once before the run and written
+// once after it, never during, so a slow backend costs two round trips
+// per run rather than one per access.
+
+/// Storage for context values, keyed by name.
+pub trait ContextStore {
+    fn get_raw(&self, key: &str) -> Option<ContextValue>;
+
+    fn set_raw(&mut self, key: &str, value: ContextValue);
+
+    /// Every key currently stored, in the store's own order.
+    fn keys(&self) -> Vec<String>;
+
+    fn remove(&mut self, key: &str) -> Option<ContextValue>;
+
+    /// The value of `key` with refs followed; see `ContextValue::Ref`.
+    fn resolve(&self, key: &str) -> Result<Option<ContextValue>, ChainError> {
+        resolve_ref(|key| self.get_raw(key), key)
+    }
+
+    /// Read `key` as a `T`, following refs, or `None` if it is missing,
+    /// holds another type or is part of a reference cycle.
+    fn get<T: TryFrom<ContextValue>>(&self, key: &str) -> Option<T>
+    where
+        Self: Sized,
+    {
+        self.resolve(key).ok()??.try_into().ok()
+    }
+
+    fn set(&mut self, key: &str, value: impl Into<ContextValue>)
+    where
+        Self: Sized,
+    {
+        self.set_raw(key, value.into());
+    }
+}
+
+/// The in-memory default.
+impl ContextStore for ContextMap {
+    fn get_raw(&self, key: &str) -> Option<ContextValue> {
+        self.get(key).cloned()
+    }
+
+    fn set_raw(&mut self, key: &str, value: ContextValue) {
+        self.insert(key.to_string(), value);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+
+    fn remove(&mut self, key: &str) -> Option<ContextValue> {
+        self.remove(key)
+    }
+}
+
+/// Keeps keys sorted, e.g. for stable output.
+impl ContextStore for BTreeMap<String, ContextValue> {
+    fn get_raw(&self, key: &str) -> Option<ContextValue> {
+        self.get(key).cloned()
+    }
+
+    fn set_raw(&mut self, key: &str, value: ContextValue) {
+        self.insert(key.to_string(), value);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+
+    fn remove(&mut self, key: &str) -> Option<ContextValue> {
+        self.remove(key)
+    }
+}
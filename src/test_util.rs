@@ -0,0 +1,40 @@
+use crate::runner::ChainOutcome;
+use crate::value::ContextValue;
+pub use crate::verify::ContextMismatch;
+use crate::verify::mismatch;
+
+// ============================================================================
+// TEST SUPPORT (feature = "test-util")
+// ============================================================================
+//
+//   assert_context!(outcome, "counter" => 1, "message" => "start -> processed");
+//
+//   outcome.assert_eq("counter", 1)?.assert_eq("message", "start -> processed")?;
+
+impl ChainOutcome {
+    /// Check that the final context holds `expected` under `key`.
+    pub fn assert_eq(
+        &self,
+        key: &str,
+        expected: impl Into<ContextValue>,
+    ) -> Result<&Self, ContextMismatch> {
+        match mismatch(&self.context, key, expected.into()) {
+            Some(mismatch) => Err(mismatch),
+            None => Ok(self),
+        }
+    }
+}
+
+/// Assert several final-context values at once, panicking on the first
+/// mismatch.
+#[macro_export]
+macro_rules! assert_context {
+    ($outcome:expr, $($key:expr => $value:expr),+ $(,)?) => {{
+        let outcome: &$crate::ChainOutcome = &$outcome;
+        $(
+            if let Err(mismatch) = outcome.assert_eq($key, $value) {
+                panic!("{}", mismatch);
+            }
+        )+
+    }};
+}
@@ -0,0 +1,55 @@
+use serde::Serialize;
+
+use crate::runner::ExecutionMode;
+
+// ============================================================================
+// CHAIN TOPOLOGY
+// ============================================================================
+// `LuaChainRunner::topology` describes how a run is put together, as plain
+// data that serializes to JSON for visualization tools:
+//
+//   { "chain": "demo", "execution_mode": "sequential",
+//     "observers": ["log"],
+//     "middleware": [
+//       { "name": "timing", "declared": 2, "depth": 0, "wraps": "auth" },
+//       { "name": "auth", "declared": 0, "depth": 1, "wraps": null } ],
+//     "events": [
+//       { "name": "increment", "order": 0, "tags": [], "bypass_middleware": false },
+//       { "name": "append", "order": 1, "tags": [], "bypass_middleware": false } ] }
+//
+// Events run in `order` (FIFO, in sequential mode). Nesting middleware is
+// listed outermost first: the last declared is entered first (LIFO), and
+// each layer's `wraps` names the layer inside it, `null` meaning the event
+// itself. Observers are called in the order listed before every event and
+// wrap nothing. Events with `bypass_middleware` skip both. The topology
+// reflects the runner when it is taken, including later event edits.
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Topology {
+    pub chain: Option<String>,
+    pub execution_mode: ExecutionMode,
+    /// Observational middleware, in call order
+    pub observers: Vec<String>,
+    /// Nesting middleware, outermost first
+    pub middleware: Vec<MiddlewareNode>,
+    pub events: Vec<EventNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MiddlewareNode {
+    pub name: String,
+    /// Position in the definition's `middleware` list
+    pub declared: usize,
+    /// 0 for the outermost layer
+    pub depth: usize,
+    /// The next layer in, or `None` for the event
+    pub wraps: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventNode {
+    pub name: String,
+    pub order: usize,
+    pub tags: Vec<String>,
+    pub bypass_middleware: bool,
+}
@@ -0,0 +1,815 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use event_chains::EventContext;
+use mlua::prelude::*;
+use serde::de::value::MapDeserializer;
+use serde::de::{DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+use crate::error::ChainError;
+
+// ============================================================================
+// OWNED CONTEXT VALUES
+// ============================================================================
+// Lua tables borrow the VM they came from; ContextValue is the owned form
+// handed back to Rust callers once a run has finished.
+//
+// Nil means absent: Lua tables cannot hold nil, so a handler that sets
+// `ctx.key = nil` removes the key, and it does not appear in the owned map.
+// There is no ContextValue for nil for the same reason.
+//
+// Lua strings are byte arrays: those that are valid UTF-8 become `Str`, any
+// other become `Bytes`, so binary payloads survive a round trip unchanged.
+// Lua cannot tell the two apart, so `Bytes` that happen to be valid UTF-8
+// come back as `Str`; reading a value as `Vec<u8>` (`TryFrom`,
+// `ContextStore::get`, `EventContextExt::view`) accepts either.
+// `Bytes` serializes as a base64 string and is never produced by
+// deserialization (a base64 string reads back as `Str`).
+//
+// Timestamps cross into Lua as a userdata (see `LuaTimestamp`) so they keep
+// their meaning instead of decaying into a number. They compare with each
+// other and with numbers of milliseconds since the Unix epoch, and
+// serialize as RFC 3339 in UTC with millisecond precision; like `Bytes`,
+// deserialization never produces one.
+//
+// With the `decimal` feature, `Decimal` holds an exact fixed-point number
+// (rust_decimal) for amounts that f64 would round. It crosses into Lua as a
+// `LuaDecimal` userdata, made from a string with `chain.decimal("0.1")`, and
+// supports arithmetic and ordering with other decimals, integers and
+// decimal strings; floats are refused rather than silently rounded.
+// Decimals serialize as strings and, like `Bytes`, are never deserialized.
+//
+// A `Stream` is a Rust iterator handed to Lua as a callable userdata, so an
+// event consumes it with `for item in ctx.items do ... end`. Streams are
+// single-pass: every clone (and every Lua reference) shares one position,
+// and an exhausted stream stays empty. They have no serialized form.
+// Streams produced in Lua with `chain.stream` wrap a coroutine and stay in
+// the Lua context like functions do; only Rust-built streams are owned.
+//
+// A `Ref` makes one key stand for another: reading the key gives the
+// referenced key's current value, so the two never drift apart. Refs may
+// point at refs; a chain longer than MAX_REF_DEPTH is treated as a cycle and
+// resolving it is an error. Writes overwrite: assigning to a ref key replaces
+// the reference with the value and leaves the referenced key alone. Refs
+// serialize as `{"$ref": "<key>"}` and are never deserialized.
+//
+// Other userdata, functions and nested tables have no owned form. They stay in
+// the Lua context for the whole run, passed by reference from event to
+// event, but snapshots built here leave them out without an error.
+
+/// Owned snapshot of a context table.
+pub type ContextMap = HashMap<String, ContextValue>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ContextValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    #[serde(serialize_with = "serialize_base64", skip_deserializing)]
+    Bytes(Vec<u8>),
+    #[serde(serialize_with = "serialize_rfc3339", skip_deserializing)]
+    Timestamp(SystemTime),
+    #[cfg(feature = "decimal")]
+    #[serde(serialize_with = "serialize_decimal", skip_deserializing)]
+    Decimal(Decimal),
+    #[serde(skip)]
+    Stream(ContextStream),
+    #[serde(serialize_with = "serialize_ref", skip_deserializing)]
+    Ref(String),
+}
+
+// Where a run's context keeps its references (see runner.rs)
+pub(crate) const REFS_FIELD: &str = "__refs";
+
+/// Most references followed to resolve one key before giving up on it as a
+/// cycle.
+pub const MAX_REF_DEPTH: usize = 16;
+
+/// A `ContextValue::Ref` as stored in a native context or seen by Lua.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextRef(pub String);
+
+impl LuaUserData for ContextRef {}
+
+/// The value of `key`, following refs through `lookup`. Missing keys (at
+/// either end) resolve to `None`.
+pub fn resolve_ref(
+    lookup: impl Fn(&str) -> Option<ContextValue>,
+    key: &str,
+) -> Result<Option<ContextValue>, ChainError> {
+    let mut value = lookup(key);
+    for _ in 0..MAX_REF_DEPTH {
+        match value {
+            Some(ContextValue::Ref(target)) => value = lookup(&target),
+            other => return Ok(other),
+        }
+    }
+    match value {
+        Some(ContextValue::Ref(_)) => Err(ChainError::RefCycle(key.to_string())),
+        other => Ok(other),
+    }
+}
+
+impl ContextValue {
+    /// Convert a Lua value, returning `None` for types with no owned form
+    /// (functions, tables, userdata, nil).
+    pub fn from_lua(value: &LuaValue) -> Option<Self> {
+        match value {
+            LuaValue::Integer(i) => Some(ContextValue::Int(*i)),
+            LuaValue::Number(n) => Some(ContextValue::Float(*n)),
+            LuaValue::String(s) => Some(match s.to_str() {
+                Ok(text) => ContextValue::Str(text.to_string()),
+                Err(_) => ContextValue::Bytes(s.as_bytes().to_vec()),
+            }),
+            LuaValue::Boolean(b) => Some(ContextValue::Bool(*b)),
+            LuaValue::UserData(ud) => {
+                if let Ok(ts) = ud.borrow::<LuaTimestamp>() {
+                    return Some(ContextValue::Timestamp(ts.0));
+                }
+                #[cfg(feature = "decimal")]
+                if let Ok(decimal) = ud.borrow::<LuaDecimal>() {
+                    return Some(ContextValue::Decimal(decimal.0));
+                }
+                if let Ok(target) = ud.borrow::<ContextRef>() {
+                    Some(ContextValue::Ref(target.0.clone()))
+                } else {
+                    ud.borrow::<ContextStream>().ok().map(|stream| ContextValue::Stream(stream.clone()))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Read `key` from a native context, trying each supported type.
+    pub fn read(context: &EventContext, key: &str) -> Option<Self> {
+        if let Some(i) = context.get::<i64>(key) {
+            Some(ContextValue::Int(i))
+        } else if let Some(n) = context.get::<f64>(key) {
+            Some(ContextValue::Float(n))
+        } else if let Some(s) = context.get::<String>(key) {
+            Some(ContextValue::Str(s))
+        } else if let Some(bytes) = context.get::<Vec<u8>>(key) {
+            Some(ContextValue::Bytes(bytes))
+        } else if let Some(time) = context.get::<SystemTime>(key) {
+            Some(ContextValue::Timestamp(time))
+        } else if let Some(decimal) = read_decimal(context, key) {
+            Some(decimal)
+        } else if let Some(stream) = context.get::<ContextStream>(key) {
+            Some(ContextValue::Stream(stream))
+        } else if let Some(ContextRef(target)) = context.get::<ContextRef>(key) {
+            Some(ContextValue::Ref(target))
+        } else {
+            context.get::<bool>(key).map(ContextValue::Bool)
+        }
+    }
+
+    /// The Rust type the value is stored as in a native context.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ContextValue::Int(_) => "i64",
+            ContextValue::Float(_) => "f64",
+            ContextValue::Str(_) => "String",
+            ContextValue::Bool(_) => "bool",
+            ContextValue::Bytes(_) => "Vec<u8>",
+            ContextValue::Timestamp(_) => "SystemTime",
+            #[cfg(feature = "decimal")]
+            ContextValue::Decimal(_) => "Decimal",
+            ContextValue::Stream(_) => "ContextStream",
+            ContextValue::Ref(_) => "ContextRef",
+        }
+    }
+
+    /// Store the value in a native context under its natural Rust type.
+    pub fn write(self, context: &mut EventContext, key: &str) {
+        match self {
+            ContextValue::Int(i) => context.set(key, i),
+            ContextValue::Float(n) => context.set(key, n),
+            ContextValue::Str(s) => context.set(key, s),
+            ContextValue::Bool(b) => context.set(key, b),
+            ContextValue::Bytes(bytes) => context.set(key, bytes),
+            ContextValue::Timestamp(time) => context.set(key, time),
+            #[cfg(feature = "decimal")]
+            ContextValue::Decimal(decimal) => context.set(key, decimal),
+            ContextValue::Stream(stream) => context.set(key, stream),
+            ContextValue::Ref(target) => context.set(key, ContextRef(target)),
+        }
+    }
+}
+
+impl From<i64> for ContextValue {
+    fn from(i: i64) -> Self { ContextValue::Int(i) }
+}
+
+impl From<i32> for ContextValue {
+    fn from(i: i32) -> Self { ContextValue::Int(i.into()) }
+}
+
+impl From<u32> for ContextValue {
+    fn from(i: u32) -> Self { ContextValue::Int(i.into()) }
+}
+
+/// Saturates at `i64::MAX`.
+impl From<usize> for ContextValue {
+    fn from(i: usize) -> Self { ContextValue::Int(i64::try_from(i).unwrap_or(i64::MAX)) }
+}
+
+impl From<f32> for ContextValue {
+    fn from(n: f32) -> Self { ContextValue::Float(n.into()) }
+}
+
+impl From<f64> for ContextValue {
+    fn from(n: f64) -> Self { ContextValue::Float(n) }
+}
+
+impl From<bool> for ContextValue {
+    fn from(b: bool) -> Self { ContextValue::Bool(b) }
+}
+
+impl From<String> for ContextValue {
+    fn from(s: String) -> Self { ContextValue::Str(s) }
+}
+
+impl From<&str> for ContextValue {
+    fn from(s: &str) -> Self { ContextValue::Str(s.to_string()) }
+}
+
+impl From<Vec<u8>> for ContextValue {
+    fn from(bytes: Vec<u8>) -> Self { ContextValue::Bytes(bytes) }
+}
+
+impl From<&[u8]> for ContextValue {
+    fn from(bytes: &[u8]) -> Self { ContextValue::Bytes(bytes.to_vec()) }
+}
+
+impl From<SystemTime> for ContextValue {
+    fn from(time: SystemTime) -> Self { ContextValue::Timestamp(time) }
+}
+
+#[cfg(feature = "decimal")]
+impl From<Decimal> for ContextValue {
+    fn from(decimal: Decimal) -> Self { ContextValue::Decimal(decimal) }
+}
+
+impl From<ContextStream> for ContextValue {
+    fn from(stream: ContextStream) -> Self { ContextValue::Stream(stream) }
+}
+
+impl fmt::Display for ContextValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextValue::Int(i) => write!(f, "{}", i),
+            ContextValue::Float(n) => write!(f, "{}", n),
+            ContextValue::Str(s) => write!(f, "{}", s),
+            ContextValue::Bool(b) => write!(f, "{}", b),
+            ContextValue::Bytes(bytes) => write!(f, "<{} bytes>", bytes.len()),
+            ContextValue::Timestamp(time) => f.write_str(&rfc3339(*time)),
+            #[cfg(feature = "decimal")]
+            ContextValue::Decimal(decimal) => write!(f, "{}", decimal),
+            ContextValue::Stream(_) => f.write_str("<stream>"),
+            ContextValue::Ref(target) => write!(f, "-> {}", target),
+        }
+    }
+}
+
+/// A context rendered one key per line, sorted, with type-annotated values:
+/// `counter: i64 = 1`. Strings are quoted; missing keys read `<missing>`.
+/// Built by [`display_context`] or `EventContextExt::display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextDisplay {
+    entries: Vec<(String, Option<ContextValue>)>,
+}
+
+impl ContextDisplay {
+    pub(crate) fn new(mut entries: Vec<(String, Option<ContextValue>)>) -> Self {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        ContextDisplay { entries }
+    }
+}
+
+impl fmt::Display for ContextDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            match value {
+                Some(ContextValue::Str(s)) => write!(f, "{}: String = {:?}", key, s)?,
+                Some(value) => write!(f, "{}: {} = {}", key, value.type_name(), value)?,
+                None => write!(f, "{}: <missing>", key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Render an owned context for humans; see [`ContextDisplay`].
+pub fn display_context(map: &ContextMap) -> ContextDisplay {
+    ContextDisplay::new(map.iter().map(|(key, value)| (key.clone(), Some(value.clone()))).collect())
+}
+
+// Conversions out of ContextValue hand the value back when the variant does
+// not match, so the caller can try another type.
+macro_rules! impl_try_from_context_value {
+    ($($variant:ident => $t:ty),+ $(,)?) => {$(
+        impl TryFrom<ContextValue> for $t {
+            type Error = ContextValue;
+
+            fn try_from(value: ContextValue) -> Result<Self, Self::Error> {
+                match value {
+                    ContextValue::$variant(v) => Ok(v),
+                    other => Err(other),
+                }
+            }
+        }
+    )+};
+}
+
+impl_try_from_context_value!(
+    Int => i64,
+    Float => f64,
+    Str => String,
+    Bool => bool,
+    Timestamp => SystemTime,
+    Stream => ContextStream,
+);
+
+#[cfg(feature = "decimal")]
+impl_try_from_context_value!(Decimal => Decimal);
+
+/// Also accepts `Str`, since bytes that are valid UTF-8 come back from Lua
+/// as one.
+impl TryFrom<ContextValue> for Vec<u8> {
+    type Error = ContextValue;
+
+    fn try_from(value: ContextValue) -> Result<Self, Self::Error> {
+        match value {
+            ContextValue::Bytes(bytes) => Ok(bytes),
+            ContextValue::Str(text) => Ok(text.into_bytes()),
+            other => Err(other),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for ContextValue {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            ContextValue::Int(i) => Ok(LuaValue::Integer(i)),
+            ContextValue::Float(n) => Ok(LuaValue::Number(n)),
+            ContextValue::Str(s) => s.into_lua(lua),
+            ContextValue::Bool(b) => Ok(LuaValue::Boolean(b)),
+            ContextValue::Bytes(bytes) => lua.create_string(&bytes).map(LuaValue::String),
+            ContextValue::Timestamp(time) => lua.create_userdata(LuaTimestamp(time)).map(LuaValue::UserData),
+            #[cfg(feature = "decimal")]
+            ContextValue::Decimal(decimal) => lua.create_userdata(LuaDecimal(decimal)).map(LuaValue::UserData),
+            ContextValue::Stream(stream) => lua.create_userdata(stream).map(LuaValue::UserData),
+            ContextValue::Ref(target) => lua.create_userdata(ContextRef(target)).map(LuaValue::UserData),
+        }
+    }
+}
+
+/// A single-pass sequence of context values backed by a Rust iterator.
+/// Clones share the iterator, so an item taken through one is gone for all.
+///
+/// ```ignore
+/// let rows = ContextStream::new(reader.lines().map(|line| ContextValue::from(line.unwrap())));
+/// context.insert("rows".to_string(), rows.into());
+/// // in Lua: for row in ctx.rows do ... end
+/// ```
+#[derive(Clone)]
+pub struct ContextStream(Arc<Mutex<Box<dyn Iterator<Item = ContextValue> + Send>>>);
+
+impl ContextStream {
+    pub fn new<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = ContextValue>,
+        I::IntoIter: Send + 'static,
+    {
+        ContextStream(Arc::new(Mutex::new(Box::new(items.into_iter()))))
+    }
+}
+
+impl Iterator for ContextStream {
+    type Item = ContextValue;
+
+    fn next(&mut self) -> Option<ContextValue> {
+        // A panic mid-`next` leaves the iterator usable, if not meaningful
+        let mut items = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        items.next()
+    }
+}
+
+impl fmt::Debug for ContextStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str("ContextStream") }
+}
+
+/// Streams are equal when they share the same iterator.
+impl PartialEq for ContextStream {
+    fn eq(&self, other: &Self) -> bool { Arc::ptr_eq(&self.0, &other.0) }
+}
+
+impl LuaUserData for ContextStream {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        // `stream()` and `stream:next()` both take the next item, nil at the end
+        methods.add_method_mut("next", |_, stream, ()| Ok(stream.next()));
+        methods.add_meta_method_mut(LuaMetaMethod::Call, |_, stream, _: LuaMultiValue| Ok(stream.next()));
+    }
+}
+
+/// A point in time as seen by Lua code, e.g. from `chain.now()`.
+///
+/// Supports `<` and `<=` against other timestamps or numbers of
+/// milliseconds since the Unix epoch, `==` between timestamps (Lua only
+/// consults `__eq` when both sides are userdata; compare `ts:millis()` with
+/// a number), `ts + ms` and `ts - ms` (giving a timestamp), `ts - ts`
+/// (giving milliseconds), `ts:millis()` and `tostring(ts)` (RFC 3339).
+/// Arithmetic that overflows the millisecond range raises.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuaTimestamp(pub SystemTime);
+
+impl LuaTimestamp {
+    pub fn from_millis(millis: i64) -> Self {
+        let offset = Duration::from_millis(millis.unsigned_abs());
+        LuaTimestamp(if millis >= 0 { UNIX_EPOCH + offset } else { UNIX_EPOCH - offset })
+    }
+
+    pub fn millis(&self) -> i64 {
+        match self.0.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_millis()).unwrap_or(i64::MAX),
+            Err(before) => i64::try_from(before.duration().as_millis()).map_or(i64::MIN, |millis| -millis),
+        }
+    }
+}
+
+// A timestamp or a plain number of milliseconds, for mixed comparisons
+fn millis_of(value: &LuaValue) -> LuaResult<i64> {
+    match value {
+        LuaValue::Integer(i) => Ok(*i),
+        LuaValue::Number(n) => Ok(*n as i64),
+        LuaValue::UserData(ud) => Ok(ud.borrow::<LuaTimestamp>()?.millis()),
+        other => Err(LuaError::RuntimeError(format!(
+            "cannot use a {} as a timestamp",
+            other.type_name()
+        ))),
+    }
+}
+
+fn overflow(operation: &str) -> LuaError {
+    LuaError::RuntimeError(format!("timestamp {} overflows", operation))
+}
+
+impl LuaUserData for LuaTimestamp {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("millis", |_, ts, ()| Ok(ts.millis()));
+        methods.add_meta_function(LuaMetaMethod::Lt, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(millis_of(&a)? < millis_of(&b)?)
+        });
+        methods.add_meta_function(LuaMetaMethod::Le, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(millis_of(&a)? <= millis_of(&b)?)
+        });
+        methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(millis_of(&a)? == millis_of(&b)?)
+        });
+        methods.add_meta_function(LuaMetaMethod::Add, |_, (a, b): (LuaValue, LuaValue)| {
+            let sum = millis_of(&a)?.checked_add(millis_of(&b)?).ok_or_else(|| overflow("addition"))?;
+            Ok(LuaTimestamp::from_millis(sum))
+        });
+        methods.add_meta_function(LuaMetaMethod::Sub, |lua, (a, b): (LuaValue, LuaValue)| {
+            let difference = millis_of(&a)?.checked_sub(millis_of(&b)?).ok_or_else(|| overflow("subtraction"))?;
+            match b {
+                LuaValue::UserData(_) => Ok(LuaValue::Integer(difference)),
+                _ => LuaTimestamp::from_millis(difference).into_lua(lua),
+            }
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, ts, ()| Ok(rfc3339(ts.0)));
+    }
+}
+
+#[cfg(feature = "decimal")]
+fn read_decimal(context: &EventContext, key: &str) -> Option<ContextValue> {
+    context.get::<Decimal>(key).map(ContextValue::Decimal)
+}
+
+#[cfg(not(feature = "decimal"))]
+fn read_decimal(_: &EventContext, _: &str) -> Option<ContextValue> { None }
+
+/// An exact decimal as seen by Lua code, e.g. from `chain.decimal("0.10")`.
+///
+/// Supports `+`, `-`, `*`, `/`, `%`, unary `-`, `<` and `<=` against other
+/// decimals, integers and decimal strings, `==` between decimals (Lua only
+/// consults `__eq` when both sides are userdata) and `tostring(d)`. Overflow
+/// and division by zero raise.
+#[cfg(feature = "decimal")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuaDecimal(pub Decimal);
+
+// A decimal, an integer or a decimal string, for mixed arithmetic
+#[cfg(feature = "decimal")]
+pub(crate) fn decimal_of(value: &LuaValue) -> LuaResult<Decimal> {
+    match value {
+        LuaValue::Integer(i) => Ok(Decimal::from(*i)),
+        LuaValue::String(s) => s
+            .to_str()?
+            .trim()
+            .parse()
+            .map_err(|e| LuaError::RuntimeError(format!("invalid decimal '{}': {}", s.to_string_lossy(), e))),
+        LuaValue::UserData(ud) => Ok(ud.borrow::<LuaDecimal>()?.0),
+        LuaValue::Number(n) => Err(LuaError::RuntimeError(format!(
+            "cannot use the float {} as a decimal; pass it as a string",
+            n
+        ))),
+        other => Err(LuaError::RuntimeError(format!("cannot use a {} as a decimal", other.type_name()))),
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl LuaUserData for LuaDecimal {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        fn arithmetic<'lua, M: LuaUserDataMethods<'lua, LuaDecimal>>(
+            methods: &mut M,
+            method: LuaMetaMethod,
+            op: fn(Decimal, Decimal) -> Option<Decimal>,
+            what: &'static str,
+        ) {
+            methods.add_meta_function(method, move |_, (a, b): (LuaValue, LuaValue)| {
+                op(decimal_of(&a)?, decimal_of(&b)?)
+                    .map(LuaDecimal)
+                    .ok_or_else(|| LuaError::RuntimeError(format!("decimal {} overflowed or divided by zero", what)))
+            });
+        }
+        arithmetic(methods, LuaMetaMethod::Add, Decimal::checked_add, "addition");
+        arithmetic(methods, LuaMetaMethod::Sub, Decimal::checked_sub, "subtraction");
+        arithmetic(methods, LuaMetaMethod::Mul, Decimal::checked_mul, "multiplication");
+        arithmetic(methods, LuaMetaMethod::Div, Decimal::checked_div, "division");
+        arithmetic(methods, LuaMetaMethod::Mod, Decimal::checked_rem, "remainder");
+        methods.add_meta_method(LuaMetaMethod::Unm, |_, d, ()| Ok(LuaDecimal(-d.0)));
+        methods.add_meta_function(LuaMetaMethod::Lt, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(decimal_of(&a)? < decimal_of(&b)?)
+        });
+        methods.add_meta_function(LuaMetaMethod::Le, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(decimal_of(&a)? <= decimal_of(&b)?)
+        });
+        methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b): (LuaValue, LuaValue)| {
+            Ok(decimal_of(&a)? == decimal_of(&b)?)
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, d, ()| Ok(d.0.to_string()));
+    }
+}
+
+#[cfg(feature = "decimal")]
+fn serialize_decimal<S: serde::Serializer>(decimal: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(decimal)
+}
+
+/// `time` as RFC 3339 in UTC with millisecond precision.
+fn rfc3339(time: SystemTime) -> String {
+    let millis = LuaTimestamp(time).millis();
+    let (days, ms_of_day) = (millis.div_euclid(86_400_000), millis.rem_euclid(86_400_000));
+    // Civil date from days since 1970-01-01 (H. Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000
+    )
+}
+
+fn serialize_rfc3339<S: serde::Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(*time))
+}
+
+fn serialize_ref<S: serde::Serializer>(target: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry("$ref", target)?;
+    map.end()
+}
+
+fn serialize_base64<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    serializer.serialize_str(&encoded)
+}
+
+/// Convert a Lua context table into an owned map, skipping values that
+/// have no owned representation. Keys removed with `nil` are absent, and
+/// references a run installed are kept as `Ref`s.
+pub fn table_to_map(table: &LuaTable) -> LuaResult<ContextMap> {
+    table_to_map_with(table, |_, value| ContextValue::from_lua(value))
+}
+
+/// Like [`table_to_map`], converting each stored value with `coerce`
+/// (given the key and the Lua value) instead of `ContextValue::from_lua`.
+pub fn table_to_map_with(
+    table: &LuaTable,
+    coerce: impl Fn(&str, &LuaValue) -> Option<ContextValue>,
+) -> LuaResult<ContextMap> {
+    let mut map = HashMap::new();
+    for pair in table.clone().pairs::<String, LuaValue>() {
+        let (key, value) = pair?;
+        if let Some(value) = coerce(&key, &value) {
+            map.insert(key, value);
+        }
+    }
+    if let Some(metatable) = table.get_metatable()
+        && let Some(refs) = metatable.raw_get::<_, Option<LuaTable>>(REFS_FIELD)?
+    {
+        for pair in refs.pairs::<String, String>() {
+            let (key, target) = pair?;
+            map.insert(key, ContextValue::Ref(target));
+        }
+    }
+    Ok(map)
+}
+
+/// Build a Lua context table from an owned map.
+pub fn map_to_table<'lua>(lua: &'lua Lua, map: &ContextMap) -> LuaResult<LuaTable<'lua>> {
+    let table = lua.create_table_with_capacity(0, map.len())?;
+    for (key, value) in map {
+        table.set(key.as_str(), value.clone())?;
+    }
+    Ok(table)
+}
+
+/// Build a context map from any `Serialize` value that serializes as a
+/// struct or map. `None`/null fields are omitted (nil means absent). Nested
+/// structs, maps and sequences have no owned form, so they are stored as
+/// their JSON text in a `Str`, and so are integers above `i64::MAX` (as
+/// their digits) rather than rounding them into a `Float`. `deserialize`
+/// reads either back into a field of the original type, or as the text
+/// into a `String` field.
+pub fn from_serialize<T: Serialize>(value: &T) -> Result<ContextMap, ChainError> {
+    let fields = match serde_json::to_value(value) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        Ok(other) => {
+            return Err(ChainError::ContextConversion(format!(
+                "expected a struct or map, found {}",
+                other
+            )));
+        }
+        Err(e) => return Err(ChainError::ContextConversion(e.to_string())),
+    };
+
+    let mut map = HashMap::with_capacity(fields.len());
+    for (key, field) in fields {
+        let value = match field {
+            serde_json::Value::Null => continue,
+            serde_json::Value::Bool(b) => ContextValue::Bool(b),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => ContextValue::Int(i),
+                (None, Some(u)) => ContextValue::Str(u.to_string()),
+                (None, None) => ContextValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => ContextValue::Str(s),
+            nested @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => {
+                ContextValue::Str(nested.to_string())
+            }
+        };
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+/// Deserialize a context map into `T`, e.g. a `#[derive(Deserialize)]`
+/// struct whose fields name context keys. Streams have no serialized form
+/// and are skipped. A `Str` holding JSON text, as `from_serialize` stores
+/// nested values, reads back into a struct, map or sequence field, and a
+/// `Str` of digits into an unsigned integer field.
+pub fn deserialize_map<T: DeserializeOwned>(map: &ContextMap) -> Result<T, ChainError> {
+    let conversion = |e: serde_json::Error| ChainError::ContextConversion(e.to_string());
+    let mut fields = Vec::with_capacity(map.len());
+    for (key, value) in map {
+        if !matches!(value, ContextValue::Stream(_)) {
+            fields.push((key.as_str(), FieldDeserializer(serde_json::to_value(value).map_err(conversion)?)));
+        }
+    }
+    T::deserialize(MapDeserializer::new(fields.into_iter())).map_err(conversion)
+}
+
+// One context value for `deserialize_map`: its JSON form, except that JSON
+// text in a string is parsed when the target type asks for something
+// nested, and digits when it asks for a u64.
+struct FieldDeserializer(serde_json::Value);
+
+impl FieldDeserializer {
+    // The value a JSON-text array or object string holds, otherwise the
+    // value as it is.
+    fn nested(self) -> serde_json::Value {
+        match &self.0 {
+            serde_json::Value::String(text) if text.starts_with(['[', '{']) => {
+                serde_json::from_str(text).unwrap_or(self.0)
+            }
+            _ => self.0,
+        }
+    }
+}
+
+impl IntoDeserializer<'_, serde_json::Error> for FieldDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self { self }
+}
+
+macro_rules! forward_to_json {
+    ($($method:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, serde_json::Error> {
+            self.0.$method(visitor)
+        }
+    )*};
+}
+
+macro_rules! forward_to_nested {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {$(
+        fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, serde_json::Error> {
+            self.nested().$method($($arg,)* visitor)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for FieldDeserializer {
+    type Error = serde_json::Error;
+
+    forward_to_nested! {
+        deserialize_any()
+        deserialize_seq()
+        deserialize_map()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+    }
+
+    forward_to_json! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u128 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, serde_json::Error> {
+        match &self.0 {
+            serde_json::Value::String(digits) if let Ok(n) = digits.parse() => visitor.visit_u64(n),
+            _ => self.0.deserialize_u64(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, serde_json::Error> {
+        match self.0 {
+            serde_json::Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, serde_json::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, serde_json::Error> {
+        self.0.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, serde_json::Error> {
+        self.nested().deserialize_enum(name, variants, visitor)
+    }
+}
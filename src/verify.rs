@@ -0,0 +1,81 @@
+use std::fmt;
+
+use crate::error::ChainError;
+use crate::runner::{ChainOutcome, LuaChainRunner};
+use crate::value::{ContextMap, ContextValue};
+
+// ============================================================================
+// SELF-TESTING CHAINS
+// ============================================================================
+// A Lua definition may carry an `expect` table of context values the chain
+// should end with:
+//
+//   return {
+//     context = { counter = 0 },
+//     events = { ... },
+//     expect = { counter = 1, message = "start -> processed" },
+//   }
+//
+// `LuaChainRunner::verify` runs the chain once and compares each expected
+// key with the final context, so CI can check a chain script by loading it
+// and calling `verify`. Keys the chain produces but `expect` does not list
+// are not checked. The comparison is the one `test-util`'s `assert_eq`
+// uses, and mismatches come back in key order as ContextMismatch values.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextMismatch {
+    pub key: String,
+    pub expected: ContextValue,
+    pub actual: Option<ContextValue>,
+}
+
+impl fmt::Display for ContextMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(
+                f,
+                "context key '{}': expected {:?}, found {:?}",
+                self.key, self.expected, actual
+            ),
+            None => write!(f, "context key '{}': expected {:?}, key is missing", self.key, self.expected),
+        }
+    }
+}
+
+impl std::error::Error for ContextMismatch {}
+
+/// How `context` differs from `expected` under `key`, if it does.
+pub(crate) fn mismatch(context: &ContextMap, key: &str, expected: ContextValue) -> Option<ContextMismatch> {
+    match context.get(key) {
+        Some(actual) if *actual == expected => None,
+        actual => Some(ContextMismatch { key: key.to_string(), expected, actual: actual.cloned() }),
+    }
+}
+
+impl LuaChainRunner {
+    /// Run the chain once and check its final context against the
+    /// definition's `expect` table. Fails with
+    /// `ChainError::ExpectationFailed` listing every differing key, or
+    /// `InvalidDefinition` if the definition has no `expect`.
+    pub fn verify(&self) -> Result<ChainOutcome, ChainError> {
+        let Some(expect) = self.expectations() else {
+            return Err(ChainError::InvalidDefinition("the definition has no `expect` table".to_string()));
+        };
+        let outcome = self.execute()?;
+        let mut keys: Vec<&String> = expect.keys().collect();
+        keys.sort();
+        let mismatches: Vec<ContextMismatch> = keys
+            .into_iter()
+            .filter_map(|key| mismatch(&outcome.context, key, expect[key].clone()))
+            .collect();
+        if mismatches.is_empty() {
+            Ok(outcome)
+        } else {
+            Err(ChainError::ExpectationFailed {
+                chain: self.meta().name.clone(),
+                run_id: outcome.run_id,
+                mismatches,
+            })
+        }
+    }
+}
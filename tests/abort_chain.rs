@@ -0,0 +1,51 @@
+mod common;
+
+use lua_chains::{assert_context, OutcomeStatus};
+
+#[test]
+fn middleware_aborting_on_event_0_stops_the_run() {
+    let runner = common::runner(
+        r#"return {
+            context = { started = true },
+            middleware = {
+                { name = "gate",
+                  applies_to = function(event_name) return event_name == "e0" end,
+                  handler = function(ctx, next)
+                      ctx.gate = "closed"
+                      chain.abort_chain()
+                      return next(ctx)
+                  end },
+            },
+            events = {
+                { name = "e0", handler = function(ctx) ctx.e0 = true return ctx end },
+                { name = "e1", handler = function(ctx) ctx.e1 = true return ctx end },
+                { name = "e2", handler = function(ctx) ctx.e2 = true return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_eq!(outcome.status, OutcomeStatus::Aborted);
+    assert_eq!(outcome.events_executed, 0);
+    assert_context!(outcome, "started" => true);
+    for key in ["e0", "e1", "e2"] {
+        assert!(!outcome.context.contains_key(key), "{key} ran");
+    }
+}
+
+#[test]
+fn a_handler_aborting_keeps_earlier_events() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "e0", handler = function(ctx) ctx.e0 = true return ctx end },
+                { name = "e1", handler = function(ctx) chain.abort_chain() end },
+                { name = "e2", handler = function(ctx) ctx.e2 = true return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_eq!(outcome.status, OutcomeStatus::Aborted);
+    assert_eq!(outcome.events_executed, 1);
+    assert_context!(outcome, "e0" => true);
+    assert!(!outcome.context.contains_key("e2"));
+}
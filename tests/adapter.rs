@@ -0,0 +1,36 @@
+mod common;
+
+use event_chains::EventContext;
+
+#[test]
+fn lua_events_on_the_native_executor_share_a_fresh_run_id() {
+    let runner = common::runner(
+        r#"return {
+            context = { n = 0 },
+            events = {
+                { name = "first", handler = function(ctx) ctx.first_id = chain.run_id() ctx.n = ctx.n + 1 return ctx end },
+                { name = "second", handler = function(ctx) ctx.second_id = chain.run_id() ctx.n = ctx.n + 1 return ctx end },
+            },
+        }"#,
+    );
+    let earlier = runner.execute().unwrap().run_id;
+    let chain = runner.into_event_chain();
+
+    let mut context = EventContext::new();
+    assert!(chain.execute(&mut context).success);
+    assert_eq!(context.get::<i64>("n"), Some(2));
+    let first: String = context.get("first_id").unwrap();
+    assert_eq!(context.get::<String>("second_id"), Some(first.clone()));
+    assert_ne!(first, earlier);
+}
+
+#[test]
+fn a_failing_lua_event_reports_its_message() {
+    let runner = common::runner(
+        "return { events = { { name = 'explode', handler = function(ctx) error('kaboom') end } } }",
+    );
+    let result = runner.into_event_chain().execute(&mut EventContext::new());
+    assert!(!result.success);
+    assert_eq!(result.failures[0].event_name, "explode");
+    assert!(result.failures[0].error_message.contains("kaboom"));
+}
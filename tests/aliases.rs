@@ -0,0 +1,54 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError};
+use mlua::prelude::*;
+
+const RENAME: &str = r#"return {
+    context = { user_name = "ada" },
+    events = {
+        { name = "legacy_writer", handler = function(ctx) ctx.username = ctx.username .. "!" return ctx end },
+        { name = "reader", handler = function(ctx) ctx.greeting = "hi " .. ctx.user_name return ctx end },
+    },
+}"#;
+
+#[test]
+fn a_write_through_the_alias_is_read_under_the_canonical_name() {
+    let mut runner = common::runner(RENAME);
+    runner.add_alias("username", "user_name").unwrap();
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "greeting" => "hi ada!");
+    assert!(!outcome.context.contains_key("username"));
+}
+
+#[test]
+fn redefining_an_alias_is_an_error() {
+    let mut runner = common::runner(RENAME);
+    runner.add_alias("username", "user_name").unwrap();
+    assert!(matches!(runner.add_alias("username", "login"), Err(ChainError::InvalidDefinition(_))));
+}
+
+#[test]
+fn aliases_chain_onto_a_context_with_its_own_metatable() {
+    let mut runner = common::runner(RENAME);
+    runner.add_alias("username", "user_name").unwrap();
+    let lua = runner.lua();
+    let context: LuaTable = lua
+        .load(
+            r#"local writes = {}
+            return setmetatable({ user_name = "bob" }, {
+                __index = function(_, key) return "default:" .. key end,
+                __newindex = function(t, key, value) writes[#writes + 1] = key rawset(t, key, value) end,
+                __writes = writes,
+            })"#,
+        )
+        .eval()
+        .unwrap();
+    let writes: LuaTable = context.get_metatable().unwrap().raw_get("__writes").unwrap();
+    let outcome = runner.execute_with_context(context).unwrap();
+    assert_context!(outcome, "greeting" => "hi bob!");
+    // The original __newindex still sees writes to plain new keys
+    assert_eq!(writes.get::<_, String>(1).unwrap(), "greeting");
+    // and its __index still answers for keys the runner does not know
+    let after: LuaTable = lua.globals().get("__context").unwrap();
+    assert_eq!(after.get::<_, String>("missing").unwrap(), "default:missing");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError};
+
+#[test]
+fn middleware_applies_only_while_the_flag_is_set() {
+    let runner = common::runner(
+        r#"return {
+            context = { debug = false, traced = "" },
+            middleware = {
+                { name = "trace",
+                  applies_to = function(event_name, ctx) return ctx.debug end,
+                  handler = function(ctx, next)
+                      ctx = next(ctx)
+                      ctx.traced = ctx.traced .. ctx.last
+                      return ctx
+                  end },
+            },
+            events = {
+                { name = "a", handler = function(ctx) ctx.last = "a" return ctx end },
+                { name = "enable", handler = function(ctx) ctx.last = "e" ctx.debug = true return ctx end },
+                { name = "b", handler = function(ctx) ctx.last = "b" return ctx end },
+                { name = "disable", handler = function(ctx) ctx.last = "d" ctx.debug = false return ctx end },
+                { name = "c", handler = function(ctx) ctx.last = "c" return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    // Checked against the context before each event runs
+    assert_context!(outcome, "traced" => "bd");
+}
+
+#[test]
+fn the_predicate_gets_the_event_name() {
+    let runner = common::runner(
+        r#"return {
+            context = { wrapped = "" },
+            middleware = {
+                { name = "only_b",
+                  applies_to = function(event_name) return event_name == "b" end,
+                  handler = function(ctx, next) ctx.wrapped = ctx.wrapped .. "x" return next(ctx) end },
+            },
+            events = {
+                { name = "a", handler = function(ctx) return ctx end },
+                { name = "b", handler = function(ctx) return ctx end },
+            },
+        }"#,
+    );
+    assert_context!(runner.execute().unwrap(), "wrapped" => "x");
+}
+
+#[test]
+fn a_raising_predicate_fails_the_event() {
+    let runner = common::runner(
+        r#"return {
+            middleware = { { name = "broken", applies_to = function() error("nope") end,
+                             handler = function(ctx, next) return next(ctx) end } },
+            events = { { name = "a", handler = function(ctx) return ctx end } },
+        }"#,
+    );
+    match runner.execute() {
+        Err(ChainError::EventFailed { index: 0, message, .. }) => {
+            assert!(message.contains("applies_to of middleware[0]:broken"), "{message}");
+        }
+        other => panic!("expected the event to fail, got {other:?}"),
+    }
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use lua_chains::{assert_context, ChainOutcome, ContextMismatch, ContextValue};
+
+fn outcome() -> ChainOutcome {
+    common::runner(
+        r#"return {
+            context = { counter = 0, message = "start" },
+            events = { { name = "process", handler = function(ctx)
+                ctx.counter = ctx.counter + 1
+                ctx.message = ctx.message .. " -> processed"
+                return ctx
+            end } },
+        }"#,
+    )
+    .execute()
+    .unwrap()
+}
+
+#[test]
+fn matching_values_pass() {
+    let outcome = outcome();
+    assert_context!(outcome, "counter" => 1, "message" => "start -> processed");
+    outcome.assert_eq("counter", 1).unwrap().assert_eq("message", "start -> processed").unwrap();
+}
+
+#[test]
+fn assert_eq_reports_the_differing_key() {
+    let outcome = outcome();
+    let mismatch = outcome.assert_eq("counter", 2).unwrap_err();
+    assert_eq!(
+        mismatch,
+        ContextMismatch { key: "counter".into(), expected: ContextValue::Int(2), actual: Some(ContextValue::Int(1)) }
+    );
+    assert_eq!(mismatch.to_string(), "context key 'counter': expected Int(2), found Int(1)");
+}
+
+#[test]
+#[should_panic(expected = "context key 'message': expected Str(\"done\"), found Str(\"start -> processed\")")]
+fn a_wrong_value_panics_with_the_diff() {
+    assert_context!(outcome(), "counter" => 1, "message" => "done");
+}
+
+#[test]
+#[should_panic(expected = "context key 'missing': expected Bool(true), key is missing")]
+fn a_missing_key_panics_with_the_diff() {
+    assert_context!(outcome(), "missing" => true);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use lua_chains::BoundaryStats;
+
+const CHAIN: &str = r#"return {
+    context = { n = 0 },
+    middleware = {
+        { name = "outer", handler = function(ctx, next) return next(ctx) end },
+        { name = "inner", handler = function(ctx, next) return next(ctx) end },
+    },
+    events = {
+        { name = "a", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+        { name = "b", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+    },
+}"#;
+
+#[test]
+fn a_known_chain_crosses_a_known_number_of_times() {
+    let runner = common::runner(CHAIN).with_boundary_stats(true);
+    let outcome = runner.execute().unwrap();
+    let expected = BoundaryStats {
+        global_reads: 0,
+        // `__context` and `chain` at the start, `__context` after each event
+        global_writes: 2 + 2,
+        // Per event: two middleware layers and the handler
+        lua_calls: 2 * 3,
+        // Per event: one `next` per middleware layer
+        functions_created: 2 * 2,
+    };
+    assert_eq!(outcome.boundary, Some(expected));
+    assert_eq!(runner.boundary_stats(), Some(expected));
+
+    runner.execute().unwrap();
+    assert_eq!(runner.boundary_stats().unwrap().total(), 2 * expected.total());
+}
+
+#[test]
+fn counting_is_off_by_default() {
+    let runner = common::runner(CHAIN);
+    assert_eq!(runner.execute().unwrap().boundary, None);
+    assert_eq!(runner.boundary_stats(), None);
+}
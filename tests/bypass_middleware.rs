@@ -0,0 +1,35 @@
+mod common;
+
+use lua_chains::assert_context;
+
+#[test]
+fn a_bypass_event_skips_the_recording_middleware() {
+    // Each event names the next one, so the middleware can record which
+    // events it wrapped
+    let runner = common::runner(
+        r#"return {
+            context = { wrapped = "", ran = "", upcoming = "a" },
+            middleware = {
+                { name = "record", handler = function(ctx, next)
+                    ctx.wrapped = ctx.wrapped .. ctx.upcoming
+                    return next(ctx)
+                end },
+            },
+            events = {
+                { name = "a", handler = function(ctx) ctx.ran = ctx.ran .. "a" ctx.upcoming = "b" return ctx end },
+                { name = "b", bypass_middleware = true, handler = function(ctx)
+                    ctx.ran = ctx.ran .. "b"
+                    ctx.upcoming = "c"
+                    return ctx
+                end },
+                { name = "c", handler = function(ctx) ctx.ran = ctx.ran .. "c" return ctx end },
+            },
+        }"#,
+    )
+    .with_trace(true);
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "ran" => "abc", "wrapped" => "ac");
+    let trace = outcome.middleware_trace.unwrap();
+    let layers: Vec<usize> = trace.iter().map(Vec::len).collect();
+    assert_eq!(layers, [1, 0, 1]);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use event_chains::EventContext;
+use lua_chains::value::map_to_table;
+use lua_chains::{assert_context, ContextMap, ContextStore, ContextValue, EventContextExt};
+
+const BLOB: &[u8] = &[0xff, 0xfe, 0x00, 0x80];
+
+fn append_runner() -> lua_chains::LuaChainRunner {
+    common::runner(
+        r#"return {
+            events = {
+                { name = "append", handler = function(ctx)
+                    ctx.blob = ctx.blob .. "\x01"
+                    ctx.size = #ctx.blob
+                    return ctx
+                end },
+            },
+        }"#,
+    )
+}
+
+#[test]
+fn non_utf8_bytes_round_trip_through_a_chain() {
+    let runner = append_runner();
+    let mut input = ContextMap::new();
+    input.insert("blob".to_string(), ContextValue::Bytes(BLOB.to_vec()));
+    let outcome = runner.execute_with_context(map_to_table(runner.lua(), &input).unwrap()).unwrap();
+    assert_context!(outcome, "blob" => ContextValue::Bytes([BLOB, &[0x01]].concat()), "size" => 5);
+}
+
+#[test]
+fn utf8_bytes_read_back_as_bytes() {
+    let runner = append_runner();
+    let mut input = ContextMap::new();
+    input.insert("blob".to_string(), ContextValue::Bytes(b"png".to_vec()));
+    let outcome = runner.execute_with_context(map_to_table(runner.lua(), &input).unwrap()).unwrap();
+    // Lua cannot tell these bytes from text...
+    assert_context!(outcome, "blob" => "png\u{1}");
+    // ...but reading them as bytes still works
+    assert_eq!(ContextStore::get::<Vec<u8>>(&outcome.context, "blob"), Some(b"png\x01".to_vec()));
+    assert_eq!(Vec::<u8>::try_from(ContextValue::Str("png".into())), Ok(b"png".to_vec()));
+}
+
+#[test]
+fn a_native_view_reads_a_string_as_bytes() {
+    let mut context = EventContext::new();
+    context.set_value("blob", "png");
+    let (blob,) = context.view::<(Vec<u8>,)>(("blob",)).unwrap();
+    assert_eq!(blob, b"png");
+}
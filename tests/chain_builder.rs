@@ -0,0 +1,48 @@
+use event_chains::{ChainableEvent, EventContext, EventResult};
+use lua_chains::{ChainBuilder, ChainError};
+
+struct Step(&'static str);
+
+impl ChainableEvent for Step {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let mut order: Vec<String> = context.get("order").unwrap_or_default();
+        order.push(self.0.to_string());
+        context.set("order", order);
+        EventResult::Success(())
+    }
+
+    fn name(&self) -> &str { self.0 }
+}
+
+fn builder() -> ChainBuilder {
+    ChainBuilder::new().event(Step("b")).event(Step("d"))
+}
+
+#[test]
+fn insert_at_the_front_middle_and_end() {
+    let mut builder = builder();
+    builder.insert(0, Step("a")).unwrap();
+    builder.insert(2, Step("c")).unwrap();
+    builder.insert(builder.len(), Step("e")).unwrap();
+    assert_eq!(builder.event_names(), ["a", "b", "c", "d", "e"]);
+
+    let mut context = EventContext::new();
+    assert!(builder.build().execute(&mut context).success);
+    assert_eq!(context.get::<Vec<String>>("order").unwrap(), ["a", "b", "c", "d", "e"]);
+}
+
+#[test]
+fn insert_past_the_end_is_refused() {
+    let mut builder = builder();
+    let err = builder.insert(3, Step("x")).unwrap_err();
+    assert!(matches!(err, ChainError::EventIndexOutOfRange { index: 3, len: 2 }));
+    assert_eq!(builder.event_names(), ["b", "d"]);
+}
+
+#[test]
+fn remove_takes_the_event_out() {
+    let mut builder = builder();
+    assert_eq!(builder.remove(0).unwrap().name(), "b");
+    assert_eq!(builder.event_names(), ["d"]);
+    assert!(matches!(builder.remove(1), Err(ChainError::EventIndexOutOfRange { index: 1, len: 1 })));
+}
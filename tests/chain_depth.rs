@@ -0,0 +1,69 @@
+use std::sync::{Arc, OnceLock};
+
+use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
+use lua_chains::{
+    chain_depth, set_max_chain_depth, with_nested_chain, ChainError, SubChain, DEFAULT_MAX_CHAIN_DEPTH,
+};
+
+// Counts how often it ran, then runs the sub-chain it belongs to again
+struct Recurse(Arc<OnceLock<SubChain>>);
+
+impl ChainableEvent for Recurse {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        context.set("levels", context.get::<i64>("levels").unwrap_or(0) + 1);
+        self.0.get().expect("sub-chain is installed").execute(context)
+    }
+
+    fn name(&self) -> &str { "recurse" }
+}
+
+// A sub-chain whose only event runs the sub-chain itself
+fn self_recursive() -> Arc<OnceLock<SubChain>> {
+    let handle = Arc::new(OnceLock::new());
+    let chain = EventChain::new().event(Recurse(handle.clone()));
+    assert!(handle.set(SubChain::new("self", chain)).is_ok());
+    handle
+}
+
+fn run(handle: &OnceLock<SubChain>) -> (EventResult<()>, i64) {
+    let mut context = EventContext::new();
+    let result = handle.get().unwrap().execute(&mut context);
+    (result, context.get::<i64>("levels").unwrap_or(0))
+}
+
+#[test]
+fn a_self_recursive_sub_chain_stops_at_the_default_limit() {
+    let handle = self_recursive();
+    match run(&handle) {
+        (EventResult::Failure(message), levels) => {
+            assert_eq!(levels, DEFAULT_MAX_CHAIN_DEPTH as i64);
+            assert!(message.contains("Sub-chain nesting depth 33 exceeds the limit of 32"), "{message}");
+        }
+        (other, _) => panic!("expected a failure, got {other:?}"),
+    }
+    assert_eq!(chain_depth(), 0);
+}
+
+#[test]
+fn the_limit_is_configurable_per_thread() {
+    set_max_chain_depth(3);
+    let handle = self_recursive();
+    match run(&handle) {
+        (EventResult::Failure(message), levels) => {
+            assert_eq!(levels, 3);
+            assert!(message.contains("Sub-chain nesting depth 4 exceeds the limit of 3"), "{message}");
+        }
+        (other, _) => panic!("expected a failure, got {other:?}"),
+    }
+}
+
+#[test]
+fn nesting_past_the_limit_is_a_max_depth_error() {
+    set_max_chain_depth(1);
+    let nested = with_nested_chain(|| {
+        assert_eq!(chain_depth(), 1);
+        with_nested_chain(|| ())
+    });
+    assert!(matches!(nested, Ok(Err(ChainError::MaxDepthExceeded { limit: 1, depth: 2 }))));
+    assert_eq!(chain_depth(), 0);
+}
@@ -0,0 +1,54 @@
+use event_chains::{ChainableEvent, EventChain, EventContext, EventResult, FaultToleranceMode};
+use lua_chains::{ChainBuilder, EventChainExt};
+
+struct Step(&'static str);
+
+impl ChainableEvent for Step {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let mut order: Vec<String> = context.get("order").unwrap_or_default();
+        order.push(self.0.to_string());
+        context.set("order", order);
+        EventResult::Success(())
+    }
+
+    fn name(&self) -> &str { self.0 }
+}
+
+struct Fail(&'static str);
+
+impl ChainableEvent for Fail {
+    fn execute(&self, _context: &mut EventContext) -> EventResult<()> {
+        EventResult::Failure("boom".to_string())
+    }
+
+    fn name(&self) -> &str { self.0 }
+}
+
+#[test]
+fn then_runs_the_first_chains_events_before_the_seconds() {
+    let a = EventChain::new().event(Step("a1")).event(Step("a2"));
+    let b = ChainBuilder::new().event(Step("b1")).event(Step("b2"));
+    let mut context = EventContext::new();
+    assert!(a.then(b).execute(&mut context).success);
+    assert_eq!(context.get::<Vec<String>>("order").unwrap(), ["a1", "a2", "b1", "b2"]);
+}
+
+#[test]
+fn appended_events_keep_their_names() {
+    let a = EventChain::new().event(Step("a1")).with_fault_tolerance(FaultToleranceMode::Lenient);
+    let b = ChainBuilder::new().event(Fail("b1")).event(Fail("b2"));
+    let result = a.then(b).execute(&mut EventContext::new());
+    let failed: Vec<&str> = result.failures.iter().map(|f| f.event_name.as_str()).collect();
+    assert_eq!(failed, ["b1", "b2"]);
+}
+
+#[test]
+fn builders_concatenate_in_order() {
+    let a = ChainBuilder::new().event(Step("a1"));
+    let b = ChainBuilder::new().event(Step("b1")).event(Step("b2"));
+    let combined = a.then(b);
+    assert_eq!(combined.event_names(), ["a1", "b1", "b2"]);
+    let mut context = EventContext::new();
+    assert!(combined.build().execute(&mut context).success);
+    assert_eq!(context.get::<Vec<String>>("order").unwrap(), ["a1", "b1", "b2"]);
+}
@@ -0,0 +1,40 @@
+use event_chains::EventContext;
+use lua_chains::EventContextExt;
+
+#[test]
+fn restore_puts_back_the_checkpointed_values() {
+    let mut context = EventContext::new();
+    context.set_value("balance", 100);
+    context.set_value("owner", "ada");
+    context.set_value("ledger", vec![0x01u8, 0x02]);
+
+    let saved = context.checkpoint(&["balance", "owner", "ledger", "missing"]);
+    assert_eq!(saved.keys().collect::<Vec<_>>(), ["balance", "owner", "ledger"]);
+
+    context.set_value("balance", 40);
+    context.set_value("owner", "grace");
+    context.set_value("ledger", vec![0xffu8]);
+    context.restore(saved);
+
+    assert_eq!(context.get::<i64>("balance"), Some(100));
+    assert_eq!(context.get::<String>("owner").as_deref(), Some("ada"));
+    assert_eq!(context.get::<Vec<u8>>("ledger"), Some(vec![0x01, 0x02]));
+}
+
+#[test]
+fn keys_outside_the_checkpoint_keep_their_changes() {
+    let mut context = EventContext::new();
+    context.set_value("balance", 100);
+    context.set_value("audit", "before");
+    let saved = context.checkpoint(&["balance"]);
+
+    context.set_value("balance", 0);
+    context.set_value("audit", "after");
+    context.set_value("created", true);
+    context.restore(saved);
+
+    assert_eq!(context.get::<i64>("balance"), Some(100));
+    assert_eq!(context.get::<String>("audit").as_deref(), Some("after"));
+    // A key absent at checkpoint time cannot be removed again
+    assert_eq!(context.get::<bool>("created"), Some(true));
+}
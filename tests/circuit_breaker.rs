@@ -0,0 +1,53 @@
+mod common;
+
+use std::rc::Rc;
+
+use lua_chains::{BreakerState, ChainError, LuaChainRunner};
+
+fn script(breaker: &str) -> String {
+    format!(
+        r#"calls = 0
+        return {{ events = {{ {{
+            name = "flaky",
+            circuit_breaker = {breaker},
+            handler = function(ctx) calls = calls + 1 error("down") end,
+        }} }} }}"#
+    )
+}
+
+fn message(result: Result<lua_chains::ChainOutcome, ChainError>) -> String {
+    match result {
+        Err(ChainError::EventFailed { message, .. }) => message,
+        other => panic!("expected the event to fail, got {other:?}"),
+    }
+}
+
+#[test]
+fn the_breaker_opens_after_three_failures() {
+    let runner = common::runner(&script("{ threshold = 3, cooldown_ms = 60000 }"));
+    for _ in 0..3 {
+        assert!(message(runner.execute()).contains("down"));
+    }
+    assert_eq!(runner.breaker_state("flaky").unwrap(), Some(BreakerState::Open));
+
+    let fourth = message(runner.execute());
+    assert!(fourth.contains("circuit breaker open"), "{fourth}");
+    assert_eq!(runner.lua().globals().get::<_, i64>("calls").unwrap(), 3);
+}
+
+#[test]
+fn after_the_cooldown_one_trial_goes_through() {
+    let runner = common::runner(&script("{ threshold = 1, cooldown_ms = 0 }"));
+    message(runner.execute());
+    assert_eq!(runner.breaker_state("flaky").unwrap(), Some(BreakerState::HalfOpen));
+    message(runner.execute());
+    assert_eq!(runner.lua().globals().get::<_, i64>("calls").unwrap(), 2);
+}
+
+#[test]
+fn unusable_settings_are_refused() {
+    for breaker in ["{ threshold = 0 }", "{ threshold = 1, cooldown_ms = -1 }", "{ threshold = 1, cooldown_ms = 1e300 }"] {
+        let result = LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), &script(breaker));
+        assert!(matches!(result, Err(ChainError::InvalidDefinition(_))), "{breaker}");
+    }
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use lua_chains::{ChainError, LuaChainRunner};
+
+fn runner(failing: &str) -> LuaChainRunner {
+    let script = format!(
+        r#"return {{
+            context = {{ ran = "" }},
+            events = {{
+                {{ name = "one", handler = function(ctx) ctx.ran = ctx.ran .. "1" return ctx end }},
+                {{ name = "two", handler = function(ctx) {failing} ctx.ran = ctx.ran .. "2" return ctx end }},
+                {{ name = "three", handler = function(ctx) ctx.ran = ctx.ran .. "3" return ctx end }},
+                {{ name = "tidy", tags = {{ "cleanup" }}, handler = function(ctx)
+                    ctx.tidied = true
+                    return ctx
+                end }},
+            }},
+        }}"#
+    );
+    common::runner(&script)
+}
+
+fn context(runner: &LuaChainRunner) -> mlua::Table<'_> {
+    runner.lua().globals().get("__context").unwrap()
+}
+
+#[test]
+fn a_failed_event_still_runs_the_cleanup_events() {
+    let runner = runner(r#"error("boom")"#);
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, name, .. }) => assert_eq!((index, name.as_str()), (1, "two")),
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+    let context = context(&runner);
+    assert_eq!(context.get::<_, String>("ran").unwrap(), "1");
+    assert!(context.get::<_, bool>("tidied").unwrap());
+}
+
+#[test]
+fn a_failed_checkpoint_snapshot_still_runs_the_cleanup_events() {
+    // A boolean key cannot be converted into an owned context, so building
+    // the checkpoint's copy after "two" fails outside any event.
+    let runner = runner("ctx[true] = 1").with_checkpoint(|_, _| {});
+    assert!(matches!(runner.execute(), Err(ChainError::Lua(_))));
+    let context = context(&runner);
+    assert_eq!(context.get::<_, String>("ran").unwrap(), "12");
+    assert!(context.get::<_, bool>("tidied").unwrap());
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use lua_chains::{ChainError, ContextValue, LuaChainRunner};
+
+fn runner(events: &str) -> LuaChainRunner {
+    common::runner(&format!("return {{ context = {{ n = 0 }}, events = {{ {events} }} }}")).with_collect_errors(true)
+}
+
+fn failures(runner: &LuaChainRunner) -> (Vec<(usize, String)>, lua_chains::ContextMap) {
+    match runner.execute() {
+        Err(ChainError::Multiple { errors, context }) => {
+            let failed = errors
+                .into_iter()
+                .map(|error| match error {
+                    ChainError::EventFailed { index, name, .. } => (index, name),
+                    other => panic!("unexpected error {other:?}"),
+                })
+                .collect();
+            (failed, context)
+        }
+        other => panic!("expected Multiple, got {other:?}"),
+    }
+}
+
+#[test]
+fn every_failure_is_reported_and_rolled_back() {
+    let runner = runner(
+        r#"{ name = "a", handler = function(ctx) ctx.n = ctx.n + 1 error("a failed") end },
+           { name = "b", handler = function(ctx) ctx.n = ctx.n + 10 return ctx end },
+           { name = "c", handler = function(ctx) ctx.n = ctx.n + 100 error("c failed") end },"#,
+    );
+    let (failed, context) = failures(&runner);
+    assert_eq!(failed, [(0, "a".to_string()), (2, "c".to_string())]);
+    assert_eq!(context.get("n"), Some(&ContextValue::Int(10)));
+}
+
+#[test]
+fn a_cyclic_context_can_be_snapshotted() {
+    let runner = runner(
+        r#"{ name = "link", handler = function(ctx) ctx.me = ctx ctx.inner = { up = ctx } return ctx end },
+           { name = "fail", handler = function(ctx) ctx.n = 1 error("boom") end },
+           { name = "check", handler = function(ctx)
+               ctx.same = ctx.me == ctx and ctx.inner.up == ctx
+               ctx.me, ctx.inner = nil, nil
+               return ctx
+           end },"#,
+    );
+    let (failed, context) = failures(&runner);
+    assert_eq!(failed, [(1, "fail".to_string())]);
+    assert_eq!(context.get("n"), Some(&ContextValue::Int(0)));
+    assert_eq!(context.get("same"), Some(&ContextValue::Bool(true)));
+}
+
+#[test]
+fn a_lazy_key_resolved_by_a_failed_event_is_restored() {
+    let runner = runner(
+        r#"{ name = "setup", handler = function(ctx)
+               chain.lazy(ctx, "x", function(c) c.n = c.n + 1 return 5 end)
+               return ctx
+           end },
+           { name = "fail", handler = function(ctx) local _ = ctx.x error("boom") end },
+           { name = "read", handler = function(ctx) ctx.y = ctx.x return ctx end },"#,
+    );
+    let (failed, context) = failures(&runner);
+    assert_eq!(failed, [(1, "fail".to_string())]);
+    assert_eq!(context.get("y"), Some(&ContextValue::Int(5)));
+    assert_eq!(context.get("n"), Some(&ContextValue::Int(1)));
+}
@@ -0,0 +1,7 @@
+use std::rc::Rc;
+use lua_chains::LuaChainRunner;
+
+/// A runner for `script` on a fresh Lua VM.
+pub fn runner(script: &str) -> LuaChainRunner {
+    LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), script).expect("chain definition loads")
+}
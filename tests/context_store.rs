@@ -0,0 +1,48 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use lua_chains::{assert_context, ContextStore, ContextValue};
+
+fn runner() -> lua_chains::LuaChainRunner {
+    common::runner(
+        r#"return {
+            events = {
+                { name = "tally", handler = function(ctx)
+                    ctx.count = ctx.count + 1
+                    ctx.last = "tally"
+                    ctx.scratch = nil
+                    return ctx
+                end },
+            },
+        }"#,
+    )
+}
+
+#[test]
+fn an_ordered_store_is_loaded_and_written_back() {
+    let mut store: BTreeMap<String, ContextValue> = BTreeMap::new();
+    store.set("count", 1);
+    store.set("scratch", "temporary");
+    store.set("owner", "ada");
+
+    let runner = runner();
+    let outcome = runner.execute_with_store(&mut store).unwrap();
+    assert_context!(outcome, "count" => 2, "last" => "tally", "owner" => "ada");
+
+    assert_eq!(ContextStore::keys(&store), ["count", "last", "owner"]);
+    assert_eq!(ContextStore::get::<i64>(&store, "count"), Some(2));
+    assert_eq!(ContextStore::get::<String>(&store, "last").as_deref(), Some("tally"));
+
+    runner.execute_with_store(&mut store).unwrap();
+    assert_eq!(ContextStore::get::<i64>(&store, "count"), Some(3));
+}
+
+#[test]
+fn a_failed_run_leaves_the_store_untouched() {
+    let mut store: BTreeMap<String, ContextValue> = BTreeMap::new();
+    store.set("count", "not a number");
+    assert!(runner().execute_with_store(&mut store).is_err());
+    assert_eq!(ContextStore::keys(&store), ["count"]);
+    assert_eq!(ContextStore::get::<String>(&store, "count").as_deref(), Some("not a number"));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, LuaChainRunner};
+
+fn runner(event: &str) -> LuaChainRunner {
+    common::runner(&format!(
+        r#"return {{
+            context = {{ balance = 10 }},
+            events = {{
+                {{ name = "deposit", handler = function(ctx) ctx.balance = ctx.balance + 5 return ctx end }},
+                {event},
+            }},
+        }}"#
+    ))
+}
+
+fn failure(runner: &LuaChainRunner) -> (usize, String, String) {
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, name, message, .. }) => (index, name, message),
+        other => panic!("expected the event to fail, got {other:?}"),
+    }
+}
+
+#[test]
+fn a_postcondition_violation_names_the_event() {
+    let runner = runner(
+        r#"{ name = "withdraw",
+             post = function(ctx) return ctx.balance >= 0 end,
+             on_error = function(ctx) ctx.recovered = true return ctx end,
+             handler = function(ctx) ctx.balance = ctx.balance - 100 return ctx end }"#,
+    );
+    let (index, name, message) = failure(&runner);
+    assert_eq!((index, name.as_str()), (1, "withdraw"));
+    assert!(message.contains("postcondition violated by event[1]:withdraw"), "{message}");
+}
+
+#[test]
+fn a_failed_precondition_fails_or_skips_the_event() {
+    let event = r#"{ name = "withdraw", pre = function(ctx) return ctx.balance >= 100 end,
+                     handler = function(ctx) ctx.balance = ctx.balance - 100 return ctx end }"#;
+    let (index, _, message) = failure(&runner(event));
+    assert_eq!(index, 1);
+    assert!(message.contains("precondition failed for event[1]:withdraw"), "{message}");
+
+    let skipping = event.replace("pre =", r#"on_pre_failure = "skip", pre ="#);
+    let outcome = runner(&skipping).execute().unwrap();
+    assert_context!(outcome, "balance" => 15);
+}
+
+#[test]
+fn a_kept_contract_passes() {
+    let runner = runner(
+        r#"{ name = "withdraw",
+             pre = function(ctx) return ctx.balance >= 5 end,
+             post = function(ctx) return ctx.balance >= 0 end,
+             handler = function(ctx) ctx.balance = ctx.balance - 5 return ctx end }"#,
+    );
+    assert_context!(runner.execute().unwrap(), "balance" => 10);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "decimal")]
+
+mod common;
+
+use lua_chains::{ChainError, ContextValue};
+use rust_decimal::Decimal;
+
+#[test]
+fn ten_tenths_make_exactly_one() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "sum", handler = function(ctx)
+                    local total = chain.decimal("0")
+                    for _ = 1, 10 do total = total + chain.decimal("0.1") end
+                    ctx.total = total
+                    ctx.exact = total == chain.decimal("1")
+                    local float = 0
+                    for _ = 1, 10 do float = float + 0.1 end
+                    ctx.float_exact = float == 1
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let context = runner.execute().unwrap().context;
+    assert_eq!(context.get("total"), Some(&ContextValue::Decimal(Decimal::ONE)));
+    assert_eq!(context["total"].to_string(), "1.0");
+    assert_eq!(context.get("exact"), Some(&ContextValue::Bool(true)));
+    assert_eq!(context.get("float_exact"), Some(&ContextValue::Bool(false)));
+}
+
+#[test]
+fn mixing_in_a_float_is_refused() {
+    let runner = common::runner(
+        r#"return { events = { { name = "mix", handler = function(ctx)
+            ctx.total = chain.decimal("0.1") + 0.1
+            return ctx
+        end } } }"#,
+    );
+    match runner.execute() {
+        Err(ChainError::EventFailed { message, .. }) => assert!(message.contains("cannot use the float"), "{message}"),
+        other => panic!("expected the event to fail, got {other:?}"),
+    }
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use lua_chains::value::map_to_table;
+use lua_chains::{assert_context, ContextMap, ContextValue};
+
+fn runner() -> lua_chains::LuaChainRunner {
+    let defaults = ContextMap::from([
+        ("region".to_string(), ContextValue::from("eu")),
+        ("retries".to_string(), ContextValue::Int(3)),
+    ]);
+    common::runner(
+        r#"return {
+            context = { region = "us" },
+            events = {
+                { name = "describe", handler = function(ctx)
+                    ctx.summary = ctx.region .. "/" .. ctx.retries
+                    return ctx
+                end },
+            },
+        }"#,
+    )
+    .with_defaults(defaults)
+}
+
+#[test]
+fn the_definitions_context_overrides_a_default() {
+    let outcome = runner().execute().unwrap();
+    assert_context!(outcome, "region" => "us", "retries" => 3, "summary" => "us/3");
+}
+
+#[test]
+fn a_per_run_context_overrides_a_default() {
+    let runner = runner();
+    let input = ContextMap::from([("retries".to_string(), ContextValue::Int(5))]);
+    let outcome = runner.execute_with_context(map_to_table(runner.lua(), &input).unwrap()).unwrap();
+    assert_context!(outcome, "region" => "eu", "retries" => 5, "summary" => "eu/5");
+}
@@ -0,0 +1,40 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use event_chains::EventContext;
+use lua_chains::{display_context, ContextMap, ContextValue, EventContextExt};
+
+#[test]
+fn a_mixed_context_prints_sorted_and_annotated() {
+    let mut map = ContextMap::new();
+    map.insert("name".into(), ContextValue::from("ada"));
+    map.insert("count".into(), ContextValue::Int(3));
+    map.insert("ratio".into(), ContextValue::Float(0.5));
+    map.insert("done".into(), ContextValue::Bool(false));
+    map.insert("blob".into(), ContextValue::Bytes(vec![1, 2, 3]));
+    map.insert("at".into(), ContextValue::Timestamp(UNIX_EPOCH + Duration::from_secs(86_400)));
+    assert_eq!(
+        display_context(&map).to_string(),
+        "at: SystemTime = 1970-01-02T00:00:00.000Z\n\
+         blob: Vec<u8> = <3 bytes>\n\
+         count: i64 = 3\n\
+         done: bool = false\n\
+         name: String = \"ada\"\n\
+         ratio: f64 = 0.5"
+    );
+}
+
+#[test]
+fn a_native_context_prints_the_requested_keys() {
+    let mut context = EventContext::new();
+    context.set("message", "start".to_string());
+    context.set("counter", 1i64);
+    assert_eq!(
+        context.display(&["message", "missing", "counter"]).to_string(),
+        "counter: i64 = 1\nmessage: String = \"start\"\nmissing: <missing>"
+    );
+}
+
+#[test]
+fn an_empty_context_prints_nothing() {
+    assert_eq!(display_context(&ContextMap::new()).to_string(), "");
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use lua_chains::assert_context;
+
+#[test]
+fn one_handler_is_reused_with_different_args() {
+    let runner = common::runner(
+        r#"local scale = function(ctx, args)
+            ctx.n = ctx.n * args.factor
+            return ctx
+        end
+        return {
+            context = { n = 1 },
+            events = {
+                { name = "double", handler = scale, args = { factor = 2 } },
+                { name = "triple", handler = scale, args = { factor = 3 } },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "n" => 6);
+}
+
+#[test]
+fn an_event_without_args_gets_an_empty_table() {
+    let runner = common::runner(
+        r#"return {
+            context = {},
+            events = {
+                { name = "count", handler = function(ctx, args)
+                    ctx.count = #args + (next(args) and 1 or 0)
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "count" => 0);
+}
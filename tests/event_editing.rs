@@ -0,0 +1,58 @@
+use std::rc::Rc;
+
+use lua_chains::{assert_context, ChainError, LuaChainRunner};
+
+fn runner() -> (Rc<mlua::Lua>, LuaChainRunner) {
+    let lua = Rc::new(mlua::Lua::new());
+    let runner = LuaChainRunner::from_script(
+        lua.clone(),
+        r#"return {
+            context = { order = "" },
+            events = {
+                { name = "a", handler = function(ctx) ctx.order = ctx.order .. "a" return ctx end },
+                { name = "b", handler = function(ctx) ctx.order = ctx.order .. "b" return ctx end },
+            },
+        }"#,
+    )
+    .unwrap();
+    (lua, runner)
+}
+
+fn appender<'lua>(lua: &'lua mlua::Lua, letter: &str) -> mlua::Function<'lua> {
+    lua.load(format!(r#"function(ctx) ctx.order = ctx.order .. "{letter}" return ctx end"#)).eval().unwrap()
+}
+
+#[test]
+fn events_are_inserted_around_a_named_event() {
+    let (lua, mut runner) = runner();
+    runner.insert_event_after("a", "x", appender(&lua, "x")).unwrap();
+    runner.insert_event_before("a", "y", appender(&lua, "y")).unwrap();
+    runner.insert_event_after("b", "z", appender(&lua, "z")).unwrap();
+    assert_eq!(runner.event_names(), ["y", "a", "x", "b", "z"]);
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "order" => "yaxbz");
+}
+
+#[test]
+fn a_removed_event_no_longer_runs() {
+    let (_lua, mut runner) = runner();
+    runner.remove_event("a").unwrap();
+    assert_eq!(runner.event_names(), ["b"]);
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "order" => "b");
+}
+
+#[test]
+fn naming_a_missing_event_is_an_error() {
+    let (lua, mut runner) = runner();
+    assert!(matches!(
+        runner.insert_event_after("missing", "x", appender(&lua, "x")),
+        Err(ChainError::UnknownEvent(name)) if name == "missing"
+    ));
+    assert!(matches!(
+        runner.insert_event_before("missing", "x", appender(&lua, "x")),
+        Err(ChainError::UnknownEvent(name)) if name == "missing"
+    ));
+    assert!(matches!(runner.remove_event("missing"), Err(ChainError::UnknownEvent(name)) if name == "missing"));
+    assert_eq!(runner.event_names(), ["a", "b"]);
+}
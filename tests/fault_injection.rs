@@ -0,0 +1,58 @@
+mod common;
+
+use std::time::Duration;
+
+use lua_chains::{ChainError, FaultInjector, LuaChainRunner};
+
+fn runner(faults: FaultInjector) -> LuaChainRunner {
+    common::runner(
+        r#"calls = 0
+        return { events = {
+            { name = "fetch", handler = function(ctx) calls = calls + 1 return ctx end },
+            { name = "other", handler = function(ctx) return ctx end },
+        } }"#,
+    )
+    .with_fault_injector(faults)
+}
+
+fn calls(runner: &LuaChainRunner) -> i64 { runner.lua().globals().get("calls").unwrap() }
+
+#[test]
+fn a_full_fault_rate_always_fails() {
+    let runner = runner(FaultInjector::new().fail("fetch", 1.0));
+    for _ in 0..20 {
+        match runner.execute() {
+            Err(ChainError::EventFailed { index: 0, message, .. }) => {
+                assert!(message.contains("injected fault in event[0]:fetch"), "{message}");
+            }
+            other => panic!("expected an injected fault, got {other:?}"),
+        }
+    }
+    assert_eq!(calls(&runner), 0);
+}
+
+#[test]
+fn a_zero_fault_rate_never_fails() {
+    let runner = runner(FaultInjector::new().fail("fetch", 0.0).fail("missing", 1.0));
+    for _ in 0..20 {
+        runner.execute().unwrap();
+    }
+    assert_eq!(calls(&runner), 20);
+}
+
+#[test]
+fn a_seeded_partial_rate_fails_the_same_runs() {
+    let failures = |seed| {
+        let runner = runner(FaultInjector::new().fail("fetch", 0.5)).with_seed(seed);
+        (0..20).map(|_| runner.execute().is_err()).collect::<Vec<bool>>()
+    };
+    let first = failures(11);
+    assert_eq!(first, failures(11));
+    assert!(first.contains(&true) && first.contains(&false), "{first:?}");
+}
+
+#[test]
+fn a_delay_slows_the_event_down() {
+    let runner = runner(FaultInjector::new().delay("fetch", Duration::from_millis(20), 1.0));
+    assert!(runner.execute().unwrap().duration >= Duration::from_millis(20));
+}
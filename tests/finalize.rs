@@ -0,0 +1,64 @@
+mod common;
+
+use lua_chains::assert_context;
+
+#[test]
+fn finalize_strips_scratch_keys_from_the_outcome() {
+    let runner = common::runner(
+        r#"return {
+            context = { n = 1 },
+            events = {
+                { name = "work", handler = function(ctx)
+                    ctx.__temp = ctx.n * 2
+                    ctx.n = ctx.__temp + 1
+                    return ctx
+                end },
+            },
+            finalize = function(ctx)
+                ctx.__temp = nil
+                return ctx
+            end,
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "n" => 3);
+    assert!(!outcome.context.contains_key("__temp"));
+}
+
+#[test]
+fn a_returned_table_from_finalize_replaces_the_context() {
+    let runner = common::runner(
+        r#"return {
+            context = { n = 1, __temp = "scratch" },
+            events = {
+                { name = "work", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+            },
+            finalize = function(ctx) return { total = ctx.n } end,
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "total" => 2);
+    assert!(!outcome.context.contains_key("__temp"));
+    assert!(!outcome.context.contains_key("n"));
+}
+
+#[test]
+fn finalize_does_not_run_after_a_failed_run() {
+    let runner = common::runner(
+        r#"return {
+            context = { __temp = "scratch" },
+            events = {
+                { name = "fail", handler = function(ctx) error("boom") end },
+            },
+            finalize = function(ctx)
+                ctx.__temp = nil
+                ctx.finalized = true
+                return ctx
+            end,
+        }"#,
+    );
+    assert!(runner.execute().is_err());
+    let context = runner.lua().globals().get::<_, mlua::Table>("__context").unwrap();
+    assert_eq!(context.get::<_, String>("__temp").unwrap(), "scratch");
+    assert!(context.get::<_, Option<bool>>("finalized").unwrap().is_none());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use lua_chains::{ConfigDiff, LuaChainRunner};
+
+fn runner(events: &[(&str, &str)]) -> LuaChainRunner {
+    let events: String = events
+        .iter()
+        .map(|(name, body)| format!(r#"{{ name = "{name}", handler = function(ctx) {body} return ctx end }},"#))
+        .collect();
+    common::runner(&format!(r#"return {{ context = {{ n = 0 }}, events = {{ {events} }} }}"#))
+}
+
+#[test]
+fn adding_an_event_changes_the_fingerprint_and_shows_in_the_diff() {
+    let before = runner(&[("load", ""), ("save", "")]);
+    let after = runner(&[("load", ""), ("validate", ""), ("save", "")]);
+    let fingerprint = before.config_fingerprint().unwrap();
+    assert_eq!(fingerprint.len(), 16);
+    assert!(fingerprint.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(fingerprint, after.config_fingerprint().unwrap());
+
+    assert_eq!(
+        before.config_diff(&after).unwrap(),
+        ConfigDiff { added_events: vec!["validate".into()], ..ConfigDiff::default() }
+    );
+    let back = after.config_diff(&before).unwrap();
+    assert_eq!(back.removed_events, ["validate"]);
+}
+
+#[test]
+fn handler_bodies_do_not_count() {
+    let a = runner(&[("load", "ctx.n = 1")]);
+    let b = runner(&[("load", "ctx.n = 2")]);
+    assert_eq!(a.config_fingerprint().unwrap(), b.config_fingerprint().unwrap());
+    assert!(a.config_diff(&b).unwrap().is_empty());
+}
+
+#[test]
+fn reordering_is_reported() {
+    let a = runner(&[("load", ""), ("save", "")]);
+    let b = runner(&[("save", ""), ("load", "")]);
+    assert_ne!(a.config_fingerprint().unwrap(), b.config_fingerprint().unwrap());
+    assert_eq!(a.config_diff(&b).unwrap(), ConfigDiff { events_reordered: true, ..ConfigDiff::default() });
+}
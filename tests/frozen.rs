@@ -0,0 +1,37 @@
+mod common;
+
+use std::rc::Rc;
+
+use lua_chains::assert_context;
+
+const CHAIN: &str = r#"return {
+    meta = { name = "frozen" },
+    context = { n = 0 },
+    events = {
+        { name = "a", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+        { name = "b", handler = function(ctx) ctx.n = ctx.n * 10 return ctx end },
+    },
+}"#;
+
+#[test]
+fn a_frozen_runner_runs_the_chain_as_configured() {
+    let mut runner = common::runner(CHAIN).with_stats(true);
+    runner.remove_event("b").unwrap();
+    let frozen = runner.freeze();
+
+    assert_eq!(frozen.event_names(), ["a"]);
+    assert_eq!(frozen.meta().name.as_deref(), Some("frozen"));
+    let outcome = frozen.execute().unwrap();
+    assert_context!(outcome, "n" => 1);
+    frozen.execute().unwrap();
+    assert_eq!(frozen.stats().unwrap().runs, 2);
+}
+
+#[test]
+fn owners_on_one_thread_share_a_frozen_runner() {
+    let frozen = Rc::new(common::runner(CHAIN).freeze());
+    let other = Rc::clone(&frozen);
+    let fingerprint = frozen.config_fingerprint().unwrap();
+    assert_context!(other.execute().unwrap(), "n" => 10);
+    assert_eq!(other.config_fingerprint().unwrap(), fingerprint);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError};
+
+fn runner(handler: &str) -> lua_chains::LuaChainRunner {
+    let script = format!(
+        r#"return {{
+            immutable_context = true,
+            context = {{ n = 1 }},
+            events = {{
+                {{ name = "step", handler = function(ctx) {handler} end }},
+            }},
+        }}"#
+    );
+    common::runner(&script)
+}
+
+#[test]
+fn assigning_to_the_context_fails_the_event() {
+    match runner("ctx.n = 2 return ctx").execute() {
+        Err(ChainError::EventFailed { index, message, .. }) => {
+            assert_eq!(index, 0);
+            assert!(message.contains("context is immutable"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+}
+
+#[test]
+fn returned_changes_are_applied() {
+    let outcome = runner("return { m = ctx.n + 1 }").execute().unwrap();
+    assert_context!(outcome, "n" => 1, "m" => 2);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError};
+
+// A chain whose `probe` event is denied `secret`, with `body` as its handler
+fn probe(body: &str) -> Result<lua_chains::ChainOutcome, ChainError> {
+    common::runner(&format!(
+        r#"return {{
+            context = {{ secret = "s3cr3t", visible = 1 }},
+            events = {{
+                {{ name = "probe", denied_keys = {{ "secret" }}, handler = function(ctx) {body} return ctx end }},
+            }},
+        }}"#
+    ))
+    .execute()
+}
+
+fn assert_refused(body: &str) {
+    match probe(body) {
+        Err(ChainError::EventFailed { name, message, .. }) => {
+            assert_eq!(name, "probe");
+            assert!(message.contains("'secret'"), "{}: {}", body, message);
+        }
+        other => panic!("expected `{}` to be refused, got {:?}", body, other),
+    }
+}
+
+#[test]
+fn reading_a_denied_key_errors() {
+    assert_refused("ctx.out = ctx.secret");
+}
+
+#[test]
+fn writing_a_denied_key_errors() {
+    assert_refused("ctx.secret = 'changed'");
+}
+
+#[test]
+fn permitted_keys_stay_usable() {
+    let outcome = probe("ctx.visible = ctx.visible + 1").unwrap();
+    assert_context!(outcome, "visible" => 2, "secret" => "s3cr3t");
+}
+
+#[test]
+fn the_context_global_does_not_reach_the_real_table() {
+    assert_refused("ctx.out = __context.secret");
+}
+
+#[test]
+fn a_lazy_producer_reads_through_the_policy() {
+    assert_refused("chain.lazy(ctx, 'derived', function(real) return real.secret end) ctx.out = ctx.derived");
+}
+
+#[test]
+fn a_lazy_key_must_be_writable() {
+    assert_refused("chain.lazy(ctx, 'secret', function() return 'x' end)");
+}
+
+#[test]
+fn a_reference_cannot_point_at_a_denied_key() {
+    assert_refused("chain.ref(ctx, 'alias', 'secret') ctx.out = ctx.alias");
+}
+
+#[test]
+fn a_ttl_cannot_be_set_on_a_denied_key() {
+    assert_refused("chain.set_ttl(ctx, 'secret', 'x', 1)");
+}
+
+#[test]
+fn the_context_global_is_restored_after_the_event() {
+    let runner = common::runner(
+        r#"return {
+            context = { secret = "s3cr3t" },
+            events = {
+                { name = "probe", denied_keys = { "secret" }, handler = function(ctx) return ctx end },
+                { name = "reader", handler = function(ctx) ctx.out = __context.secret return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "out" => "s3cr3t");
+}
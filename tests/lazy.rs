@@ -0,0 +1,72 @@
+mod common;
+
+use lua_chains::{assert_context, ContextValue};
+
+#[test]
+fn a_producer_runs_once_however_often_the_key_is_read() {
+    let runner = common::runner(
+        r#"return {
+            context = { calls = 0 },
+            events = {
+                { name = "declare", handler = function(ctx)
+                    chain.lazy(ctx, "total", function(c) c.calls = c.calls + 1 return 42 end)
+                    return ctx
+                end },
+                { name = "read", handler = function(ctx)
+                    ctx.a, ctx.b = ctx.total, ctx.total
+                    return ctx
+                end },
+                { name = "read_again", handler = function(ctx) ctx.c = ctx.total return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    for key in ["a", "b", "c", "total"] {
+        assert_eq!(outcome.context.get(key), Some(&ContextValue::Int(42)), "{}", key);
+    }
+    assert_context!(outcome, "calls" => 1);
+}
+
+#[test]
+fn an_unread_producer_never_runs() {
+    let runner = common::runner(
+        r#"return {
+            context = { calls = 0 },
+            events = {
+                { name = "declare", handler = function(ctx)
+                    chain.lazy(ctx, "total", function(c) c.calls = c.calls + 1 return 42 end)
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert!(!outcome.context.contains_key("total"));
+    assert_context!(outcome, "calls" => 0);
+}
+
+#[test]
+fn a_failing_producer_is_retried_on_the_next_read() {
+    let runner = common::runner(
+        r#"return {
+            context = { attempts = 0 },
+            events = {
+                { name = "declare", handler = function(ctx)
+                    chain.lazy(ctx, "total", function(c)
+                        c.attempts = c.attempts + 1
+                        if c.attempts == 1 then error("not yet") end
+                        return 7
+                    end)
+                    return ctx
+                end },
+                { name = "read", handler = function(ctx)
+                    ctx.first_ok = pcall(function() return ctx.total end)
+                    ctx.second = ctx.total
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "first_ok" => false, "second" => 7, "attempts" => 2);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use lua_chains::ChainError;
+
+fn runner() -> lua_chains::LuaChainRunner {
+    common::runner(
+        r#"return {
+            context = { n = 1 },
+            events = {
+                { name = "check", handler = function(ctx)
+                    if ctx.n < 10 then warn("x") end
+                    ctx.checked = true
+                    return ctx
+                end },
+                { name = "after", handler = function(ctx) ctx.after = true return ctx end },
+            },
+        }"#,
+    )
+}
+
+#[test]
+fn collected_warnings_are_reported_in_the_outcome() {
+    let outcome = runner().with_lua_warnings(true).execute().unwrap();
+    assert_eq!(outcome.lua_warnings.unwrap(), ["x"]);
+    assert_eq!(outcome.events_executed, 2);
+}
+
+#[test]
+fn strict_warnings_fail_the_event_that_warned() {
+    match runner().with_strict_warnings(true).execute() {
+        Err(ChainError::EventFailed { index, message, .. }) => {
+            assert_eq!(index, 0);
+            assert!(message.contains("Lua warning: x"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+}
+
+#[test]
+fn warnings_are_ignored_by_default() {
+    let outcome = runner().execute().unwrap();
+    assert_eq!(outcome.lua_warnings, None);
+    assert_eq!(outcome.events_executed, 2);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use std::rc::Rc;
+use lua_chains::{assert_context, ChainError, LuaChainRunner, MAX_MIDDLEWARE_DEPTH};
+
+fn script(layers: usize) -> String {
+    let middleware: String = (0..layers)
+        .map(|i| format!("{{ name = 'm{i}', handler = function(ctx, next) ctx.depth = ctx.depth + 1 return next(ctx) end }},"))
+        .collect();
+    format!(
+        "return {{ context = {{ depth = 0 }}, middleware = {{ {middleware} }},
+            events = {{ {{ name = 'noop', handler = function(ctx) return ctx end }} }} }}"
+    )
+}
+
+#[test]
+fn the_maximum_depth_runs() {
+    let runner = common::runner(&script(MAX_MIDDLEWARE_DEPTH));
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "depth" => MAX_MIDDLEWARE_DEPTH as i64);
+}
+
+#[test]
+fn deeper_stacks_are_rejected_at_load() {
+    // 250 layers would overflow Lua's C stack at run time
+    for layers in [MAX_MIDDLEWARE_DEPTH + 1, 250] {
+        match LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), &script(layers)) {
+            Err(ChainError::InvalidDefinition(message)) => assert!(message.contains("exceed the maximum depth")),
+            other => panic!("expected the definition to be rejected, got {:?}", other.map(|_| ())),
+        }
+    }
+}
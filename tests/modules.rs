@@ -0,0 +1,56 @@
+use std::rc::Rc;
+
+use lua_chains::{assert_context, LuaChainRunner};
+
+const CHAIN: &str = r#"
+    local common = require("common_middleware")
+    return {
+        context = { n = 1 },
+        middleware = { { name = "tagging", handler = common.tagging } },
+        events = {
+            { name = "double", handler = function(ctx) ctx.n = ctx.n * 2 return ctx end },
+        },
+    }"#;
+
+#[test]
+fn preloaded_middleware_is_loaded_through_require() {
+    let lua = Rc::new(mlua::Lua::new());
+    LuaChainRunner::preload_module(
+        &lua,
+        "common_middleware",
+        r#"local M = {}
+        function M.tagging(ctx, next)
+            ctx.tag = "before"
+            local result = next(ctx)
+            result.tag = result.tag .. "/after"
+            return result
+        end
+        return M"#,
+    )
+    .unwrap();
+    let runner = LuaChainRunner::from_script(lua, CHAIN).unwrap();
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "n" => 2, "tag" => "before/after");
+}
+
+#[test]
+fn a_preloaded_module_is_evaluated_once_per_vm() {
+    let lua = Rc::new(mlua::Lua::new());
+    LuaChainRunner::preload_module(
+        &lua,
+        "common_middleware",
+        r#"loads = (loads or 0) + 1
+        return { tagging = function(ctx, next) return next(ctx) end }"#,
+    )
+    .unwrap();
+    let first = LuaChainRunner::from_script(lua.clone(), CHAIN).unwrap();
+    let second = LuaChainRunner::from_script(lua.clone(), CHAIN).unwrap();
+    first.execute().unwrap();
+    second.execute().unwrap();
+    assert_eq!(lua.globals().get::<_, i64>("loads").unwrap(), 1);
+}
+
+#[test]
+fn a_missing_module_fails_to_load() {
+    assert!(LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), CHAIN).is_err());
+}
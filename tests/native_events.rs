@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use event_chains::{ChainableEvent, EventContext, EventResult};
+use lua_chains::{assert_context, ChainError, LuaChainRunner};
+
+struct Double;
+
+impl ChainableEvent for Double {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let n: i64 = context.get("n").unwrap_or_default();
+        context.set("n", n * 2);
+        EventResult::Success(())
+    }
+
+    fn name(&self) -> &str { "double" }
+}
+
+struct Negate;
+
+impl ChainableEvent for Negate {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        let n: i64 = context.get("n").unwrap_or_default();
+        context.set("n", -n);
+        EventResult::Success(())
+    }
+
+    fn name(&self) -> &str { "negate" }
+}
+
+fn plugins() -> HashMap<String, Box<dyn ChainableEvent>> {
+    let mut events: HashMap<String, Box<dyn ChainableEvent>> = HashMap::new();
+    events.insert("double".into(), Box::new(Double));
+    events.insert("negate".into(), Box::new(Negate));
+    events
+}
+
+#[test]
+fn events_registered_through_a_map_run_from_lua() {
+    let lua = Rc::new(mlua::Lua::new());
+    LuaChainRunner::register_events(&lua, plugins());
+    let runner = LuaChainRunner::from_script(
+        Rc::clone(&lua),
+        r#"return {
+            context = { n = 3 },
+            events = {
+                { name = "twice", native = "double" },
+                { name = "plus_one", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+                { name = "flip", native = "negate" },
+            },
+        }"#,
+    )
+    .unwrap();
+    assert_context!(runner.execute().unwrap(), "n" => -7);
+}
+
+#[test]
+fn registered_names_include_the_built_ins() {
+    let lua = mlua::Lua::new();
+    assert_eq!(LuaChainRunner::registered_event_names(&lua), ["append", "increment"]);
+    LuaChainRunner::register_events(&lua, plugins());
+    assert_eq!(LuaChainRunner::registered_event_names(&lua), ["append", "double", "increment", "negate"]);
+}
+
+#[test]
+fn an_unregistered_name_is_refused_at_load() {
+    let result = LuaChainRunner::from_script(
+        Rc::new(mlua::Lua::new()),
+        r#"return { events = { { name = "twice", native = "double" } } }"#,
+    );
+    assert!(matches!(result, Err(ChainError::InvalidDefinition(_))), "{:?}", result.err());
+}
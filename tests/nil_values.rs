@@ -0,0 +1,18 @@
+mod common;
+
+use lua_chains::assert_context;
+
+#[test]
+fn setting_a_key_to_nil_removes_it() {
+    let runner = common::runner(
+        r#"return {
+            context = { token = "abc", counter = 1 },
+            events = {
+                { name = "drop_token", handler = function(ctx) ctx.token = nil return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert!(!outcome.context.contains_key("token"));
+    assert_context!(outcome, "counter" => 1);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+fn runner() -> lua_chains::LuaChainRunner {
+    common::runner(
+        r#"return {
+            context = { n = 1 },
+            events = {
+                { name = "write", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+                { name = "read_only", handler = function(ctx) local seen = ctx.n return ctx end },
+                { name = "same_value", handler = function(ctx) ctx.n = ctx.n return ctx end },
+            },
+        }"#,
+    )
+}
+
+#[test]
+fn events_that_change_nothing_are_flagged() {
+    let outcome = runner().with_warn_on_no_change(true).execute().unwrap();
+    assert_eq!(
+        outcome.no_change_warnings.unwrap(),
+        [
+            "event[1]:read_only completed without changing the context",
+            "event[2]:same_value completed without changing the context",
+        ]
+    );
+}
+
+#[test]
+fn no_warnings_are_collected_when_disabled() {
+    let outcome = runner().execute().unwrap();
+    assert_eq!(outcome.no_change_warnings, None);
+}
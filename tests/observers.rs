@@ -0,0 +1,56 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError};
+
+#[test]
+fn every_observer_sees_the_same_input_context() {
+    let runner = common::runner(
+        r#"seen = {}
+        local function record(ctx) table.insert(seen, tostring(ctx.n) .. "/" .. tostring(ctx.extra)) end
+        return {
+            context = { n = 1 },
+            middleware = {
+                { name = "assigns", observational = true, handler = function(ctx)
+                    record(ctx)
+                    assigned = pcall(function() ctx.extra = "leaked" end)
+                end },
+                { name = "rawsets", observational = true, handler = function(ctx)
+                    record(ctx)
+                    rawset(ctx, "extra", "leaked")
+                end },
+                { name = "reads", observational = true, handler = function(ctx) record(ctx) end },
+            },
+            events = {
+                { name = "bump", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "n" => 2);
+    assert!(!outcome.context.contains_key("extra"));
+    let globals = runner.lua().globals();
+    assert!(!globals.get::<_, bool>("assigned").unwrap());
+    let seen: Vec<String> = globals.get("seen").unwrap();
+    assert_eq!(seen, ["1/nil", "1/nil", "1/nil"]);
+}
+
+#[test]
+fn a_raising_observer_fails_the_event() {
+    let runner = common::runner(
+        r#"return {
+            middleware = {
+                { name = "audit", observational = true, handler = function(ctx) error("audit down") end },
+            },
+            events = {
+                { name = "bump", handler = function(ctx) ctx.ran = true return ctx end },
+            },
+        }"#,
+    );
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, message, .. }) => {
+            assert_eq!(index, 0);
+            assert!(message.contains("audit down"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+}
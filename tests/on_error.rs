@@ -0,0 +1,55 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, ContextValue, OutcomeStatus};
+
+#[test]
+fn on_error_supplies_a_fallback_context_and_the_run_succeeds() {
+    let runner = common::runner(
+        r#"return {
+            context = { price = 10 },
+            events = {
+                { name = "fetch_rate", handler = function(ctx)
+                    error("rate service down")
+                end, on_error = function(ctx, err)
+                    ctx.rate = 1
+                    ctx.fallback_reason = err
+                    return ctx
+                end },
+                { name = "convert", handler = function(ctx)
+                    ctx.converted = ctx.price * ctx.rate
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_eq!(outcome.status, OutcomeStatus::Completed);
+    assert_eq!(outcome.events_executed, 2);
+    assert_context!(outcome, "rate" => 1, "converted" => 10);
+    match outcome.context.get("fallback_reason") {
+        Some(ContextValue::Str(reason)) => assert!(reason.contains("rate service down"), "{reason}"),
+        other => panic!("expected the error message, got {other:?}"),
+    }
+}
+
+#[test]
+fn raising_from_on_error_fails_the_event() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "fetch_rate", handler = function(ctx)
+                    error("rate service down")
+                end, on_error = function(ctx, err)
+                    error("no fallback either")
+                end },
+            },
+        }"#,
+    );
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, message, .. }) => {
+            assert_eq!(index, 0);
+            assert!(message.contains("no fallback either"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use lua_chains::assert_context;
+
+fn setups(runner: &lua_chains::LuaChainRunner) -> i64 {
+    runner.lua().globals().get("setups").unwrap()
+}
+
+#[test]
+fn a_once_event_fires_again_after_reset_once_flags() {
+    let runner = common::runner(
+        r#"setups = 0
+        return {
+            context = { n = 0 },
+            events = {
+                { name = "setup", once = true, handler = function(ctx)
+                    setups = setups + 1
+                    return ctx
+                end },
+                { name = "work", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+            },
+        }"#,
+    );
+    let first = runner.execute().unwrap();
+    assert_eq!((first.events_executed, setups(&runner)), (2, 1));
+    let second = runner.execute().unwrap();
+    assert_eq!((second.events_executed, setups(&runner)), (1, 1));
+    assert_context!(second, "n" => 1);
+
+    runner.reset_once_flags();
+    let third = runner.execute().unwrap();
+    assert_eq!((third.events_executed, setups(&runner)), (2, 2));
+}
+
+#[test]
+fn a_failed_once_event_stays_armed() {
+    let runner = common::runner(
+        r#"setups = 0
+        return {
+            events = {
+                { name = "setup", once = true, handler = function(ctx)
+                    setups = setups + 1
+                    if setups == 1 then error("not ready") end
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    assert!(runner.execute().is_err());
+    assert_eq!(runner.execute().unwrap().events_executed, 1);
+    assert_eq!(runner.execute().unwrap().events_executed, 0);
+    assert_eq!(setups(&runner), 2);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use lua_chains::{ContextMap, ContextValue, LuaChainRunner};
+
+fn counter(runner: &LuaChainRunner) -> Option<ContextValue> {
+    runner.execute().ok().and_then(|outcome| outcome.context.get("counter").cloned())
+}
+
+#[test]
+fn the_counter_carries_over_between_executes() {
+    let runner = common::runner(
+        r#"fail = false
+        return {
+            context = { counter = 0 },
+            events = { { name = "count", handler = function(ctx)
+                ctx.counter = ctx.counter + 1
+                if fail then error("failing on purpose") end
+                return ctx
+            end } },
+        }"#,
+    )
+    .with_persistent_context(ContextMap::new());
+    assert_eq!(counter(&runner), Some(ContextValue::Int(1)));
+    assert_eq!(counter(&runner), Some(ContextValue::Int(2)));
+
+    // A failed run leaves the stored context as it was
+    runner.lua().globals().set("fail", true).unwrap();
+    assert_eq!(counter(&runner), None);
+    runner.lua().globals().set("fail", false).unwrap();
+    assert_eq!(counter(&runner), Some(ContextValue::Int(3)));
+}
+
+#[test]
+fn without_a_store_each_execute_starts_fresh() {
+    let runner = common::runner(
+        r#"return {
+            context = { counter = 0 },
+            events = { { name = "count", handler = function(ctx) ctx.counter = ctx.counter + 1 return ctx end } },
+        }"#,
+    );
+    assert_eq!(counter(&runner), Some(ContextValue::Int(1)));
+    assert_eq!(counter(&runner), Some(ContextValue::Int(1)));
+}
@@ -0,0 +1,53 @@
+mod common;
+
+#[test]
+fn metrics_name_the_series_and_count_a_known_event() {
+    let runner = common::runner(
+        r#"fail = false
+        return {
+            meta = { name = "orders" },
+            events = {
+                { name = "fetch", handler = function(ctx) return ctx end },
+                { name = "store", handler = function(ctx) if fail then error("down") end return ctx end },
+            },
+        }"#,
+    )
+    .with_stats(true);
+    runner.execute().unwrap();
+    runner.execute().unwrap();
+    runner.lua().globals().set("fail", true).unwrap();
+    assert!(runner.execute().is_err());
+
+    let metrics = runner.metrics_prometheus().unwrap();
+    let lines: Vec<&str> = metrics.lines().collect();
+    for line in [
+        "# TYPE lua_chains_runs_total counter",
+        "# TYPE lua_chains_event_calls_total counter",
+        "# TYPE lua_chains_event_duration_seconds histogram",
+        r#"lua_chains_runs_total{chain="orders"} 3"#,
+        r#"lua_chains_event_calls_total{chain="orders",index="0",name="fetch",status="success"} 3"#,
+        r#"lua_chains_event_calls_total{chain="orders",index="0",name="fetch",status="failure"} 0"#,
+        r#"lua_chains_event_calls_total{chain="orders",index="1",name="store",status="success"} 2"#,
+        r#"lua_chains_event_calls_total{chain="orders",index="1",name="store",status="failure"} 1"#,
+        r#"lua_chains_event_duration_seconds_bucket{chain="orders",index="1",name="store",le="+Inf"} 3"#,
+        r#"lua_chains_event_duration_seconds_count{chain="orders",index="1",name="store"} 3"#,
+    ] {
+        assert!(lines.contains(&line), "missing {line:?} in\n{metrics}");
+    }
+
+    runner.reset_stats();
+    assert!(runner.metrics_prometheus().unwrap().contains(r#"lua_chains_runs_total{chain="orders"} 0"#));
+}
+
+#[test]
+fn metrics_need_stats() {
+    assert_eq!(common::runner("return { events = {} }").metrics_prometheus(), None);
+}
+
+#[test]
+fn label_values_are_escaped() {
+    let mut stats = lua_chains::ChainStats::default();
+    stats.events.push(lua_chains::EventStats { name: "say \"hi\"\n".into(), ..Default::default() });
+    let metrics = stats.to_prometheus(Some(r"a\b"));
+    assert!(metrics.contains(r#"{chain="a\\b",index="0",name="say \"hi\"\n",status="success"} 0"#), "{metrics}");
+}
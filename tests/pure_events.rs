@@ -0,0 +1,59 @@
+mod common;
+
+use lua_chains::value::map_to_table;
+use lua_chains::{assert_context, ContextMap, ContextValue, LuaChainRunner};
+
+fn runner() -> LuaChainRunner {
+    common::runner(
+        r#"calls = 0
+        return {
+            context = { x = 2 },
+            events = { { name = "square", pure = true, handler = function(ctx)
+                calls = calls + 1
+                ctx.y = ctx.x * ctx.x
+                return ctx
+            end } },
+        }"#,
+    )
+}
+
+fn calls(runner: &LuaChainRunner) -> i64 { runner.lua().globals().get("calls").unwrap() }
+
+fn run_with_x(runner: &LuaChainRunner, x: i64) -> Option<ContextValue> {
+    let mut map = ContextMap::new();
+    map.insert("x".into(), ContextValue::Int(x));
+    let context = map_to_table(runner.lua(), &map).unwrap();
+    runner.execute_with_context(context).unwrap().context.get("y").cloned()
+}
+
+#[test]
+fn a_pure_handler_runs_once_for_identical_inputs() {
+    let runner = runner();
+    assert_context!(runner.execute().unwrap(), "y" => 4);
+    assert_context!(runner.execute().unwrap(), "y" => 4);
+    assert_eq!(calls(&runner), 1);
+    assert_eq!(runner.memo_len("square").unwrap(), Some(1));
+}
+
+#[test]
+fn a_different_input_runs_the_handler_again() {
+    let runner = runner();
+    assert_eq!(run_with_x(&runner, 2), Some(ContextValue::Int(4)));
+    assert_eq!(run_with_x(&runner, 3), Some(ContextValue::Int(9)));
+    assert_eq!(run_with_x(&runner, 2), Some(ContextValue::Int(4)));
+    assert_eq!(calls(&runner), 2);
+}
+
+#[test]
+fn clearing_or_disabling_the_memo_reruns_the_handler() {
+    let runner = runner();
+    runner.execute().unwrap();
+    runner.clear_memo();
+    runner.execute().unwrap();
+    assert_eq!(calls(&runner), 2);
+
+    let runner = self::runner().with_memo_capacity(0);
+    runner.execute().unwrap();
+    runner.execute().unwrap();
+    assert_eq!(calls(&runner), 2);
+}
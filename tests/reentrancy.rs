@@ -0,0 +1,51 @@
+mod common;
+
+use std::rc::Rc;
+
+use lua_chains::{assert_context, ChainError, LuaChainRunner};
+
+// A runner whose handler calls back into `execute` on the same runner
+// through a Rust function exposed to Lua as `reenter`, which returns the
+// error it got as a string.
+fn reentrant_runner() -> Rc<LuaChainRunner> {
+    let runner = Rc::new(common::runner(
+        r#"return {
+            context = { runs = 0 },
+            events = {
+                { name = "again", handler = function(ctx)
+                    ctx.runs = ctx.runs + 1
+                    ctx.nested = reenter()
+                    return ctx
+                end },
+            },
+        }"#,
+    ));
+    // The function keeps the runner alive; leaking it is fine in a test
+    let inner = runner.clone();
+    let reenter = runner
+        .lua()
+        .create_function(move |_, ()| {
+            Ok(match inner.execute() {
+                Err(ChainError::AlreadyRunning) => "AlreadyRunning".to_string(),
+                other => format!("{other:?}"),
+            })
+        })
+        .unwrap();
+    runner.lua().globals().set("reenter", reenter).unwrap();
+    runner
+}
+
+#[test]
+fn executing_from_inside_a_handler_is_refused() {
+    let runner = reentrant_runner();
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "runs" => 1, "nested" => "AlreadyRunning");
+}
+
+#[test]
+fn the_runner_executes_again_once_the_outer_run_finished() {
+    let runner = reentrant_runner();
+    runner.execute().unwrap();
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "runs" => 1, "nested" => "AlreadyRunning");
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use event_chains::EventContext;
+use lua_chains::{assert_context, resolve_ref, ChainError, ContextMap, ContextValue, EventContextExt, MAX_REF_DEPTH};
+
+fn lookup(map: &ContextMap) -> impl Fn(&str) -> Option<ContextValue> + '_ {
+    |key| map.get(key).cloned()
+}
+
+#[test]
+fn a_ref_chain_resolves_to_the_final_value() {
+    let mut map = ContextMap::new();
+    map.insert("total".into(), ContextValue::Int(42));
+    map.insert("sum".into(), ContextValue::Ref("total".into()));
+    map.insert("result".into(), ContextValue::Ref("sum".into()));
+    map.insert("dangling".into(), ContextValue::Ref("nowhere".into()));
+    assert_eq!(resolve_ref(lookup(&map), "result").unwrap(), Some(ContextValue::Int(42)));
+    assert_eq!(resolve_ref(lookup(&map), "dangling").unwrap(), None);
+}
+
+#[test]
+fn a_ref_cycle_is_reported() {
+    let mut map = ContextMap::new();
+    map.insert("a".into(), ContextValue::Ref("b".into()));
+    map.insert("b".into(), ContextValue::Ref("a".into()));
+    assert!(matches!(resolve_ref(lookup(&map), "a"), Err(ChainError::RefCycle(key)) if key == "a"));
+}
+
+#[test]
+fn a_chain_of_max_depth_still_resolves() {
+    let mut map = ContextMap::new();
+    map.insert(format!("k{MAX_REF_DEPTH}"), ContextValue::Int(1));
+    for i in 0..MAX_REF_DEPTH {
+        map.insert(format!("k{i}"), ContextValue::Ref(format!("k{}", i + 1)));
+    }
+    assert_eq!(resolve_ref(lookup(&map), "k0").unwrap(), Some(ContextValue::Int(1)));
+}
+
+#[test]
+fn native_contexts_read_through_refs() {
+    let mut context = EventContext::new();
+    context.set("total", 7i64);
+    ContextValue::Ref("total".into()).write(&mut context, "alias");
+    assert_eq!(context.get_value("alias"), Some(ContextValue::Int(7)));
+}
+
+#[test]
+fn lua_reads_follow_refs_and_writes_replace_them() {
+    let runner = common::runner(
+        r#"return {
+            context = { total = 1 },
+            events = {
+                { name = "link", handler = function(ctx) chain.ref(ctx, "sum", "total") return ctx end },
+                { name = "read", handler = function(ctx)
+                    ctx.total = 2
+                    ctx.seen = ctx.sum
+                    ctx.sum = 3
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "seen" => 2, "sum" => 3, "total" => 2);
+}
+
+#[test]
+fn a_lua_ref_cycle_fails_the_reading_event() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "link", handler = function(ctx)
+                    chain.ref(ctx, "a", "b")
+                    chain.ref(ctx, "b", "a")
+                    return ctx
+                end },
+                { name = "read", handler = function(ctx) ctx.x = ctx.a return ctx end },
+            },
+        }"#,
+    );
+    match runner.execute() {
+        Err(ChainError::EventFailed { index: 1, message, .. }) => {
+            assert!(message.contains("reference cycle"), "{message}");
+        }
+        other => panic!("expected the read to fail, got {other:?}"),
+    }
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use lua_chains::assert_context;
+
+fn runner(return_mode: &str) -> lua_chains::LuaChainRunner {
+    let script = format!(
+        r#"return {{
+            context = {{ a = 1, b = 2 }},
+            events = {{
+                {{ name = "bump", {return_mode} handler = function(ctx)
+                    return {{ b = ctx.b + 10, c = 3 }}
+                end }},
+            }},
+        }}"#
+    );
+    common::runner(&script)
+}
+
+#[test]
+fn a_patch_is_merged_onto_the_context() {
+    let outcome = runner(r#"return_mode = "patch","#).execute().unwrap();
+    assert_context!(outcome, "a" => 1, "b" => 12, "c" => 3);
+}
+
+#[test]
+fn a_chain_wide_patch_mode_applies_to_every_event() {
+    let runner = common::runner(
+        r#"return {
+            return_mode = "patch",
+            context = { a = 1, b = 2 },
+            events = {
+                { name = "first", handler = function(ctx) return { b = 20 } end },
+                { name = "second", handler = function(ctx) return { c = ctx.a + ctx.b } end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "a" => 1, "b" => 20, "c" => 21);
+}
+
+#[test]
+fn without_patch_mode_the_returned_table_replaces_the_context() {
+    let outcome = runner("").execute().unwrap();
+    assert_context!(outcome, "b" => 12, "c" => 3);
+    assert!(!outcome.context.contains_key("a"));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use lua_chains::{ChainError, ContextMap, ContextValue};
+
+fn runner() -> lua_chains::LuaChainRunner {
+    common::runner(
+        r#"return {
+            middleware = {
+                { name = "auth", handler = function(ctx, next)
+                    if ctx.token ~= "secret" then
+                        ctx.rejected = "bad token"
+                        return ctx
+                    end
+                    ctx.user = "ada"
+                    return next(ctx)
+                end },
+                { name = "audit", observational = true, handler = function(ctx) end },
+            },
+            events = {
+                { name = "never", handler = function(ctx) error("events do not run") end },
+            },
+        }"#,
+    )
+}
+
+fn request(token: &str) -> ContextMap {
+    ContextMap::from([("token".to_string(), ContextValue::from(token))])
+}
+
+#[test]
+fn an_authorized_request_reaches_next() {
+    let (context, trace) = runner().run_middleware("auth", request("secret")).unwrap();
+    assert_eq!(trace, ["auth", "next"]);
+    assert_eq!(context.get("user"), Some(&ContextValue::from("ada")));
+    assert!(!context.contains_key("rejected"));
+}
+
+#[test]
+fn a_rejected_request_never_reaches_next() {
+    let (context, trace) = runner().run_middleware("auth", request("guess")).unwrap();
+    assert_eq!(trace, ["auth"]);
+    assert_eq!(context.get("rejected"), Some(&ContextValue::from("bad token")));
+    assert!(!context.contains_key("user"));
+}
+
+#[test]
+fn an_observer_runs_alone() {
+    let (context, trace) = runner().run_middleware("audit", request("secret")).unwrap();
+    assert_eq!(trace, ["audit"]);
+    assert_eq!(context, request("secret"));
+}
+
+#[test]
+fn an_unknown_name_is_an_error() {
+    assert!(matches!(
+        runner().run_middleware("missing", ContextMap::new()),
+        Err(ChainError::UnknownMiddleware(name)) if name == "missing"
+    ));
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use lua_chains::{assert_context, LuaChainRunner};
+
+// Ten events under one middleware that counts the events it wraps
+fn runner(rate: f64, observational: bool) -> LuaChainRunner {
+    let middleware = match observational {
+        true => "observational = true, handler = function(ctx) wrapped = wrapped + 1 end",
+        false => "handler = function(ctx, next) wrapped = wrapped + 1 return next(ctx) end",
+    };
+    let events: String = (0..10)
+        .map(|i| format!(r#"{{ name = "e{i}", handler = function(ctx) ctx.ran = ctx.ran + 1 return ctx end }},"#))
+        .collect();
+    let script = format!(
+        r#"wrapped = 0
+        return {{
+            context = {{ ran = 0 }},
+            middleware = {{ {{ name = "count", sample_rate = {rate}, {middleware} }} }},
+            events = {{ {events} }},
+        }}"#
+    );
+    common::runner(&script)
+}
+
+fn wrapped(runner: &LuaChainRunner) -> i64 {
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "ran" => 10);
+    runner.lua().globals().get("wrapped").unwrap()
+}
+
+#[test]
+fn rate_zero_never_runs() {
+    assert_eq!(wrapped(&runner(0.0, false)), 0);
+    assert_eq!(wrapped(&runner(0.0, true)), 0);
+}
+
+#[test]
+fn rate_one_always_runs() {
+    assert_eq!(wrapped(&runner(1.0, false)), 10);
+    assert_eq!(wrapped(&runner(1.0, true)), 10);
+}
+
+#[test]
+fn a_seeded_rate_samples_the_same_events() {
+    let sampled = |seed| {
+        let runner = runner(0.5, false).with_seed(seed).with_trace(true);
+        let trace = runner.execute().unwrap().middleware_trace.unwrap();
+        trace.iter().map(|layers| !layers.is_empty()).collect::<Vec<bool>>()
+    };
+    let first = sampled(3);
+    assert_eq!(first, sampled(3));
+    assert!(first.contains(&true) && first.contains(&false), "{first:?}");
+}
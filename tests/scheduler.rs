@@ -0,0 +1,54 @@
+use std::rc::Rc;
+
+use lua_chains::{assert_context, ChainError, ChainScheduler, LuaChainRunner, OutcomeStatus};
+
+// Both chains append to one global log on a shared VM
+fn runner(lua: &Rc<mlua::Lua>, chain: &str, events: usize, failing: Option<usize>) -> LuaChainRunner {
+    let events: String = (0..events)
+        .map(|i| {
+            let body = match failing == Some(i) {
+                true => "error('boom')".to_string(),
+                false => "ctx.n = ctx.n + 1 return ctx".to_string(),
+            };
+            format!(r#"{{ name = "{chain}{i}", handler = function(ctx) log[#log + 1] = "{chain}{i}" {body} end }},"#)
+        })
+        .collect();
+    let script = format!("return {{ context = {{ n = 0 }}, events = {{ {events} }} }}");
+    LuaChainRunner::from_script(Rc::clone(lua), &script).expect("chain definition loads")
+}
+
+fn log(lua: &mlua::Lua) -> Vec<String> { lua.globals().get("log").unwrap() }
+
+fn shared_lua() -> Rc<mlua::Lua> {
+    let lua = Rc::new(mlua::Lua::new());
+    lua.globals().set("log", lua.create_table().unwrap()).unwrap();
+    lua
+}
+
+#[test]
+fn chains_interleave_one_event_at_a_time() {
+    let lua = shared_lua();
+    let mut scheduler = ChainScheduler::new().chain(runner(&lua, "a", 2, None)).chain(runner(&lua, "b", 2, None));
+    assert_eq!(scheduler.tick(), [(0, 0), (1, 0)]);
+    assert_eq!(scheduler.tick(), [(0, 1), (1, 1)]);
+    assert_eq!(log(&lua), ["a0", "b0", "a1", "b1"]);
+
+    let outcomes = scheduler.run();
+    for outcome in outcomes {
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.status, OutcomeStatus::Completed);
+        assert_context!(outcome, "n" => 2);
+    }
+}
+
+#[test]
+fn a_failed_chain_stops_while_the_others_continue() {
+    let lua = shared_lua();
+    let outcomes = ChainScheduler::new()
+        .chain(runner(&lua, "a", 3, Some(1)))
+        .chain(runner(&lua, "b", 3, None))
+        .run();
+    assert_eq!(log(&lua), ["a0", "b0", "a1", "b1", "b2"]);
+    assert!(matches!(&outcomes[0], Err(ChainError::EventFailed { index: 1, .. })));
+    assert_eq!(outcomes[1].as_ref().unwrap().events_executed, 3);
+}
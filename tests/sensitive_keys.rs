@@ -0,0 +1,75 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, ContextCipher, ContextMap, ContextValue, LuaChainRunner};
+
+/// XOR with a byte derived from the key name; enough to tell ciphertext
+/// from plaintext, not a real cipher.
+struct XorCipher;
+
+impl XorCipher {
+    fn apply(key: &str, bytes: &[u8]) -> Vec<u8> {
+        let pad = key.bytes().fold(0x5a, |pad, b| pad ^ b);
+        bytes.iter().map(|b| b ^ pad).collect()
+    }
+}
+
+impl ContextCipher for XorCipher {
+    fn encrypt(&self, key: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> { Ok(Self::apply(key, plaintext)) }
+
+    fn decrypt(&self, key: &str, ciphertext: &[u8]) -> Result<Vec<u8>, String> { Ok(Self::apply(key, ciphertext)) }
+}
+
+const SECRET: &str = "hunter2-hunter2";
+
+fn runner() -> LuaChainRunner {
+    common::runner(
+        r#"return {
+            events = { { name = "use", handler = function(ctx)
+                ctx.token = ctx.token or "hunter2-hunter2"
+                ctx.seen = ctx.token
+                ctx.runs = (ctx.runs or 0) + 1
+                return ctx
+            end } },
+        }"#,
+    )
+    .with_cipher(XorCipher)
+    .with_sensitive_key("token")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool { haystack.windows(needle.len()).any(|window| window == needle) }
+
+#[test]
+fn a_sensitive_key_round_trips_through_the_store() {
+    let runner = runner();
+    let mut store = ContextMap::new();
+    runner.execute_with_store(&mut store).unwrap();
+
+    match store.get("token") {
+        Some(ContextValue::Bytes(stored)) => assert!(!contains(stored, SECRET.as_bytes())),
+        other => panic!("expected ciphertext, found {other:?}"),
+    }
+    assert_eq!(store.get("runs"), Some(&ContextValue::Int(1)));
+
+    // The second run reads the decrypted token back from the store
+    store.insert("seen".into(), ContextValue::from("stale"));
+    let outcome = runner.execute_with_store(&mut store).unwrap();
+    assert_context!(outcome, "seen" => SECRET);
+    assert_eq!(store.get("runs"), Some(&ContextValue::Int(2)));
+}
+
+#[test]
+fn a_plaintext_value_under_a_sensitive_key_fails_the_load() {
+    let mut store = ContextMap::new();
+    store.insert("token".into(), ContextValue::from(SECRET));
+    match runner().execute_with_store(&mut store) {
+        Err(ChainError::Cipher { key, .. }) => assert_eq!(key, "token"),
+        other => panic!("expected a cipher error, got {other:?}"),
+    }
+    assert_eq!(store.get("token"), Some(&ContextValue::from(SECRET)));
+}
+
+#[test]
+fn sensitive_keys_need_a_cipher() {
+    let runner = common::runner(r#"return { events = {} }"#).with_sensitive_key("token");
+    assert!(matches!(runner.execute_with_store(&mut ContextMap::new()), Err(ChainError::Cipher { .. })));
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use lua_chains::value::{deserialize_map, map_to_table};
+use lua_chains::{from_serialize, ContextStream, ContextValue};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Address {
+    city: String,
+}
+
+#[derive(Serialize)]
+struct Request {
+    user: String,
+    retries: i64,
+    note: Option<String>,
+    address: Address,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Response {
+    user: String,
+    retries: i64,
+    address: String,
+    tags: String,
+}
+
+#[test]
+fn nested_fields_become_json_text() {
+    let request = Request {
+        user: "ada".to_string(),
+        retries: 2,
+        note: None,
+        address: Address { city: "Paris".to_string() },
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let map = from_serialize(&request).unwrap();
+    assert_eq!(map.get("user"), Some(&ContextValue::Str("ada".to_string())));
+    assert_eq!(map.get("retries"), Some(&ContextValue::Int(2)));
+    assert!(!map.contains_key("note"));
+    assert_eq!(map.get("address"), Some(&ContextValue::Str(r#"{"city":"Paris"}"#.to_string())));
+    assert_eq!(map.get("tags"), Some(&ContextValue::Str(r#"["a","b"]"#.to_string())));
+}
+
+#[test]
+fn serialized_context_round_trips_through_a_run() {
+    let request = Request {
+        user: "ada".to_string(),
+        retries: 2,
+        note: None,
+        address: Address { city: "Paris".to_string() },
+        tags: vec!["a".to_string()],
+    };
+    let runner = common::runner(
+        "return { events = { { name = 'retry', handler = function(ctx) ctx.retries = ctx.retries + 1 return ctx end } } }",
+    );
+    let context = map_to_table(runner.lua(), &from_serialize(&request).unwrap()).unwrap();
+    let response: Response = runner.execute_with_context(context).unwrap().deserialize().unwrap();
+    assert_eq!(
+        response,
+        Response {
+            user: "ada".to_string(),
+            retries: 3,
+            address: r#"{"city":"Paris"}"#.to_string(),
+            tags: r#"["a"]"#.to_string(),
+        }
+    );
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct TypedResponse {
+    retries: i64,
+    address: Address,
+    tags: Vec<String>,
+}
+
+#[test]
+fn nested_fields_read_back_as_their_own_types() {
+    let request = Request {
+        user: "ada".to_string(),
+        retries: 2,
+        note: None,
+        address: Address { city: "Paris".to_string() },
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let runner = common::runner(
+        "return { events = { { name = 'retry', handler = function(ctx) ctx.retries = ctx.retries + 1 return ctx end } } }",
+    );
+    let context = map_to_table(runner.lua(), &from_serialize(&request).unwrap()).unwrap();
+    let response: TypedResponse = runner.execute_with_context(context).unwrap().deserialize().unwrap();
+    assert_eq!(
+        response,
+        TypedResponse {
+            retries: 3,
+            address: Address { city: "Paris".to_string() },
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    );
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Counter {
+    id: u64,
+    label: Option<String>,
+}
+
+#[test]
+fn integers_above_i64_max_are_kept_exactly() {
+    let counter = Counter { id: u64::MAX, label: None };
+    let map = from_serialize(&counter).unwrap();
+    assert_eq!(map.get("id"), Some(&ContextValue::Str(u64::MAX.to_string())));
+    assert_eq!(deserialize_map::<Counter>(&map).unwrap(), counter);
+}
+
+#[test]
+fn streams_are_skipped_when_deserializing_an_outcome() {
+    let runner = common::runner(
+        "return { events = { { name = 'label', handler = function(ctx) ctx.label = 'seen' return ctx end } } }",
+    );
+    let mut map = from_serialize(&Counter { id: 7, label: None }).unwrap();
+    map.insert("items".to_string(), ContextValue::Stream(ContextStream::new(vec![ContextValue::Int(1)])));
+    let outcome = runner.execute_with_context(map_to_table(runner.lua(), &map).unwrap()).unwrap();
+    assert!(matches!(outcome.context.get("items"), Some(ContextValue::Stream(_))));
+    let counter: Counter = outcome.deserialize().unwrap();
+    assert_eq!(counter, Counter { id: 7, label: Some("seen".to_string()) });
+}
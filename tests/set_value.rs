@@ -0,0 +1,25 @@
+use event_chains::EventContext;
+use lua_chains::{ContextValue, EventContextExt};
+
+#[test]
+fn str_and_i32_are_stored_under_their_canonical_types() {
+    let mut context = EventContext::new();
+    context.set_value("message", "start");
+    context.set_value("counter", 1i32);
+    assert_eq!(context.get::<String>("message").as_deref(), Some("start"));
+    assert_eq!(context.get::<i64>("counter"), Some(1));
+    assert_eq!(context.get_value("counter"), Some(ContextValue::Int(1)));
+}
+
+#[test]
+fn other_numbers_are_widened() {
+    let mut context = EventContext::new();
+    context.set_value("u32", 7u32);
+    context.set_value("usize", usize::MAX);
+    context.set_value("f32", 0.5f32);
+    context.set_value("flag", true);
+    assert_eq!(context.get::<i64>("u32"), Some(7));
+    assert_eq!(context.get::<i64>("usize"), Some(i64::MAX));
+    assert_eq!(context.get::<f64>("f32"), Some(0.5));
+    assert_eq!(context.get::<bool>("flag"), Some(true));
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use std::time::Duration;
+
+#[test]
+fn construction_time_is_recorded() {
+    let runner = common::runner(r#"return { events = { { name = "a", handler = function(ctx) return ctx end } } }"#);
+    let setup = runner.setup_duration();
+    assert!(setup > Duration::ZERO);
+    runner.execute().unwrap();
+    assert_eq!(runner.setup_duration(), setup);
+}
+
+#[test]
+fn slow_definition_code_counts_towards_setup() {
+    let runner = common::runner(
+        r#"local started = os.clock()
+        while os.clock() - started < 0.02 do end
+        return { events = { { name = "a", handler = function(ctx) return ctx end } } }"#,
+    );
+    assert!(runner.setup_duration() >= Duration::from_millis(20), "{:?}", runner.setup_duration());
+    assert!(runner.execute().unwrap().duration < runner.setup_duration());
+}
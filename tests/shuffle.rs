@@ -0,0 +1,36 @@
+mod common;
+
+use lua_chains::{ContextValue, LuaChainRunner};
+
+fn runner(seed: u64) -> LuaChainRunner {
+    let events: String = (1..=6)
+        .map(|i| format!(r#"{{ name = "e{i}", handler = function(ctx) ctx.order = ctx.order .. "{i}" return ctx end }},"#))
+        .collect();
+    let script = format!(r#"return {{ execution_mode = "shuffle", context = {{ order = "" }}, events = {{ {events} }} }}"#);
+    common::runner(&script).with_seed(seed)
+}
+
+fn orders(runner: &LuaChainRunner, runs: usize) -> Vec<String> {
+    (0..runs)
+        .map(|_| match runner.execute().unwrap().context.remove("order") {
+            Some(ContextValue::Str(order)) => order,
+            other => panic!("unexpected order {other:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn a_fixed_seed_gives_the_same_permutations() {
+    let first = orders(&runner(7), 5);
+    assert_eq!(first, orders(&runner(7), 5));
+    for order in &first {
+        let mut digits: Vec<char> = order.chars().collect();
+        digits.sort();
+        assert_eq!(digits.into_iter().collect::<String>(), "123456");
+    }
+}
+
+#[test]
+fn another_seed_gives_other_permutations() {
+    assert_ne!(orders(&runner(7), 5), orders(&runner(8), 5));
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use lua_chains::ChainError;
+
+fn sleep_error(ms: &str) -> String {
+    let runner = common::runner(&format!(
+        "return {{ events = {{ {{ name = 'pause', handler = function(ctx) chain.sleep({ms}) return ctx end }} }} }}"
+    ));
+    match runner.execute() {
+        Err(ChainError::EventFailed { message, .. }) => message,
+        other => panic!("expected the event to fail, got {:?}", other),
+    }
+}
+
+#[test]
+fn sleep_pauses_for_a_valid_duration() {
+    let runner = common::runner(
+        "return { events = { { name = 'pause', handler = function(ctx) chain.sleep(1) return ctx end } } }",
+    );
+    let outcome = runner.execute().unwrap();
+    assert!(outcome.duration >= std::time::Duration::from_millis(1));
+}
+
+#[test]
+fn sleep_rejects_durations_out_of_range() {
+    for ms in ["1e300", "-5", "0/0", "math.huge"] {
+        assert!(sleep_error(ms).contains("chain.sleep: invalid duration"), "{}", ms);
+    }
+}
@@ -0,0 +1,62 @@
+use std::rc::Rc;
+
+use lua_chains::{assert_context, ChainError, LuaChainRunner};
+
+fn load(script: &str) -> Result<LuaChainRunner, ChainError> {
+    LuaChainRunner::from_script(Rc::new(mlua::Lua::new()), script)
+}
+
+#[test]
+fn a_source_string_runs_as_the_handler_body() {
+    let runner = load(
+        r#"return {
+            context = { n = 2 },
+            events = {
+                { name = "scale", args = { factor = 5 }, source = "ctx.n = ctx.n * args.factor return ctx" },
+            },
+        }"#,
+    )
+    .unwrap();
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "n" => 10);
+}
+
+#[test]
+fn a_syntax_error_names_the_event() {
+    let error = load(
+        r#"return {
+            events = {
+                { name = "fine", source = "return ctx" },
+                { name = "broken", source = "ctx.n = = 1 return ctx" },
+            },
+        }"#,
+    )
+    .err()
+    .expect("the definition is rejected");
+    match error {
+        ChainError::InvalidDefinition(message) => {
+            assert!(message.starts_with("event[1] (broken) has invalid source"), "{message}");
+            assert!(message.contains("event[1]:broken:1:"), "{message}");
+        }
+        other => panic!("expected InvalidDefinition, got {other:?}"),
+    }
+}
+
+#[test]
+fn a_runtime_error_carries_the_chunk_name() {
+    let runner = load(
+        r#"return {
+            events = {
+                { name = "explode", source = "error('boom')" },
+            },
+        }"#,
+    )
+    .unwrap();
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, message, .. }) => {
+            assert_eq!(index, 0);
+            assert!(message.contains("event[0]:explode:1: boom"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+}
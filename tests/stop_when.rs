@@ -0,0 +1,46 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, LuaChainRunner, OutcomeStatus};
+
+fn runner(stop_when: &str) -> LuaChainRunner {
+    let script = format!(
+        r#"return {{
+            context = {{ ran = "" }},
+            stop_when = {stop_when},
+            events = {{
+                {{ name = "one", handler = function(ctx) ctx.ran = ctx.ran .. "1" return ctx end }},
+                {{ name = "two", handler = function(ctx) ctx.ran = ctx.ran .. "2" return ctx end }},
+                {{ name = "three", handler = function(ctx) ctx.ran = ctx.ran .. "3" return ctx end }},
+                {{ name = "tidy", tags = {{ "cleanup" }}, handler = function(ctx)
+                    ctx.tidied = true
+                    return ctx
+                end }},
+            }},
+        }}"#
+    );
+    common::runner(&script)
+}
+
+#[test]
+fn stopping_after_the_first_event_skips_the_rest() {
+    let outcome = runner(r#"function(ctx) return ctx.ran == "1" end"#).execute().unwrap();
+    assert_eq!(outcome.status, OutcomeStatus::StoppedEarly);
+    assert_eq!(outcome.events_executed, 1);
+    assert_context!(outcome, "ran" => "1", "tidied" => true);
+}
+
+#[test]
+fn an_error_in_stop_when_fails_the_event_just_run() {
+    let runner = runner(r#"function(ctx) if ctx.ran == "12" then error("bad guard") end return false end"#);
+    match runner.execute() {
+        Err(ChainError::EventFailed { index, name, message, .. }) => {
+            assert_eq!((index, name.as_str()), (1, "two"));
+            assert!(message.contains("stop_when failed"), "{message}");
+            assert!(message.contains("bad guard"), "{message}");
+        }
+        other => panic!("expected EventFailed, got {other:?}"),
+    }
+    let context = runner.lua().globals().get::<_, mlua::Table>("__context").unwrap();
+    assert_eq!(context.get::<_, String>("ran").unwrap(), "12");
+    assert!(context.get::<_, bool>("tidied").unwrap());
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use lua_chains::value::map_to_table;
+use lua_chains::{assert_context, ContextMap, ContextStream, ContextValue};
+
+#[test]
+fn one_event_yields_and_the_next_sums() {
+    let runner = common::runner(
+        r#"return {
+            context = { total = 0 },
+            events = {
+                { name = "produce", handler = function(ctx)
+                    ctx.items = chain.stream(function()
+                        for i = 1, 5 do coroutine.yield(i) end
+                    end)
+                    return ctx
+                end },
+                { name = "sum", handler = function(ctx)
+                    for item in ctx.items do ctx.total = ctx.total + item end
+                    ctx.items = nil
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "total" => 15);
+}
+
+#[test]
+fn a_rust_stream_is_consumed_in_lua() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "sum", handler = function(ctx)
+                    ctx.total = 0
+                    for item in ctx.items do ctx.total = ctx.total + item end
+                    ctx.items = nil
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let mut map = ContextMap::new();
+    map.insert("items".into(), ContextStream::new((1..=4).map(ContextValue::Int)).into());
+    let context = map_to_table(runner.lua(), &map).unwrap();
+    let outcome = runner.execute_with_context(context).unwrap();
+    assert_context!(outcome, "total" => 10);
+}
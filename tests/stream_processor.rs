@@ -0,0 +1,64 @@
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+
+use lua_chains::{assert_context, ChainError, ContextValue, EventInput, LuaChainRunner};
+
+fn runner() -> LuaChainRunner {
+    common::runner(
+        r#"return {
+            context = { n = 0 },
+            events = {
+                { name = "add", handler = function(ctx) ctx.n = ctx.n + (ctx.step or 1) return ctx end },
+                { name = "fail", handler = function(ctx) ctx.n = -1 error("boom") end },
+            },
+        }"#,
+    )
+}
+
+#[test]
+fn three_events_in_give_three_outcomes_out() {
+    let runner = runner();
+    let (input, inputs) = mpsc::channel();
+    let (outputs, output) = mpsc::channel();
+    let producer = thread::spawn(move || {
+        input.send(EventInput::named("add")).unwrap();
+        input.send(EventInput::named("add").with_override("step", 10i64)).unwrap();
+        input.send(EventInput::inline("double", "ctx.n = ctx.n * 2 return ctx")).unwrap();
+    });
+    let last = runner.execute_stream(inputs, outputs).unwrap();
+    producer.join().unwrap();
+
+    let n: Vec<Option<ContextValue>> = output.iter().map(|result| result.unwrap().context.get("n").cloned()).collect();
+    assert_eq!(n, [Some(ContextValue::Int(1)), Some(ContextValue::Int(11)), Some(ContextValue::Int(22))]);
+    assert_eq!(last.get("n"), Some(&ContextValue::Int(22)));
+}
+
+#[test]
+fn failures_are_reported_and_the_stream_carries_on() {
+    let runner = runner();
+    let (input, inputs) = mpsc::channel();
+    let (outputs, output) = mpsc::channel();
+    for message in [
+        EventInput::named("fail"),
+        EventInput::named("missing"),
+        EventInput::inline("broken", "this is not lua"),
+        EventInput::named("add"),
+    ] {
+        input.send(message).unwrap();
+    }
+    drop(input);
+    let last = runner.execute_stream(inputs, outputs).unwrap();
+
+    let results: Vec<_> = output.iter().collect();
+    assert!(matches!(&results[0], Err(ChainError::EventFailed { index: 1, .. })));
+    assert!(matches!(&results[1], Err(ChainError::UnknownEvent(name)) if name == "missing"));
+    assert!(
+        matches!(&results[2], Err(ChainError::InvalidDefinition(message)) if message.contains("inline event (broken)")),
+        "{:?}",
+        results[2]
+    );
+    assert_context!(results[3].as_ref().unwrap(), "n" => 1);
+    assert_eq!(last.get("n"), Some(&ContextValue::Int(1)));
+}
@@ -0,0 +1,62 @@
+use event_chains::{ChainableEvent, EventChain, EventContext, EventResult};
+use lua_chains::SwitchEvent;
+
+struct Mark(&'static str);
+
+impl ChainableEvent for Mark {
+    fn execute(&self, context: &mut EventContext) -> EventResult<()> {
+        context.set("ran", self.0.to_string());
+        EventResult::Success(())
+    }
+
+    fn name(&self) -> &str { self.0 }
+}
+
+fn switch() -> SwitchEvent {
+    SwitchEvent::new("mode")
+        .branch("fast", EventChain::new().event(Mark("fast")))
+        .branch("safe", EventChain::new().event(Mark("safe")))
+}
+
+fn run(switch: SwitchEvent, mode: Option<&str>) -> (EventResult<()>, Option<String>) {
+    let mut context = EventContext::new();
+    if let Some(mode) = mode {
+        context.set("mode", mode.to_string());
+    }
+    let result = switch.execute(&mut context);
+    (result, context.get::<String>("ran"))
+}
+
+#[test]
+fn the_matching_branch_runs() {
+    let (result, ran) = run(switch(), Some("safe"));
+    assert!(matches!(result, EventResult::Success(())));
+    assert_eq!(ran.as_deref(), Some("safe"));
+}
+
+#[test]
+fn an_unmatched_value_fails_by_default() {
+    match run(switch(), Some("turbo")) {
+        (EventResult::Failure(message), None) => assert_eq!(message, "no branch for mode = turbo"),
+        other => panic!("expected a failure, got {other:?}"),
+    }
+    match run(switch(), None) {
+        (EventResult::Failure(message), None) => assert_eq!(message, "context key 'mode' is missing"),
+        other => panic!("expected a failure, got {other:?}"),
+    }
+}
+
+#[test]
+fn an_unmatched_value_runs_the_default_branch() {
+    let switch = switch().default_branch(EventChain::new().event(Mark("fallback")));
+    let (result, ran) = run(switch, Some("turbo"));
+    assert!(matches!(result, EventResult::Success(())));
+    assert_eq!(ran.as_deref(), Some("fallback"));
+}
+
+#[test]
+fn an_unmatched_value_is_skipped_when_asked() {
+    let (result, ran) = run(switch().skip_unmatched(), Some("turbo"));
+    assert!(matches!(result, EventResult::Success(())));
+    assert_eq!(ran, None);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[test]
+fn teardown_runs_when_the_runner_is_dropped() {
+    let torn_down = Rc::new(Cell::new(false));
+    let flag = torn_down.clone();
+    let runner = common::runner("return { events = {} }").with_teardown(move || flag.set(true));
+    runner.execute().unwrap();
+    assert!(!torn_down.get());
+    drop(runner);
+    assert!(torn_down.get());
+}
+
+#[test]
+fn teardown_runs_after_on_shutdown() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let lua = Rc::new(mlua::Lua::new());
+    let shutdown_order = order.clone();
+    let record = lua.create_function(move |_, ()| {
+        shutdown_order.borrow_mut().push("on_shutdown");
+        Ok(())
+    });
+    lua.globals().set("record_shutdown", record.unwrap()).unwrap();
+    let teardown_order = order.clone();
+    let runner = lua_chains::LuaChainRunner::from_script(
+        lua,
+        "return { events = {}, on_shutdown = function() record_shutdown() end }",
+    )
+    .unwrap()
+    .with_teardown(move || teardown_order.borrow_mut().push("teardown"));
+    drop(runner);
+    assert_eq!(*order.borrow(), ["on_shutdown", "teardown"]);
+}
+
+#[test]
+fn a_panicking_teardown_does_not_escape_the_drop() {
+    let runner = common::runner("return { events = {} }").with_teardown(|| panic!("teardown failed"));
+    drop(runner);
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::time::SystemTime;
+use lua_chains::{assert_context, ChainError, ContextValue};
+
+#[test]
+fn a_timestamp_from_now_round_trips_and_compares() {
+    let before = SystemTime::now();
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "stamp", handler = function(ctx) ctx.created = chain.now() return ctx end },
+                { name = "check", handler = function(ctx)
+                    local hour_later = ctx.created + 3600 * 1000
+                    ctx.fresh = ctx.created < hour_later and not (hour_later <= ctx.created)
+                    ctx.same = ctx.created == chain.timestamp(ctx.created:millis())
+                    ctx.age_ms = hour_later - ctx.created
+                    return ctx
+                end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    match outcome.context.get("created") {
+        Some(ContextValue::Timestamp(created)) => assert!(*created >= before && *created <= SystemTime::now()),
+        other => panic!("expected a timestamp, got {:?}", other),
+    }
+    assert_context!(outcome, "fresh" => true, "same" => true, "age_ms" => 3_600_000);
+}
+
+#[test]
+fn overflowing_arithmetic_raises() {
+    for expression in ["chain.timestamp(math.maxinteger) + 1", "chain.timestamp(math.mininteger) - 1"] {
+        let runner = common::runner(&format!(
+            "return {{ events = {{ {{ name = 'shift', handler = function(ctx) ctx.t = {expression} return ctx end }} }} }}"
+        ));
+        match runner.execute() {
+            Err(ChainError::EventFailed { message, .. }) => assert!(message.contains("overflows"), "{}", message),
+            other => panic!("expected {} to fail the event, got {:?}", expression, other),
+        }
+    }
+}
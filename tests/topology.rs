@@ -0,0 +1,50 @@
+mod common;
+
+use serde_json::json;
+
+#[test]
+fn two_events_and_two_middleware_serialize_to_the_documented_shape() {
+    let runner = common::runner(
+        r#"return {
+            meta = { name = "demo" },
+            middleware = {
+                { name = "auth", handler = function(ctx, next) return next(ctx) end },
+                { name = "log", observational = true, handler = function(ctx) end },
+                { name = "timing", handler = function(ctx, next) return next(ctx) end },
+            },
+            events = {
+                { name = "increment", handler = function(ctx) return ctx end },
+                { name = "append", tags = { "cleanup" }, bypass_middleware = true, handler = function(ctx) return ctx end },
+            },
+        }"#,
+    );
+    assert_eq!(
+        serde_json::to_value(runner.topology()).unwrap(),
+        json!({
+            "chain": "demo",
+            "execution_mode": "sequential",
+            "observers": ["log"],
+            "middleware": [
+                { "name": "timing", "declared": 2, "depth": 0, "wraps": "auth" },
+                { "name": "auth", "declared": 0, "depth": 1, "wraps": null },
+            ],
+            "events": [
+                { "name": "increment", "order": 0, "tags": [], "bypass_middleware": false },
+                { "name": "append", "order": 1, "tags": ["cleanup"], "bypass_middleware": true },
+            ],
+        })
+    );
+}
+
+#[test]
+fn the_topology_follows_event_edits() {
+    let mut runner = common::runner(
+        r#"return { events = {
+            { name = "a", handler = function(ctx) return ctx end },
+            { name = "b", handler = function(ctx) return ctx end },
+        } }"#,
+    );
+    runner.move_event("b", 0).unwrap();
+    let names: Vec<(String, usize)> = runner.topology().events.into_iter().map(|event| (event.name, event.order)).collect();
+    assert_eq!(names, [("b".to_string(), 0), ("a".to_string(), 1)]);
+}
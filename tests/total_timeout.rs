@@ -0,0 +1,41 @@
+mod common;
+
+use std::time::Duration;
+
+use lua_chains::ChainError;
+
+#[test]
+fn a_slow_event_exhausts_the_budget() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "slow", handler = function(ctx) chain.sleep(20) ctx.slow = true return ctx end },
+                { name = "next", handler = function(ctx) ctx.next = true return ctx end },
+            },
+        }"#,
+    )
+    .with_max_total_duration(Duration::from_millis(5));
+    match runner.execute() {
+        Err(ChainError::TotalTimeout { next_event, limit, elapsed, .. }) => {
+            assert_eq!(next_event, 1);
+            assert_eq!(limit, Duration::from_millis(5));
+            assert!(elapsed >= Duration::from_millis(20), "{elapsed:?}");
+        }
+        other => panic!("expected TotalTimeout, got {other:?}"),
+    }
+    let context: mlua::Table = runner.lua().globals().get("__context").unwrap();
+    assert!(context.get::<_, bool>("slow").unwrap());
+    assert_eq!(context.get::<_, Option<bool>>("next").unwrap(), None);
+}
+
+#[test]
+fn a_generous_budget_lets_the_run_finish() {
+    let runner = common::runner(
+        r#"return { events = {
+            { name = "slow", handler = function(ctx) chain.sleep(1) return ctx end },
+            { name = "next", handler = function(ctx) ctx.next = true return ctx end },
+        } }"#,
+    )
+    .with_max_total_duration(Duration::from_secs(10));
+    assert_eq!(runner.execute().unwrap().events_executed, 2);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// A Vec<u8> the test can still read after handing the sink to the runner
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn every_trace_line_is_a_json_record() {
+    let buffer = SharedBuffer::default();
+    let runner = common::runner(
+        r#"return {
+            context = { n = 0 },
+            middleware = {
+                { name = "wrap", handler = function(ctx, next) return next(ctx) end },
+            },
+            events = {
+                { name = "first", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+                { name = "second", handler = function(ctx) ctx.n = ctx.n + 1 return ctx end },
+            },
+        }"#,
+    )
+    .with_trace_sink(Box::new(buffer.clone()));
+    let outcome = runner.execute().unwrap();
+
+    let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    let records: Vec<serde_json::Value> =
+        text.lines().map(|line| serde_json::from_str(line).expect("each line is JSON")).collect();
+    let summary: Vec<(String, String, String)> = records
+        .iter()
+        .map(|record| {
+            assert_eq!(record["run_id"], outcome.run_id.as_str());
+            let field = |key: &str| record[key].as_str().unwrap().to_string();
+            (field("kind"), field("name"), field("phase"))
+        })
+        .collect();
+    let expected: Vec<(String, String, String)> = [
+        ("event", "first", "enter"),
+        ("middleware", "wrap", "enter"),
+        ("middleware", "wrap", "exit"),
+        ("event", "first", "exit"),
+        ("event", "second", "enter"),
+        ("middleware", "wrap", "enter"),
+        ("middleware", "wrap", "exit"),
+        ("event", "second", "exit"),
+    ]
+    .iter()
+    .map(|(kind, name, phase)| (kind.to_string(), name.to_string(), phase.to_string()))
+    .collect();
+    assert_eq!(summary, expected);
+    for exit in records.iter().filter(|record| record["phase"] == "exit") {
+        assert_eq!(exit["status"], "ok");
+        assert!(exit["duration_us"].is_u64());
+    }
+}
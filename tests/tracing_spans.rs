@@ -0,0 +1,94 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// One captured span: its name, its parent's name and its `name` and
+/// `status` fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Captured {
+    span: String,
+    parent: Option<String>,
+    name: Option<String>,
+    status: Option<String>,
+}
+
+impl Visit for Captured {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "name" => self.name = Some(value.to_string()),
+            "status" => self.status = Some(value.to_string()),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<(Id, Captured)>>>);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut captured = Captured {
+            span: attrs.metadata().name().to_string(),
+            parent: ctx.span(id).and_then(|span| span.parent()).map(|parent| parent.name().to_string()),
+            ..Captured::default()
+        };
+        attrs.record(&mut captured);
+        self.0.lock().unwrap().push((id.clone(), captured));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+        if let Some((_, captured)) = self.0.lock().unwrap().iter_mut().find(|(span, _)| span == id) {
+            values.record(captured);
+        }
+    }
+}
+
+fn span(span: &str, parent: Option<&str>, name: Option<&str>, status: Option<&str>) -> Captured {
+    Captured {
+        span: span.to_string(),
+        parent: parent.map(str::to_string),
+        name: name.map(str::to_string),
+        status: status.map(str::to_string),
+    }
+}
+
+#[test]
+fn a_run_emits_nested_spans() {
+    let runner = common::runner(
+        r#"return {
+            middleware = { { name = "wrap", handler = function(ctx, next) return next(ctx) end } },
+            events = {
+                { name = "ok", handler = function(ctx) return ctx end },
+                { name = "fails", handler = function(ctx) error("boom") end },
+            },
+        }"#,
+    );
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    tracing::subscriber::with_default(subscriber, || assert!(runner.execute().is_err()));
+
+    let spans: Vec<Captured> = capture.0.lock().unwrap().iter().map(|(_, span)| span.clone()).collect();
+    assert_eq!(
+        spans,
+        [
+            span("chain_run", None, None, None),
+            span("event", Some("chain_run"), Some("ok"), Some("ok")),
+            span("middleware", Some("event"), Some("wrap"), None),
+            span("event", Some("chain_run"), Some("fails"), Some("error")),
+            span("middleware", Some("event"), Some("wrap"), None),
+        ]
+    );
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use lua_chains::{assert_context, ContextMap, ContextValue, LuaChainRunner};
+
+fn runner() -> LuaChainRunner {
+    common::runner(
+        r#"return {
+            context = { counter = 0, scratch = "x" },
+            events = { { name = "count", handler = function(ctx)
+                ctx.counter = ctx.counter + (ctx.step or 1)
+                ctx.scratch = nil
+                return ctx
+            end } },
+        }"#,
+    )
+    .with_persistent_context(ContextMap::new())
+}
+
+#[test]
+fn a_trial_leaves_the_persistent_context_unchanged() {
+    let runner = runner();
+    runner.execute().unwrap();
+
+    let mut overrides = ContextMap::new();
+    overrides.insert("step".into(), ContextValue::Int(10));
+    let trial = runner.trial(overrides).unwrap();
+    assert_context!(trial.outcome, "counter" => 11);
+    assert_eq!(trial.changed.get("counter"), Some(&ContextValue::Int(11)));
+    assert!(trial.removed.is_empty());
+
+    // The real run continues from the stored counter of 1
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "counter" => 2);
+    assert!(!outcome.context.contains_key("step"));
+}
+
+#[test]
+fn a_trial_reports_removed_keys() {
+    let trial = runner().trial(ContextMap::new()).unwrap();
+    assert_eq!(trial.removed, ["scratch"]);
+    assert_eq!(trial.changed.get("counter"), Some(&ContextValue::Int(1)));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, ContextValue};
+
+#[test]
+fn a_key_with_ttl_one_is_gone_after_the_next_event() {
+    let runner = common::runner(
+        r#"return {
+            events = {
+                { name = "issue", handler = function(ctx) chain.set_ttl(ctx, "token", "abc", 1) return ctx end },
+                { name = "use", handler = function(ctx) ctx.seen = ctx.token return ctx end },
+                { name = "later", handler = function(ctx) ctx.still_there = ctx.token ~= nil return ctx end },
+            },
+        }"#,
+    );
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "seen" => "abc", "still_there" => false);
+    assert!(!outcome.context.contains_key("token"));
+}
+
+#[test]
+fn event_ttls_count_events_run_in_shuffle_mode() {
+    // Whatever order the shuffle picks, the key set first lives through
+    // exactly one more event
+    let script = r#"
+        local function step(ctx)
+            if ctx.token ~= nil then ctx.reads = ctx.reads + 1 end
+            if not ctx.issued then
+                ctx.issued = true
+                chain.set_ttl(ctx, "token", 1, 1)
+            end
+            return ctx
+        end
+        return {
+            execution_mode = "shuffle",
+            context = { reads = 0 },
+            events = { { name = "a", handler = step }, { name = "b", handler = step }, { name = "c", handler = step } },
+        }"#;
+    for seed in 0..8 {
+        let outcome = common::runner(script).with_seed(seed).execute().unwrap();
+        assert_eq!(outcome.context.get("reads"), Some(&ContextValue::Int(1)), "seed {}", seed);
+    }
+}
+
+#[test]
+fn out_of_range_durations_raise() {
+    for ttl in ["{ ms = 1e300 }", "{ ms = -1 }", "{ ms = 0/0 }"] {
+        let runner = common::runner(&format!(
+            "return {{ events = {{ {{ name = 'set', handler = function(ctx) chain.set_ttl(ctx, 'k', 1, {ttl}) return ctx end }} }} }}"
+        ));
+        match runner.execute() {
+            Err(ChainError::EventFailed { message, .. }) => assert!(message.contains("chain.set_ttl"), "{}", message),
+            other => panic!("expected {} to fail the event, got {:?}", ttl, other),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::rc::Rc;
+use lua_chains::{assert_context, LuaChainRunner};
+use mlua::prelude::*;
+
+struct Connection {
+    id: i64,
+}
+
+impl LuaUserData for Connection {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("id", |_, this, ()| Ok(this.id));
+    }
+}
+
+#[test]
+fn userdata_placed_by_one_event_reaches_the_next() {
+    let lua = Rc::new(Lua::new());
+    let open = lua.create_function(|_, id: i64| Ok(Connection { id })).unwrap();
+    lua.globals().set("open_connection", open).unwrap();
+    let runner = LuaChainRunner::from_script(
+        lua.clone(),
+        r#"return {
+            events = {
+                { name = "connect", handler = function(ctx) ctx.conn = open_connection(7) return ctx end },
+                { name = "query", handler = function(ctx) ctx.conn_id = ctx.conn:id() return ctx end },
+            },
+        }"#,
+    )
+    .unwrap();
+
+    let outcome = runner.execute().unwrap();
+    assert_context!(outcome, "conn_id" => 7);
+    // Owned snapshots skip userdata, but the run's table still holds it
+    assert!(!outcome.context.contains_key("conn"));
+    let context: LuaTable = lua.globals().get("__context").unwrap();
+    let conn: LuaAnyUserData = context.get("conn").unwrap();
+    assert_eq!(conn.borrow::<Connection>().unwrap().id, 7);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, ContextValue, LuaChainRunner};
+
+fn non_negative(value: &ContextValue) -> Result<(), String> {
+    match value {
+        ContextValue::Int(n) if *n < 0 => Err(format!("{n} is negative")),
+        _ => Ok(()),
+    }
+}
+
+fn runner(handler: &str) -> LuaChainRunner {
+    let script = format!(
+        r#"return {{
+            context = {{ counter = 1 }},
+            events = {{ {{ name = "set", handler = {handler} }} }},
+        }}"#
+    );
+    common::runner(&script).with_validator("counter", non_negative)
+}
+
+fn assert_rejected(runner: &LuaChainRunner) {
+    match runner.execute() {
+        Err(ChainError::ValidationFailed { index, name, message, .. }) => {
+            assert_eq!((index, name.as_str()), (0, "set"));
+            assert_eq!(message, "'counter': -1 is negative");
+        }
+        other => panic!("expected ValidationFailed, got {other:?}"),
+    }
+}
+
+#[test]
+fn assigning_an_invalid_value_fails_validation() {
+    assert_rejected(&runner("function(ctx) ctx.counter = -1 return ctx end"));
+}
+
+#[test]
+fn returning_an_invalid_value_fails_validation() {
+    assert_rejected(&runner("function(ctx) return { counter = -1 } end"));
+}
+
+#[test]
+fn valid_values_and_removal_pass() {
+    let outcome = runner("function(ctx) ctx.counter = 5 return ctx end").execute().unwrap();
+    assert_context!(outcome, "counter" => 5);
+    let outcome = runner("function(ctx) ctx.counter = nil return ctx end").execute().unwrap();
+    assert_eq!(outcome.context.get("counter"), None);
+}
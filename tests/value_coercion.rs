@@ -0,0 +1,58 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lua_chains::{assert_context, ContextValue, LuaChainRunner};
+use mlua::Value as LuaValue;
+
+fn uppercase(_: &str, value: &LuaValue) -> Option<ContextValue> {
+    match ContextValue::from_lua(value)? {
+        ContextValue::Str(s) => Some(ContextValue::Str(s.to_uppercase())),
+        other => Some(other),
+    }
+}
+
+fn runner() -> LuaChainRunner {
+    common::runner(
+        r#"return {
+            context = { name = "ada", n = 1 },
+            events = { { name = "greet", handler = function(ctx)
+                ctx.greeting = "hello " .. ctx.name
+                ctx.secret = "hidden"
+                return ctx
+            end } },
+        }"#,
+    )
+}
+
+#[test]
+fn coercion_uppercases_strings() {
+    let outcome = runner().with_value_coercion(uppercase).execute().unwrap();
+    assert_context!(outcome, "name" => "ADA", "greeting" => "HELLO ADA", "n" => 1);
+}
+
+#[test]
+fn coercion_can_leave_keys_out() {
+    let outcome = runner()
+        .with_value_coercion(|key, value| match key {
+            "secret" => None,
+            _ => ContextValue::from_lua(value),
+        })
+        .execute()
+        .unwrap();
+    assert!(!outcome.context.contains_key("secret"));
+    assert_context!(outcome, "greeting" => "hello ada");
+}
+
+#[test]
+fn checkpoints_see_coerced_values() {
+    let seen = Rc::new(RefCell::new(None));
+    let sink = Rc::clone(&seen);
+    runner()
+        .with_value_coercion(uppercase)
+        .with_checkpoint(move |_, context| *sink.borrow_mut() = context.get("greeting").cloned())
+        .execute()
+        .unwrap();
+    assert_eq!(*seen.borrow(), Some(ContextValue::from("HELLO ADA")));
+}
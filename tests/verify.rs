@@ -0,0 +1,50 @@
+mod common;
+
+use lua_chains::{assert_context, ChainError, ContextMismatch, ContextValue};
+
+fn script(expect: &str) -> String {
+    format!(
+        r#"return {{
+            context = {{ counter = 0, message = "start" }},
+            events = {{ {{ name = "process", handler = function(ctx)
+                ctx.counter = ctx.counter + 1
+                ctx.message = ctx.message .. " -> processed"
+                ctx.extra = true
+                return ctx
+            end }} }},
+            expect = {expect},
+        }}"#
+    )
+}
+
+#[test]
+fn a_correct_chain_verifies() {
+    let runner = common::runner(&script(r#"{ counter = 1, message = "start -> processed" }"#));
+    let outcome = runner.verify().unwrap();
+    assert_context!(outcome, "counter" => 1);
+}
+
+#[test]
+fn a_wrong_expect_reports_each_differing_key() {
+    let runner = common::runner(&script(r#"{ counter = 2, message = "start -> processed", missing = "x" }"#));
+    match runner.verify() {
+        Err(ChainError::ExpectationFailed { mismatches, .. }) => assert_eq!(
+            mismatches,
+            [
+                ContextMismatch {
+                    key: "counter".into(),
+                    expected: ContextValue::Int(2),
+                    actual: Some(ContextValue::Int(1)),
+                },
+                ContextMismatch { key: "missing".into(), expected: ContextValue::from("x"), actual: None },
+            ]
+        ),
+        other => panic!("expected ExpectationFailed, got {other:?}"),
+    }
+}
+
+#[test]
+fn verifying_without_expect_is_an_error() {
+    let runner = common::runner(r#"return { events = {} }"#);
+    assert!(matches!(runner.verify(), Err(ChainError::InvalidDefinition(_))));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use lua_chains::{ContextValue, LuaChainRunner};
+
+const RUNS: usize = 4000;
+
+fn runner(weights: [&str; 2]) -> LuaChainRunner {
+    let events: String = ["a", "b"]
+        .iter()
+        .zip(weights)
+        .map(|(name, weight)| {
+            format!(r#"{{ name = "{name}", {weight} handler = function(ctx) ctx.drawn = "{name}" return ctx end }},"#)
+        })
+        .collect();
+    let script = format!(r#"return {{ execution_mode = "weighted_random", context = {{}}, events = {{ {events} }} }}"#);
+    common::runner(&script).with_seed(42)
+}
+
+// The share of `RUNS` executions that drew event "a".
+fn share_of_a(runner: &LuaChainRunner) -> f64 {
+    let drawn_a = (0..RUNS)
+        .filter(|_| {
+            let outcome = runner.execute().unwrap();
+            assert_eq!(outcome.events_executed, 1);
+            outcome.context.get("drawn") == Some(&ContextValue::Str("a".into()))
+        })
+        .count();
+    drawn_a as f64 / RUNS as f64
+}
+
+#[test]
+fn draws_follow_the_weights() {
+    let share = share_of_a(&runner(["weight = 1,", "weight = 3,"]));
+    assert!((share - 0.25).abs() < 0.03, "a drawn in {share} of runs, expected about 0.25");
+}
+
+#[test]
+fn an_event_without_a_weight_counts_as_one() {
+    let share = share_of_a(&runner(["", "weight = 3,"]));
+    assert!((share - 0.25).abs() < 0.03, "a drawn in {share} of runs, expected about 0.25");
+}
+
+#[test]
+fn equal_weights_draw_evenly() {
+    let share = share_of_a(&runner(["", ""]));
+    assert!((share - 0.5).abs() < 0.03, "a drawn in {share} of runs, expected about 0.5");
+}